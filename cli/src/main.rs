@@ -8,7 +8,7 @@
 //! - Inspect node state
 //! - Manage cluster membership
 
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use anyhow::Result;
 use serde_json::json;
 use std::time::Duration;
@@ -37,6 +37,8 @@ enum Commands {
     Get {
         /// Key to get
         key: String,
+        #[command(flatten)]
+        read: ReadOptions,
         /// Target node address
         #[arg(short, long, default_value = "http://127.0.0.1:8080")]
         address: String,
@@ -67,6 +69,32 @@ enum Commands {
         #[arg(short, long, default_value = "http://127.0.0.1:8080")]
         address: String,
     },
+    /// Add a non-voting learner to the cluster
+    AddLearner {
+        /// ID of the new node
+        node_id: String,
+        /// Raft address of the new node
+        node_address: String,
+        /// Leader address
+        #[arg(short, long, default_value = "http://127.0.0.1:8080")]
+        address: String,
+    },
+    /// Promote a caught-up learner to a voting member
+    Promote {
+        /// ID of the learner
+        node_id: String,
+        /// Leader address
+        #[arg(short, long, default_value = "http://127.0.0.1:8080")]
+        address: String,
+    },
+    /// Hand leadership over to another voting member
+    TransferLeader {
+        /// ID of the node that should become leader
+        node_id: String,
+        /// Leader address
+        #[arg(short, long, default_value = "http://127.0.0.1:8080")]
+        address: String,
+    },
     /// Benchmark the cluster
     Benchmark {
         /// Number of operations to perform
@@ -78,22 +106,40 @@ enum Commands {
         /// Target node address
         #[arg(short, long, default_value = "http://127.0.0.1:8080")]
         address: String,
+        /// Node of a second cluster to run the same workload against and compare
+        /// with, e.g. one running an older build
+        #[arg(long)]
+        baseline: Option<String>,
     },
 }
 
+/// How fresh a read has to be
+#[derive(Args)]
+struct ReadOptions {
+    /// Read consistency; the server's read mode decides when omitted
+    #[arg(long, value_parser = ["linearizable", "lease", "stale", "bounded"])]
+    consistency: Option<String>,
+    /// Bounded reads: maximum committed entries the node may not have applied yet
+    #[arg(long)]
+    max_lag_entries: Option<u64>,
+    /// Bounded reads: maximum milliseconds since the node heard from a leader
+    #[arg(long)]
+    max_lag_ms: Option<u64>,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
         Commands::Set { key, value, address } => {
-            send_kv_command(&address, "SET", &key, Some(&value)).await?;
+            send_kv_command(&address, "SET", &key, Some(&value), None).await?;
         }
-        Commands::Get { key, address } => {
-            send_kv_command(&address, "GET", &key, None).await?;
+        Commands::Get { key, read, address } => {
+            send_kv_command(&address, "GET", &key, None, Some(&read)).await?;
         }
         Commands::Delete { key, address } => {
-            send_kv_command(&address, "DELETE", &key, None).await?;
+            send_kv_command(&address, "DELETE", &key, None, None).await?;
         }
         Commands::Status { address } => {
             get_status(&address).await?;
@@ -104,15 +150,42 @@ async fn main() -> Result<()> {
         Commands::Health { address } => {
             check_health(&address).await?;
         }
-        Commands::Benchmark { operations, clients, address } => {
-            run_benchmark(&address, operations, clients).await?;
+        Commands::AddLearner { node_id, node_address, address } => {
+            let body = json!({ "node_id": node_id, "address": node_address });
+            send_admin_request(&address, "/admin/learners", &body).await?;
+        }
+        Commands::Promote { node_id, address } => {
+            let path = format!("/admin/learners/{}/promote", node_id);
+            send_admin_request(&address, &path, &json!({})).await?;
+        }
+        Commands::TransferLeader { node_id, address } => {
+            let body = json!({ "node_id": node_id });
+            send_admin_request(&address, "/admin/transfer-leader", &body).await?;
+        }
+        Commands::Benchmark { operations, clients, address, baseline } => {
+            let throughput = run_benchmark(&address, operations, clients).await?;
+            if let Some(baseline) = baseline {
+                println!();
+                let baseline_throughput = run_benchmark(&baseline, operations, clients).await?;
+                println!();
+                println!("⚖️  Comparison:");
+                println!("   {}: {:.1} ops/sec", address, throughput);
+                println!("   {} (baseline): {:.1} ops/sec", baseline, baseline_throughput);
+                println!("   Speedup: {:.2}x", throughput / baseline_throughput);
+            }
         }
     }
 
     Ok(())
 }
 
-async fn send_kv_command(address: &str, operation: &str, key: &str, value: Option<&str>) -> Result<()> {
+async fn send_kv_command(
+    address: &str,
+    operation: &str,
+    key: &str,
+    value: Option<&str>,
+    read: Option<&ReadOptions>,
+) -> Result<()> {
     let client = reqwest::Client::new();
 
     let command = match operation {
@@ -125,10 +198,16 @@ async fn send_kv_command(address: &str, operation: &str, key: &str, value: Optio
             })
         }
         "GET" => {
-            json!({
+            let mut command = json!({
                 "type": "GET",
                 "key": key
-            })
+            });
+            if let Some(read) = read {
+                command["consistency"] = json!(read.consistency);
+                command["max_lag_entries"] = json!(read.max_lag_entries);
+                command["max_lag_ms"] = json!(read.max_lag_ms);
+            }
+            command
         }
         "DELETE" => {
             json!({
@@ -167,6 +246,34 @@ async fn send_kv_command(address: &str, operation: &str, key: &str, value: Optio
     Ok(())
 }
 
+async fn send_admin_request(address: &str, path: &str, body: &serde_json::Value) -> Result<()> {
+    let client = reqwest::Client::new();
+    let url = format!("{}{}", address, path);
+
+    let response = client
+        .post(&url)
+        .json(body)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await;
+
+    match response {
+        Ok(resp) => {
+            let result: serde_json::Value = resp.json().await?;
+            if result["success"].as_bool().unwrap_or(false) {
+                println!("✅ Success: {}", serde_json::to_string_pretty(&result)?);
+            } else {
+                println!("❌ Error: {}", result["error"].as_str().unwrap_or("Unknown error"));
+            }
+        }
+        Err(e) => {
+            println!("❌ Failed to connect to {}: {}", address, e);
+        }
+    }
+
+    Ok(())
+}
+
 async fn get_status(address: &str) -> Result<()> {
     let client = reqwest::Client::new();
     let url = format!("{}/status", address);
@@ -249,7 +356,7 @@ async fn check_health(address: &str) -> Result<()> {
     Ok(())
 }
 
-async fn run_benchmark(address: &str, operations: usize, clients: usize) -> Result<()> {
+async fn run_benchmark(address: &str, operations: usize, clients: usize) -> Result<f64> {
     use std::time::Instant;
     use tokio::task::JoinSet;
 
@@ -316,7 +423,7 @@ async fn run_benchmark(address: &str, operations: usize, clients: usize) -> Resu
     println!("   Average latency: {:.2}ms",
              duration.as_millis() as f64 / total_ops as f64);
 
-    Ok(())
+    Ok(ops_per_sec)
 }
 
 async fn run_client_benchmark(client_id: usize, operations: usize, address: &str) -> Result<(usize, usize)> {
@@ -1,39 +1,26 @@
 use std::env;
+use std::path::PathBuf;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Check if protoc is available and try to compile
-    if let Ok(protoc_path) = env::var("PROTOC") {
-        println!("cargo:warning=Using PROTOC from environment: {}", protoc_path);
-        compile_protobuf()?;
-    } else if which::which("protoc").is_ok() {
-        println!("cargo:warning=Found protoc in PATH, compiling protobuf definitions");
-        compile_protobuf()?;
-    } else {
-        println!("cargo:warning=protoc not found in PATH");
-        println!("cargo:warning=Using fallback protobuf implementations");
-        println!("cargo:warning=To enable full protobuf support, install protoc or set PROTOC environment variable");
-        println!("cargo:warning=Project will compile and work with manual implementations");
+    // Use the protoc shipped with protoc-bin-vendored so every machine generates
+    // the same code; PROTOC still overrides it, as it does for prost-build.
+    if env::var_os("PROTOC").is_none() {
+        env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
     }
 
-    Ok(())
-}
+    // Well-known types come from the repo's protoc/include, not the system
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR")?);
+    let well_known = manifest_dir.join("../protoc/include");
+
+    println!("cargo:rerun-if-changed=raft.proto");
+    println!("cargo:rerun-if-env-changed=PROTOC");
 
-fn compile_protobuf() -> Result<(), Box<dyn std::error::Error>> {
-    let result = tonic_build::configure()
+    tonic_build::configure()
         .build_server(true)
         .build_client(true)
-        .compile(&["raft.proto"], &["."]);
-
-    match result {
-        Ok(_) => {
-            println!("cargo:rustc-cfg=feature=\"generated-proto\"");
-            println!("cargo:warning=Successfully compiled protobuf definitions");
-        },
-        Err(e) => {
-            println!("cargo:warning=Failed to compile protobuf: {}", e);
-            println!("cargo:warning=Using fallback implementations");
-        }
-    }
+        // Payloads are shared with the Raft types instead of copied into each message
+        .bytes([".raft.LogEntry.data", ".raft.InstallSnapshotRequest.data"])
+        .compile(&["raft.proto"], &[manifest_dir.as_path(), well_known.as_path()])?;
 
     Ok(())
 }
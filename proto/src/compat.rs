@@ -0,0 +1,157 @@
+//! Wire compatibility tests
+//!
+//! Checks the code generated from `raft.proto` still speaks the frozen wire
+//! format in `fallback`. When `raft.proto` changes, these tests change with
+//! it; `fallback` does not.
+
+use prost::Message;
+
+use crate::fallback;
+use crate::raft as generated;
+
+/// Encode `message` with the generated type and check the hand-written one
+/// decodes it and encodes the same bytes back
+macro_rules! assert_wire_compatible {
+    ($name:ident, $message:expr) => {{
+        let message: generated::$name = $message;
+        let bytes = message.encode_to_vec();
+
+        let fallback = fallback::$name::decode(bytes.as_slice())
+            .unwrap_or_else(|e| panic!("{} does not decode: {}", stringify!($name), e));
+        assert_eq!(fallback.encode_to_vec(), bytes, "{} encodes differently", stringify!($name));
+        assert_eq!(generated::$name::decode(bytes.as_slice()).unwrap(), message);
+    }};
+}
+
+fn node(node_id: &str, voting: bool) -> generated::NodeInfo {
+    generated::NodeInfo {
+        node_id: node_id.to_string(),
+        address: format!("{}:50051", node_id),
+        voting,
+        grpc_address: None,
+    }
+}
+
+fn config() -> generated::ClusterConfig {
+    generated::ClusterConfig {
+        nodes: vec![node("node-1", true), node("node-4", false)],
+        config_index: 12,
+        old_nodes: vec![node("node-1", true), node("node-2", true)],
+    }
+}
+
+#[test]
+fn test_vote_messages_are_wire_compatible() {
+    assert_wire_compatible!(RequestVoteRequest, generated::RequestVoteRequest {
+        term: 7,
+        candidate_id: "node-2".to_string(),
+        last_log_index: 41,
+        last_log_term: 6,
+        leadership_transfer: true,
+    });
+    assert_wire_compatible!(RequestVoteResponse, generated::RequestVoteResponse {
+        term: 7,
+        vote_granted: true,
+    });
+    assert_wire_compatible!(PreVoteRequest, generated::PreVoteRequest {
+        term: 8,
+        candidate_id: "node-3".to_string(),
+        last_log_index: 41,
+        last_log_term: 6,
+    });
+    assert_wire_compatible!(PreVoteResponse, generated::PreVoteResponse {
+        term: 7,
+        vote_granted: true,
+    });
+    assert_wire_compatible!(TimeoutNowRequest, generated::TimeoutNowRequest {
+        term: 7,
+        leader_id: "node-1".to_string(),
+    });
+    assert_wire_compatible!(TimeoutNowResponse, generated::TimeoutNowResponse { term: 7 });
+}
+
+#[test]
+fn test_replication_messages_are_wire_compatible() {
+    let entry = |index, entry_type: generated::EntryType| generated::LogEntry {
+        index,
+        term: 3,
+        entry_type: entry_type as i32,
+        data: vec![1, 2, 3].into(),
+        client_id: Some("client-9".to_string()),
+        sequence_number: Some(index * 2),
+    };
+
+    assert_wire_compatible!(LogEntry, entry(4, generated::EntryType::Configuration));
+    assert_wire_compatible!(AppendEntriesRequest, generated::AppendEntriesRequest {
+        term: 3,
+        leader_id: "node-1".to_string(),
+        prev_log_index: 3,
+        prev_log_term: 2,
+        entries: vec![
+            entry(4, generated::EntryType::Command),
+            entry(5, generated::EntryType::NoOp),
+        ],
+        leader_commit: 3,
+    });
+    assert_wire_compatible!(AppendEntriesResponse, generated::AppendEntriesResponse {
+        term: 3,
+        success: false,
+        conflict_index: 2,
+        conflict_term: 1,
+    });
+    assert_wire_compatible!(InstallSnapshotRequest, generated::InstallSnapshotRequest {
+        term: 3,
+        leader_id: "node-1".to_string(),
+        last_included_index: 100,
+        last_included_term: 2,
+        offset: 65536,
+        data: vec![0xff; 16].into(),
+        done: true,
+        config: Some(config()),
+    });
+    assert_wire_compatible!(InstallSnapshotResponse, generated::InstallSnapshotResponse { term: 3 });
+    assert_wire_compatible!(ClusterConfig, config());
+    assert_wire_compatible!(NodeInfo, node("node-5", false));
+}
+
+#[test]
+fn test_client_messages_are_wire_compatible() {
+    assert_wire_compatible!(SubmitCommandRequest, generated::SubmitCommandRequest {
+        command: br#"{"Set":{"key":"a","value":"b"}}"#.to_vec(),
+        client_id: "client-9".to_string(),
+        sequence_number: 17,
+    });
+    assert_wire_compatible!(SubmitCommandResponse, generated::SubmitCommandResponse {
+        success: false,
+        error: "Not the leader".to_string(),
+        result: b"b".to_vec(),
+        leader_id: "node-2".to_string(),
+    });
+    assert_wire_compatible!(GetStatusRequest, generated::GetStatusRequest {});
+    assert_wire_compatible!(GetStatusResponse, generated::GetStatusResponse {
+        state: generated::NodeState::PreCandidate as i32,
+        current_term: 9,
+        node_id: "node-3".to_string(),
+        leader_id: "node-1".to_string(),
+        commit_index: 40,
+        last_applied: 39,
+        log_length: 42,
+        peers: vec!["node-1".to_string(), "node-2".to_string()],
+    });
+}
+
+#[test]
+fn test_enums_are_wire_compatible() {
+    for value in 0..3 {
+        let generated = generated::EntryType::try_from(value).unwrap();
+        let fallback = fallback::EntryType::try_from(value).unwrap();
+        assert_eq!(format!("{:?}", generated), format!("{:?}", fallback));
+    }
+    for value in 0..4 {
+        let generated = generated::NodeState::try_from(value).unwrap();
+        let fallback = fallback::NodeState::try_from(value).unwrap();
+        assert_eq!(format!("{:?}", generated), format!("{:?}", fallback));
+    }
+    assert!(generated::EntryType::try_from(3).is_err());
+    assert!(generated::NodeState::try_from(4).is_err());
+}
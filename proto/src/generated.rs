@@ -1 +0,0 @@
-// Protobuf generation skipped
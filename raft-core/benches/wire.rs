@@ -0,0 +1,86 @@
+//! Throughput of the binary encoding against JSON for peer RPCs and log entries
+//!
+//! Run with `cargo bench -p raft-core --bench wire`. The encoded size of each
+//! message is printed before its group runs.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use raft_core::codec::{self, WireMessage};
+use raft_core::{AppendRequest, EntryType, LogEntry, VoteRequest};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// A log entry carrying a serialized `SET` command, as the server writes them
+fn command_entry(index: u64) -> LogEntry {
+    let command = format!(r#"{{"Set":{{"key":"user:{}","value":"{}"}}}}"#, index, "x".repeat(64));
+    LogEntry {
+        index,
+        term: 3,
+        entry_type: EntryType::Command,
+        data: command.into_bytes().into(),
+        client_id: Some("42".to_string()),
+        sequence_number: Some(index),
+    }
+}
+
+fn append_request(entries: u64) -> AppendRequest {
+    AppendRequest {
+        term: 3,
+        leader_id: "node-1".to_string(),
+        prev_log_index: 1000,
+        prev_log_term: 3,
+        entries: (1001..1001 + entries).map(command_entry).collect(),
+        leader_commit: 1000,
+    }
+}
+
+fn vote_request() -> VoteRequest {
+    VoteRequest {
+        term: 4,
+        candidate_id: "node-2".to_string(),
+        last_log_index: 1100,
+        last_log_term: 3,
+        leadership_transfer: false,
+    }
+}
+
+/// Benchmark encoding then decoding `message` both ways, counting `elements` per iteration
+fn roundtrip<T>(c: &mut Criterion, group: &str, elements: u64, message: &T)
+where
+    T: WireMessage + Serialize + DeserializeOwned,
+{
+    let json_len = serde_json::to_vec(message).unwrap().len();
+    let binary_len = codec::encode(message).len();
+    println!("{}: json {} bytes, binary {} bytes", group, json_len, binary_len);
+
+    let mut group = c.benchmark_group(group);
+    group.throughput(Throughput::Elements(elements));
+    group.bench_with_input(BenchmarkId::new("json", json_len), message, |b, message| {
+        b.iter(|| {
+            let bytes = serde_json::to_vec(black_box(message)).unwrap();
+            serde_json::from_slice::<T>(&bytes).unwrap()
+        })
+    });
+    group.bench_with_input(BenchmarkId::new("binary", binary_len), message, |b, message| {
+        b.iter(|| {
+            let bytes = codec::encode(black_box(message));
+            codec::decode::<T>(&bytes).unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn bench_append_request(c: &mut Criterion) {
+    for entries in [1, 100] {
+        roundtrip(c, &format!("append_request_{}", entries), entries, &append_request(entries));
+    }
+}
+
+fn bench_vote_request(c: &mut Criterion) {
+    roundtrip(c, "vote_request", 1, &vote_request());
+}
+
+fn bench_log_entry(c: &mut Criterion) {
+    roundtrip(c, "log_entry", 1, &command_entry(1));
+}
+
+criterion_group!(benches, bench_append_request, bench_vote_request, bench_log_entry);
+criterion_main!(benches);
@@ -0,0 +1,396 @@
+//! Binary encoding of peer RPCs and log entries
+//!
+//! A message is one version byte followed by its protobuf encoding from
+//! `proto/raft.proto`. Byte vectors such as `LogEntry::data` travel as raw
+//! bytes rather than the JSON arrays of numbers serde_json produces. A node
+//! that does not know a message's version refuses it, so the sender can fall
+//! back to JSON, which every release understands.
+//!
+//! Only the envelope is binary. What `LogEntry::data` and snapshot data hold
+//! is up to the application and is carried as opaque bytes; the server still
+//! writes its state machine commands and session table there as JSON.
+
+use proto::raft as pb;
+use prost::Message;
+
+use crate::error::RaftError;
+use crate::types::*;
+use crate::RaftResult;
+
+/// Version of the binary encoding this release writes
+pub const WIRE_VERSION: u8 = 1;
+
+/// A Raft type with a binary encoding through its protobuf message
+///
+/// Decoding fails for messages this release cannot represent, such as an
+/// entry type added by a newer release.
+pub trait WireMessage: Sized {
+    /// The protobuf message from `raft.proto` carrying this type
+    type Proto: Message + Default + for<'a> From<&'a Self>;
+
+    /// Convert a decoded protobuf message back into this type
+    fn from_proto(proto: Self::Proto) -> RaftResult<Self>;
+}
+
+macro_rules! wire_message {
+    ($($raft:ty => $proto:ty),* $(,)?) => {
+        $(impl WireMessage for $raft {
+            type Proto = $proto;
+
+            fn from_proto(proto: $proto) -> RaftResult<Self> {
+                Self::try_from(proto).map_err(Into::into)
+            }
+        })*
+    };
+}
+
+wire_message! {
+    VoteRequest => pb::RequestVoteRequest,
+    VoteResponse => pb::RequestVoteResponse,
+    PreVoteRequest => pb::PreVoteRequest,
+    PreVoteResponse => pb::PreVoteResponse,
+    AppendRequest => pb::AppendEntriesRequest,
+    AppendResponse => pb::AppendEntriesResponse,
+    InstallSnapshotRequest => pb::InstallSnapshotRequest,
+    InstallSnapshotResponse => pb::InstallSnapshotResponse,
+    TimeoutNowRequest => pb::TimeoutNowRequest,
+    TimeoutNowResponse => pb::TimeoutNowResponse,
+    LogEntry => pb::LogEntry,
+}
+
+/// Encode a message as `[WIRE_VERSION][protobuf]`
+///
+/// Payloads are shared with `message` rather than copied into the protobuf
+/// message; only IDs and other small fields are cloned.
+pub fn encode<T: WireMessage>(message: &T) -> Vec<u8> {
+    let message = T::Proto::from(message);
+    let mut buffer = Vec::with_capacity(1 + message.encoded_len());
+    buffer.push(WIRE_VERSION);
+    message.encode_raw(&mut buffer);
+    buffer
+}
+
+/// Decode a message written by [`encode`]
+///
+/// Fails with `RaftError::UnsupportedWireVersion` if the version byte is not
+/// one this release can read, and with `RaftError::Decode` if the message
+/// holds a value it does not know.
+pub fn decode<T: WireMessage>(bytes: &[u8]) -> RaftResult<T> {
+    let Some((&version, body)) = bytes.split_first() else {
+        return Err(RaftError::Decode("empty message".to_string()));
+    };
+    if version != WIRE_VERSION {
+        return Err(RaftError::UnsupportedWireVersion { version });
+    }
+
+    let message = T::Proto::decode(body).map_err(|e| RaftError::Decode(e.to_string()))?;
+    T::from_proto(message)
+}
+
+// Conversions between the Raft types and their protobuf messages, shared with
+// the gRPC transport and server
+
+impl From<&VoteRequest> for pb::RequestVoteRequest {
+    fn from(request: &VoteRequest) -> Self {
+        Self {
+            term: request.term,
+            candidate_id: request.candidate_id.clone(),
+            last_log_index: request.last_log_index,
+            last_log_term: request.last_log_term,
+            leadership_transfer: request.leadership_transfer,
+        }
+    }
+}
+
+impl From<pb::RequestVoteRequest> for VoteRequest {
+    fn from(request: pb::RequestVoteRequest) -> Self {
+        Self {
+            term: request.term,
+            candidate_id: request.candidate_id,
+            last_log_index: request.last_log_index,
+            last_log_term: request.last_log_term,
+            leadership_transfer: request.leadership_transfer,
+        }
+    }
+}
+
+impl From<&VoteResponse> for pb::RequestVoteResponse {
+    fn from(response: &VoteResponse) -> Self {
+        Self {
+            term: response.term,
+            vote_granted: response.vote_granted,
+        }
+    }
+}
+
+impl From<pb::RequestVoteResponse> for VoteResponse {
+    fn from(response: pb::RequestVoteResponse) -> Self {
+        Self {
+            term: response.term,
+            vote_granted: response.vote_granted,
+        }
+    }
+}
+
+impl From<&PreVoteRequest> for pb::PreVoteRequest {
+    fn from(request: &PreVoteRequest) -> Self {
+        Self {
+            term: request.term,
+            candidate_id: request.candidate_id.clone(),
+            last_log_index: request.last_log_index,
+            last_log_term: request.last_log_term,
+        }
+    }
+}
+
+impl From<pb::PreVoteRequest> for PreVoteRequest {
+    fn from(request: pb::PreVoteRequest) -> Self {
+        Self {
+            term: request.term,
+            candidate_id: request.candidate_id,
+            last_log_index: request.last_log_index,
+            last_log_term: request.last_log_term,
+        }
+    }
+}
+
+impl From<&PreVoteResponse> for pb::PreVoteResponse {
+    fn from(response: &PreVoteResponse) -> Self {
+        Self {
+            term: response.term,
+            vote_granted: response.vote_granted,
+        }
+    }
+}
+
+impl From<pb::PreVoteResponse> for PreVoteResponse {
+    fn from(response: pb::PreVoteResponse) -> Self {
+        Self {
+            term: response.term,
+            vote_granted: response.vote_granted,
+        }
+    }
+}
+
+impl From<EntryType> for pb::EntryType {
+    fn from(entry_type: EntryType) -> Self {
+        match entry_type {
+            EntryType::Command => pb::EntryType::Command,
+            EntryType::Configuration => pb::EntryType::Configuration,
+            EntryType::NoOp => pb::EntryType::NoOp,
+        }
+    }
+}
+
+impl From<&LogEntry> for pb::LogEntry {
+    fn from(entry: &LogEntry) -> Self {
+        Self {
+            index: entry.index,
+            term: entry.term,
+            entry_type: pb::EntryType::from(entry.entry_type) as i32,
+            data: entry.data.clone(),
+            client_id: entry.client_id.clone(),
+            sequence_number: entry.sequence_number,
+        }
+    }
+}
+
+/// An entry type from a newer release is refused rather than applied as
+/// something else
+impl TryFrom<pb::LogEntry> for LogEntry {
+    type Error = RaftError;
+
+    fn try_from(entry: pb::LogEntry) -> Result<Self, Self::Error> {
+        let entry_type = match pb::EntryType::try_from(entry.entry_type) {
+            Ok(pb::EntryType::Command) => EntryType::Command,
+            Ok(pb::EntryType::Configuration) => EntryType::Configuration,
+            Ok(pb::EntryType::NoOp) => EntryType::NoOp,
+            Err(_) => return Err(RaftError::Decode(format!("unknown entry type {}", entry.entry_type))),
+        };
+
+        Ok(Self {
+            index: entry.index,
+            term: entry.term,
+            entry_type,
+            data: entry.data,
+            client_id: entry.client_id,
+            sequence_number: entry.sequence_number,
+        })
+    }
+}
+
+impl From<&AppendRequest> for pb::AppendEntriesRequest {
+    fn from(request: &AppendRequest) -> Self {
+        Self {
+            term: request.term,
+            leader_id: request.leader_id.clone(),
+            prev_log_index: request.prev_log_index,
+            prev_log_term: request.prev_log_term,
+            entries: request.entries.iter().map(Into::into).collect(),
+            leader_commit: request.leader_commit,
+        }
+    }
+}
+
+impl TryFrom<pb::AppendEntriesRequest> for AppendRequest {
+    type Error = RaftError;
+
+    fn try_from(request: pb::AppendEntriesRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            term: request.term,
+            leader_id: request.leader_id,
+            prev_log_index: request.prev_log_index,
+            prev_log_term: request.prev_log_term,
+            entries: request.entries.into_iter().map(TryInto::try_into).collect::<RaftResult<_>>()?,
+            leader_commit: request.leader_commit,
+        })
+    }
+}
+
+impl From<&AppendResponse> for pb::AppendEntriesResponse {
+    fn from(response: &AppendResponse) -> Self {
+        Self {
+            term: response.term,
+            success: response.success,
+            conflict_index: response.conflict_index.unwrap_or(0),
+            conflict_term: response.conflict_term.unwrap_or(0),
+        }
+    }
+}
+
+impl From<pb::AppendEntriesResponse> for AppendResponse {
+    fn from(response: pb::AppendEntriesResponse) -> Self {
+        Self {
+            term: response.term,
+            success: response.success,
+            conflict_index: (response.conflict_index != 0).then_some(response.conflict_index),
+            conflict_term: (response.conflict_term != 0).then_some(response.conflict_term),
+        }
+    }
+}
+
+impl From<&PeerInfo> for pb::NodeInfo {
+    fn from(peer: &PeerInfo) -> Self {
+        Self {
+            node_id: peer.node_id.clone(),
+            address: peer.address.clone(),
+            voting: peer.voting,
+            grpc_address: peer.grpc_address.clone(),
+        }
+    }
+}
+
+impl From<pb::NodeInfo> for PeerInfo {
+    fn from(node: pb::NodeInfo) -> Self {
+        Self {
+            node_id: node.node_id,
+            address: node.address,
+            voting: node.voting,
+            grpc_address: node.grpc_address,
+        }
+    }
+}
+
+impl From<&ClusterConfig> for pb::ClusterConfig {
+    fn from(config: &ClusterConfig) -> Self {
+        Self {
+            nodes: config.nodes.iter().map(Into::into).collect(),
+            config_index: config.config_index,
+            old_nodes: config.old_nodes.iter().flatten().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<pb::ClusterConfig> for ClusterConfig {
+    fn from(config: pb::ClusterConfig) -> Self {
+        Self {
+            nodes: config.nodes.into_iter().map(Into::into).collect(),
+            old_nodes: (!config.old_nodes.is_empty())
+                .then(|| config.old_nodes.into_iter().map(Into::into).collect()),
+            config_index: config.config_index,
+        }
+    }
+}
+
+impl From<&InstallSnapshotRequest> for pb::InstallSnapshotRequest {
+    fn from(request: &InstallSnapshotRequest) -> Self {
+        Self {
+            term: request.term,
+            leader_id: request.leader_id.clone(),
+            last_included_index: request.last_included_index,
+            last_included_term: request.last_included_term,
+            offset: request.offset,
+            data: request.data.clone(),
+            done: request.done,
+            config: request.config.as_ref().map(Into::into),
+        }
+    }
+}
+
+impl From<pb::InstallSnapshotRequest> for InstallSnapshotRequest {
+    fn from(request: pb::InstallSnapshotRequest) -> Self {
+        Self {
+            term: request.term,
+            leader_id: request.leader_id,
+            last_included_index: request.last_included_index,
+            last_included_term: request.last_included_term,
+            config: request.config.map(Into::into),
+            offset: request.offset,
+            data: request.data,
+            done: request.done,
+        }
+    }
+}
+
+impl From<&InstallSnapshotResponse> for pb::InstallSnapshotResponse {
+    fn from(response: &InstallSnapshotResponse) -> Self {
+        Self { term: response.term }
+    }
+}
+
+impl From<pb::InstallSnapshotResponse> for InstallSnapshotResponse {
+    fn from(response: pb::InstallSnapshotResponse) -> Self {
+        Self { term: response.term }
+    }
+}
+
+impl From<&TimeoutNowRequest> for pb::TimeoutNowRequest {
+    fn from(request: &TimeoutNowRequest) -> Self {
+        Self {
+            term: request.term,
+            leader_id: request.leader_id.clone(),
+        }
+    }
+}
+
+impl From<pb::TimeoutNowRequest> for TimeoutNowRequest {
+    fn from(request: pb::TimeoutNowRequest) -> Self {
+        Self {
+            term: request.term,
+            leader_id: request.leader_id,
+        }
+    }
+}
+
+impl From<&TimeoutNowResponse> for pb::TimeoutNowResponse {
+    fn from(response: &TimeoutNowResponse) -> Self {
+        Self { term: response.term }
+    }
+}
+
+impl From<pb::TimeoutNowResponse> for TimeoutNowResponse {
+    fn from(response: pb::TimeoutNowResponse) -> Self {
+        Self { term: response.term }
+    }
+}
+
+impl From<NodeState> for pb::NodeState {
+    fn from(state: NodeState) -> Self {
+        match state {
+            NodeState::Follower => pb::NodeState::Follower,
+            NodeState::PreCandidate => pb::NodeState::PreCandidate,
+            NodeState::Candidate => pb::NodeState::Candidate,
+            NodeState::Leader => pb::NodeState::Leader,
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use crate::types::*;
-use crate::error::RaftError;
 use crate::RaftResult;
 
 /// Election management for Raft nodes
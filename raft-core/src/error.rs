@@ -9,6 +9,12 @@ pub enum RaftError {
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
     
+    #[error("Decode error: {0}")]
+    Decode(String),
+    
+    #[error("Unsupported wire version {version}")]
+    UnsupportedWireVersion { version: u8 },
+    
     #[error("Invalid state transition from {from} to {to}")]
     InvalidStateTransition { from: String, to: String },
     
@@ -27,9 +33,35 @@ pub enum RaftError {
     #[error("Not leader")]
     NotLeader,
     
+    #[error("Leadership transfer to {target} in progress")]
+    TransferInProgress { target: String },
+    
+    #[error("Leadership transfer to {target} timed out")]
+    TransferTimeout { target: String },
+    
+    #[error("Leader has not committed an entry in its current term yet")]
+    ReadIndexNotReady,
+    
     #[error("Network error: {0}")]
     Network(String),
     
     #[error("Configuration error: {0}")]
     Configuration(String),
+    
+    #[error("Storage error: {0}")]
+    Storage(String),
+    
+    #[error("Raft event loop unavailable")]
+    Unavailable,
+    
+    /// The event queue or the uncommitted log is full; retry after a backoff
+    #[error("Overloaded: {0}")]
+    Overloaded(String),
+}
+
+/// Lets conversions that cannot fail stand in where a `RaftError` is expected
+impl From<std::convert::Infallible> for RaftError {
+    fn from(never: std::convert::Infallible) -> Self {
+        match never {}
+    }
 }
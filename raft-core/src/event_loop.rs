@@ -1,12 +1,21 @@
 use crate::types::*;
 use crate::node::RaftNode;
 use crate::error::RaftError;
+use crate::handle::RaftHandle;
+use crate::replication::{Progress, ReplicationRequest};
+use crate::replicator::{Assignment, Replicator, ReplicatorEvent, ReplicatorHandle};
+use crate::transport::{HttpTransport, Transport};
 use crate::RaftResult;
-use std::sync::Arc;
-use tokio::sync::{RwLock, mpsc};
-use tokio::time::{interval, Duration};
+use tokio::sync::{mpsc, watch};
+use tokio::time::{interval, Duration, Instant};
 use tracing::{info, warn, error, debug};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+
+/// Where the answer to a `RaftEvent::ReadIndex` goes
+type ReadIndexResponder = tokio::sync::oneshot::Sender<RaftResult<(LogIndex, ReadMode)>>;
+
+/// Where the answer to a `RaftEvent::SubmitCommand` goes
+type SubmitResponder = tokio::sync::oneshot::Sender<RaftResult<(LogIndex, Term)>>;
 
 /// Events that can be sent to the Raft event loop
 #[derive(Debug)]
@@ -16,126 +25,230 @@ pub enum RaftEvent {
         request: VoteRequest,
         response_tx: tokio::sync::oneshot::Sender<VoteResponse>,
     },
+    /// Pre-vote request from another node
+    PreVoteRequest {
+        request: PreVoteRequest,
+        response_tx: tokio::sync::oneshot::Sender<PreVoteResponse>,
+    },
     /// Append entries request from leader
     AppendRequest {
         request: AppendRequest,
         response_tx: tokio::sync::oneshot::Sender<AppendResponse>,
     },
-    /// Submit a command to the cluster
+    /// Install snapshot chunk from leader
+    InstallSnapshot {
+        request: InstallSnapshotRequest,
+        response_tx: tokio::sync::oneshot::Sender<InstallSnapshotResponse>,
+    },
+    /// TimeoutNow from the leader handing leadership over to us
+    TimeoutNow {
+        request: TimeoutNowRequest,
+        response_tx: tokio::sync::oneshot::Sender<TimeoutNowResponse>,
+    },
+    /// Submit a command to the cluster; replies with the log index and term it
+    /// was appended at. Commands submitted within one tick share a single append.
     SubmitCommand {
         command: Vec<u8>,
+        client_id: Option<String>,
+        sequence_number: Option<u64>,
+        response_tx: SubmitResponder,
+    },
+    /// Change cluster membership to the given nodes (leaders only); replies with
+    /// the index of the joint configuration entry
+    ChangeMembership {
+        nodes: Vec<PeerInfo>,
+        response_tx: tokio::sync::oneshot::Sender<RaftResult<LogIndex>>,
+    },
+    /// Add a non-voting learner (leaders only); replies with the configuration entry index
+    AddLearner {
+        learner: PeerInfo,
+        response_tx: tokio::sync::oneshot::Sender<RaftResult<LogIndex>>,
+    },
+    /// Promote a caught-up learner to a voter (leaders only); replies with the
+    /// index of the joint configuration entry
+    PromoteLearner {
+        node_id: NodeId,
         response_tx: tokio::sync::oneshot::Sender<RaftResult<LogIndex>>,
     },
+    /// Confirm leadership for a linearizable read (leaders only); replies with
+    /// the index `last_applied` must reach before the read is served and how
+    /// leadership was confirmed. With `allow_lease` a valid lease answers at once.
+    ReadIndex {
+        allow_lease: bool,
+        response_tx: tokio::sync::oneshot::Sender<RaftResult<(LogIndex, ReadMode)>>,
+    },
+    /// Hand leadership over to another voter (leaders only); replies once a
+    /// newer term has started, or with an error if the transfer gave up
+    TransferLeadership {
+        target: NodeId,
+        response_tx: tokio::sync::oneshot::Sender<RaftResult<()>>,
+    },
     /// Get current status
     GetStatus {
         response_tx: tokio::sync::oneshot::Sender<NodeStatus>,
     },
+    /// Get how long ago this node last heard from a leader, if it knows one
+    GetLeaderContact {
+        response_tx: tokio::sync::oneshot::Sender<Option<Duration>>,
+    },
+    /// Get the committed entries that have not been applied yet
+    GetEntriesToApply {
+        response_tx: tokio::sync::oneshot::Sender<Vec<LogEntry>>,
+    },
+    /// Get the snapshot to restore the state machine from, if it is behind one
+    GetSnapshotToRestore {
+        response_tx: tokio::sync::oneshot::Sender<Option<Snapshot>>,
+    },
+    /// Record that the state machine has applied the log up to `index`
+    SetLastApplied {
+        index: LogIndex,
+    },
+    /// Replace the log up to `index` with a snapshot of the state machine
+    Compact {
+        index: LogIndex,
+        data: Vec<u8>,
+        response_tx: tokio::sync::oneshot::Sender<RaftResult<()>>,
+    },
     /// Shutdown the event loop
     Shutdown,
 }
 
 /// Current status of a Raft node
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct NodeStatus {
     pub node_id: NodeId,
     pub state: NodeState,
     pub current_term: Term,
     pub leader_id: Option<NodeId>,
+    /// Address of the leader, from the cluster configuration
+    pub leader_address: Option<String>,
     pub commit_index: LogIndex,
     pub last_applied: LogIndex,
     pub log_length: usize,
     pub peers: Vec<String>,
+    pub learners: Vec<String>,
 }
 
 /// Raft event loop that coordinates all Raft operations
-pub struct RaftEventLoop {
-    node: Arc<RwLock<RaftNode>>,
-    event_rx: mpsc::UnboundedReceiver<RaftEvent>,
-    peer_clients: HashMap<NodeId, RaftPeerClient>,
+///
+/// The loop owns its `RaftNode`: every other task reaches the node through a
+/// [`RaftHandle`], so state transitions never interleave. Peer RPCs go through
+/// the transport `T`, HTTP/JSON unless chosen otherwise; their answers come
+/// back to the loop as events rather than being awaited in it.
+pub struct RaftEventLoop<T: Transport = HttpTransport> {
+    node: RaftNode,
+    event_rx: mpsc::Receiver<RaftEvent>,
+    transport: T,
+    /// The other members of the active configuration
+    peers: HashMap<NodeId, PeerInfo>,
+    status_tx: watch::Sender<NodeStatus>,
+    /// When each peer last answered us while we were leader (CheckQuorum)
+    last_heard: HashMap<NodeId, Instant>,
+    /// Term and start of the leadership `last_heard` belongs to
+    leadership: Option<(Term, Instant)>,
+    /// Caller waiting on the leadership transfer started in the given term
+    transfer_tx: Option<(Term, tokio::sync::oneshot::Sender<RaftResult<()>>)>,
+    /// Where vote and pre-vote tasks report the answers they collect
+    ballot_tx: mpsc::UnboundedSender<Ballot>,
+    ballot_rx: mpsc::UnboundedReceiver<Ballot>,
+    /// Where replicators report to the event loop, and where it hears them
+    replicator_tx: mpsc::UnboundedSender<ReplicatorEvent>,
+    replicator_rx: mpsc::UnboundedReceiver<ReplicatorEvent>,
+    /// Replicator of each peer while we lead in `replicators_term`
+    replicators: HashMap<NodeId, ReplicatorHandle>,
+    replicators_term: Term,
+    /// Latest heartbeat round, and when each round not yet confirmed started
+    round: u64,
+    rounds_started: VecDeque<(u64, Instant)>,
+    /// Latest round each peer has answered in our term
+    acked_rounds: HashMap<NodeId, u64>,
+    /// Read indexes waiting for a quorum to answer the given heartbeat round
+    pending_reads: Vec<(u64, LogIndex, ReadIndexResponder)>,
+    /// Reads waiting for the NoOp appended on election to commit, and whether
+    /// a lease may answer them
+    blocked_reads: Vec<(bool, ReadIndexResponder)>,
+    /// Term of the leader lease and when it expires
+    lease: Option<(Term, Instant)>,
+    /// Commands waiting for the next tick to be appended as one batch
+    queued_commands: Vec<(Vec<u8>, Option<String>, Option<u64>)>,
+    /// Where the answer for each queued command goes, in the same order
+    queued_responders: Vec<SubmitResponder>,
+    /// Uncommitted entries and bytes in the log when the first queued command
+    /// arrived, plus the queued commands themselves
+    queued_load: (usize, usize),
 }
 
-/// Client for communicating with peer nodes
-pub struct RaftPeerClient {
-    node_id: NodeId,
-    address: String,
-    client: reqwest::Client,
+/// A peer's answer to a vote or pre-vote request
+enum Ballot {
+    Vote(NodeId, VoteResponse),
+    PreVote(NodeId, PreVoteResponse),
 }
 
-impl RaftPeerClient {
-    pub fn new(node_id: NodeId, address: String) -> Self {
-        Self {
-            node_id,
-            address,
-            client: reqwest::Client::new(),
-        }
-    }
-    
-    /// Send a vote request to this peer
-    pub async fn request_vote(&self, request: &VoteRequest) -> RaftResult<VoteResponse> {
-        let url = format!("{}/raft/vote", self.address);
-        
-        let response = self.client
-            .post(&url)
-            .json(request)
-            .timeout(Duration::from_millis(1000))
-            .send()
-            .await
-            .map_err(|e| RaftError::Network(e.to_string()))?;
-            
-        if response.status().is_success() {
-            let vote_response: VoteResponse = response
-                .json()
-                .await
-                .map_err(|e| RaftError::Network(e.to_string()))?;
-            Ok(vote_response)
-        } else {
-            Err(RaftError::Network(format!("HTTP {}", response.status())))
-        }
-    }
-    
-    /// Send an append entries request to this peer
-    pub async fn append_entries(&self, request: &AppendRequest) -> RaftResult<AppendResponse> {
-        let url = format!("{}/raft/append", self.address);
-        
-        let response = self.client
-            .post(&url)
-            .json(request)
-            .timeout(Duration::from_millis(2000))
-            .send()
-            .await
-            .map_err(|e| RaftError::Network(e.to_string()))?;
-            
-        if response.status().is_success() {
-            let append_response: AppendResponse = response
-                .json()
-                .await
-                .map_err(|e| RaftError::Network(e.to_string()))?;
-            Ok(append_response)
-        } else {
-            Err(RaftError::Network(format!("HTTP {}", response.status())))
-        }
-    }
-}
+impl<T: Transport> RaftEventLoop<T> {
+    /// Create a new Raft event loop owning `node` and sending peer RPCs over
+    /// `transport`, and the handle through which everything else reaches it
+    pub fn new(node: RaftNode, transport: T) -> (Self, RaftHandle) {
+        let (status_tx, _) = watch::channel(NodeStatus {
+            node_id: NodeId::new(),
+            state: NodeState::Follower,
+            current_term: 0,
+            leader_id: None,
+            leader_address: None,
+            commit_index: 0,
+            last_applied: 0,
+            log_length: 0,
+            peers: vec![],
+            learners: vec![],
+        });
+        let (event_tx, event_rx) = mpsc::channel(node.event_queue_capacity());
+        let (ballot_tx, ballot_rx) = mpsc::unbounded_channel();
+        let (replicator_tx, replicator_rx) = mpsc::unbounded_channel();
+        let handle = RaftHandle::new(event_tx, status_tx.subscribe());
 
-impl RaftEventLoop {
-    /// Create a new Raft event loop
-    pub fn new(
-        node: Arc<RwLock<RaftNode>>,
-        event_rx: mpsc::UnboundedReceiver<RaftEvent>,
-    ) -> Self {
-        Self {
+        let event_loop = Self {
             node,
             event_rx,
-            peer_clients: HashMap::new(),
-        }
+            transport,
+            peers: HashMap::new(),
+            status_tx,
+            last_heard: HashMap::new(),
+            leadership: None,
+            transfer_tx: None,
+            ballot_tx,
+            ballot_rx,
+            replicator_tx,
+            replicator_rx,
+            replicators: HashMap::new(),
+            replicators_term: 0,
+            round: 0,
+            rounds_started: VecDeque::new(),
+            acked_rounds: HashMap::new(),
+            pending_reads: Vec::new(),
+            blocked_reads: Vec::new(),
+            lease: None,
+            queued_commands: Vec::new(),
+            queued_responders: Vec::new(),
+            queued_load: (0, 0),
+        };
+        (event_loop, handle)
+    }
+
+    /// Subscribe to status changes
+    ///
+    /// The receiver is notified whenever the term, role, leader, commit index or
+    /// log length changes, which lets other tasks react to commits and leadership
+    /// changes without polling.
+    pub fn subscribe(&self) -> watch::Receiver<NodeStatus> {
+        self.status_tx.subscribe()
     }
     
-    /// Initialize peer clients
-    pub async fn initialize_peers(&mut self, peers: &[String]) {
-        for (i, peer_addr) in peers.iter().enumerate() {
-            let node_id = format!("node-{}", i + 1);
-            let client = RaftPeerClient::new(node_id.clone(), peer_addr.clone());
-            self.peer_clients.insert(node_id, client);
+    /// Initialize peers, keyed by each peer's node ID
+    ///
+    /// Peers are kept in line with the cluster configuration as it changes;
+    /// this only adds them up front. An entry for this node itself is skipped.
+    pub fn initialize_peers(&mut self, peers: &[PeerInfo]) {
+        for peer in peers.iter().filter(|peer| &peer.node_id != self.node.node_id()) {
+            self.peers.insert(peer.node_id.clone(), peer.clone());
         }
     }
     
@@ -153,7 +266,7 @@ impl RaftEventLoop {
                 event = self.event_rx.recv() => {
                     match event {
                         Some(event) => {
-                            if let Err(e) = self.handle_event(event).await {
+                            if let Err(e) = self.handle_event(event) {
                                 error!("Error handling event: {}", e);
                             }
                         }
@@ -164,20 +277,39 @@ impl RaftEventLoop {
                     }
                 }
                 
+                // Count votes and pre-votes as they come in
+                Some(ballot) = self.ballot_rx.recv() => {
+                    if let Err(e) = self.handle_ballot(ballot) {
+                        error!("Error counting vote: {}", e);
+                    }
+                }
+                
+                // Build requests for replicators and apply their peers' answers
+                Some(event) = self.replicator_rx.recv() => {
+                    if let Err(e) = self.handle_replicator_event(event) {
+                        error!("Error handling replication progress: {}", e);
+                    }
+                }
+                
                 // Check for election timeout
                 _ = election_timer.tick() => {
-                    if let Err(e) = self.check_election_timeout().await {
+                    if let Err(e) = self.check_election_timeout() {
                         error!("Error checking election timeout: {}", e);
                     }
+                    self.check_quorum();
+                    self.check_transfer();
                 }
                 
-                // Send heartbeats if leader
+                // Append queued commands and send heartbeats if leader
                 _ = heartbeat_timer.tick() => {
-                    if let Err(e) = self.send_heartbeats().await {
-                        error!("Error sending heartbeats: {}", e);
+                    if let Err(e) = self.append_queued_commands() {
+                        error!("Error appending commands: {}", e);
                     }
+                    self.send_heartbeats();
                 }
             }
+
+            self.publish_status();
         }
         
         info!("Raft event loop stopped");
@@ -185,39 +317,120 @@ impl RaftEventLoop {
     }
     
     /// Handle a single event
-    async fn handle_event(&mut self, event: RaftEvent) -> RaftResult<()> {
+    fn handle_event(&mut self, event: RaftEvent) -> RaftResult<()> {
         match event {
             RaftEvent::VoteRequest { request, response_tx } => {
-                let mut node = self.node.write().await;
-                let response = node.handle_vote_request(request)?;
+                let response = self.node.handle_vote_request(request)?;
+                let _ = response_tx.send(response);
+            }
+            
+            RaftEvent::PreVoteRequest { request, response_tx } => {
+                let response = self.node.handle_pre_vote_request(request)?;
                 let _ = response_tx.send(response);
             }
             
             RaftEvent::AppendRequest { request, response_tx } => {
-                let mut node = self.node.write().await;
-                let response = node.handle_append_request(request)?;
+                let response = self.node.handle_append_request(request)?;
                 let _ = response_tx.send(response);
             }
             
-            RaftEvent::SubmitCommand { command, response_tx } => {
-                let mut node = self.node.write().await;
-                let result = node.submit_command(command);
+            RaftEvent::InstallSnapshot { request, response_tx } => {
+                let response = self.node.handle_install_snapshot(request)?;
+                let _ = response_tx.send(response);
+            }
+            
+            RaftEvent::TimeoutNow { request, response_tx } => {
+                let response = self.node.handle_timeout_now(request)?;
+                let _ = response_tx.send(response);
+                self.request_votes();
+            }
+            
+            RaftEvent::SubmitCommand { command, client_id, sequence_number, response_tx } => {
+                // Commits only shrink the load, so measuring it once per batch
+                // errs on the side of rejecting
+                if self.queued_commands.is_empty() {
+                    self.queued_load = self.node.uncommitted_load();
+                }
+                match self.node.check_uncommitted_limits(self.queued_load, 1, command.len()) {
+                    Ok(()) => {
+                        self.queued_load.0 += 1;
+                        self.queued_load.1 += command.len();
+                        self.queued_commands.push((command, client_id, sequence_number));
+                        self.queued_responders.push(response_tx);
+                    }
+                    Err(e) => {
+                        let _ = response_tx.send(Err(e));
+                    }
+                }
+            }
+            
+            RaftEvent::ChangeMembership { nodes, response_tx } => {
+                let result = self.node.propose_membership(nodes);
+                Self::sync_peers(&mut self.peers, &self.node);
                 let _ = response_tx.send(result);
             }
             
+            RaftEvent::AddLearner { learner, response_tx } => {
+                let result = self.node.add_learner(learner);
+                Self::sync_peers(&mut self.peers, &self.node);
+                let _ = response_tx.send(result);
+            }
+            
+            RaftEvent::PromoteLearner { node_id, response_tx } => {
+                let result = self.node.promote_learner(&node_id);
+                let _ = response_tx.send(result);
+            }
+            
+            RaftEvent::ReadIndex { allow_lease, response_tx } => {
+                match self.node.read_index() {
+                    Ok(index) if allow_lease && self.lease_valid() => {
+                        let _ = response_tx.send(Ok((index, ReadMode::Lease)));
+                    }
+                    Ok(index) => {
+                        self.pending_reads.push((self.round + 1, index, response_tx));
+                        self.node.trigger_heartbeat();
+                        self.send_heartbeats();
+                    }
+                    Err(RaftError::ReadIndexNotReady) => {
+                        self.blocked_reads.push((allow_lease, response_tx));
+                    }
+                    Err(e) => {
+                        let _ = response_tx.send(Err(e));
+                    }
+                }
+            }
+            
+            RaftEvent::TransferLeadership { target, response_tx } => {
+                match self.node.transfer_leadership(&target) {
+                    Ok(()) => self.transfer_tx = Some((self.node.current_term(), response_tx)),
+                    Err(e) => {
+                        let _ = response_tx.send(Err(e));
+                    }
+                }
+            }
+            
             RaftEvent::GetStatus { response_tx } => {
-                let node = self.node.read().await;
-                let status = NodeStatus {
-                    node_id: node.node_id().clone(),
-                    state: node.state(),
-                    current_term: node.current_term(),
-                    leader_id: node.leader_id().cloned(),
-                    commit_index: node.commit_index(),
-                    last_applied: node.last_applied(),
-                    log_length: node.log_length(),
-                    peers: vec![], // TODO: Get from config
-                };
-                let _ = response_tx.send(status);
+                let _ = response_tx.send(self.status());
+            }
+            
+            RaftEvent::GetLeaderContact { response_tx } => {
+                let _ = response_tx.send(self.node.last_leader_contact());
+            }
+            
+            RaftEvent::GetEntriesToApply { response_tx } => {
+                let _ = response_tx.send(self.node.get_entries_to_apply().to_vec());
+            }
+            
+            RaftEvent::GetSnapshotToRestore { response_tx } => {
+                let _ = response_tx.send(self.node.snapshot_to_restore().cloned());
+            }
+            
+            RaftEvent::SetLastApplied { index } => {
+                self.node.set_last_applied(index);
+            }
+            
+            RaftEvent::Compact { index, data, response_tx } => {
+                let _ = response_tx.send(self.node.compact(index, data));
             }
             
             RaftEvent::Shutdown => {
@@ -229,135 +442,446 @@ impl RaftEventLoop {
         Ok(())
     }
     
+    /// Build the current status of the node
+    fn status(&self) -> NodeStatus {
+        let node = &self.node;
+        NodeStatus {
+            node_id: node.node_id().clone(),
+            state: node.state(),
+            current_term: node.current_term(),
+            leader_id: node.leader_id().cloned(),
+            leader_address: node.leader_address().map(str::to_string),
+            commit_index: node.commit_index(),
+            last_applied: node.last_applied(),
+            log_length: node.log_length(),
+            peers: node.cluster()
+                .members()
+                .into_iter()
+                .filter(|peer| &peer.node_id != node.node_id())
+                .map(|peer| peer.node_id.clone())
+                .collect(),
+            learners: node.cluster()
+                .members()
+                .into_iter()
+                .filter(|peer| !node.cluster().is_voter(&peer.node_id))
+                .map(|peer| peer.node_id.clone())
+                .collect(),
+        }
+    }
+
+    /// Notify subscribers if the status changed since the last publish
+    fn publish_status(&self) {
+        let status = self.status();
+        self.status_tx.send_if_modified(|current| {
+            if *current == status {
+                false
+            } else {
+                *current = status;
+                true
+            }
+        });
+    }
+    
     /// Check if election timeout has occurred and start election if needed
-    async fn check_election_timeout(&mut self) -> RaftResult<()> {
-        let should_start_election = {
-            let node = self.node.read().await;
-            node.state() != NodeState::Leader && node.is_election_timeout()
-        };
-        
-        if should_start_election {
-            self.start_election().await?;
+    fn check_election_timeout(&mut self) -> RaftResult<()> {
+        let node = &self.node;
+        if node.state() != NodeState::Leader && node.is_voter() && node.is_election_timeout() {
+            self.start_election()?;
         }
         
         Ok(())
     }
     
-    /// Start a new election
-    async fn start_election(&mut self) -> RaftResult<()> {
-        let (vote_request, current_term) = {
-            let mut node = self.node.write().await;
-            node.start_election()?;
-            
-            let vote_request = VoteRequest {
-                term: node.current_term(),
-                candidate_id: node.node_id().clone(),
-                last_log_index: node.log_length() as LogIndex,
-                last_log_term: 0, // TODO: Get last log term
-            };
-            
-            (vote_request, node.current_term())
+    /// Step down if a quorum has not answered within an election timeout (CheckQuorum)
+    fn check_quorum(&mut self) {
+        if self.node.state() != NodeState::Leader {
+            self.leadership = None;
+            return;
+        }
+
+        // Every peer gets a full election timeout from the start of a leadership
+        let now = Instant::now();
+        let term = self.node.current_term();
+        let since = match self.leadership {
+            Some((leader_term, since)) if leader_term == term => since,
+            _ => {
+                self.last_heard.clear();
+                self.leadership = Some((term, now));
+                now
+            }
         };
+
+        let timeout = self.node.max_election_timeout();
+        let last_heard = &self.last_heard;
+        self.node.check_quorum(|peer_id| {
+            let heard = last_heard.get(peer_id).copied().unwrap_or(since);
+            now.duration_since(heard) < timeout
+        });
+    }
+    
+    /// Check if the leader lease still covers this moment
+    ///
+    /// A leadership transfer voids the lease: the target's election bypasses
+    /// the stickiness the lease relies on.
+    fn lease_valid(&self) -> bool {
+        let node = &self.node;
+        node.state() == NodeState::Leader
+            && node.transfer_target().is_none()
+            && self.lease.is_some_and(|(term, expires)| {
+                term == node.current_term() && Instant::now() < expires
+            })
+    }
+    
+    /// Finish or abandon the leadership transfer in progress
+    ///
+    /// A newer term means the target has started its election and we have
+    /// stepped down. The transfer gives up after one election timeout, after
+    /// which this node keeps leading and accepts commands again.
+    fn check_transfer(&mut self) {
+        let Some((term, _)) = &self.transfer_tx else {
+            return;
+        };
+
+        let result = if self.node.current_term() > *term {
+            Some(Ok(()))
+        } else if self.node.state() != NodeState::Leader {
+            Some(Err(RaftError::NotLeader))
+        } else {
+            self.node.check_transfer_timeout().map(Err)
+        };
+
+        if let Some(result) = result {
+            if let Some((_, response_tx)) = self.transfer_tx.take() {
+                let _ = response_tx.send(result);
+            }
+        }
+    }
+    
+    /// Start a new election
+    ///
+    /// With pre-vote enabled, a pre-vote round runs first and the real election
+    /// only starts once a quorum has granted a pre-vote.
+    fn start_election(&mut self) -> RaftResult<()> {
+        self.node.campaign()?;
+        Self::sync_peers(&mut self.peers, &self.node);
         
-        info!("Starting election for term {}", current_term);
+        if self.node.state() == NodeState::PreCandidate {
+            self.request_pre_votes();
+        } else {
+            self.request_votes();
+        }
+        Ok(())
+    }
+    
+    /// Ask every peer for its vote if we are a candidate
+    ///
+    /// The answers come back as ballots, so the loop keeps serving events
+    /// while the election runs.
+    fn request_votes(&mut self) {
+        if self.node.state() != NodeState::Candidate {
+            return;
+        }
+        Self::sync_peers(&mut self.peers, &self.node);
+        let vote_request = self.node.vote_request();
         
-        // Send vote requests to all peers
-        let mut vote_tasks = Vec::new();
+        info!("Starting election for term {}", vote_request.term);
         
-        for (peer_id, client) in &self.peer_clients {
-            let client = client.clone();
+        for (peer_id, peer) in &self.peers {
+            let transport = self.transport.clone();
+            let peer = peer.clone();
             let request = vote_request.clone();
             let peer_id = peer_id.clone();
+            let ballot_tx = self.ballot_tx.clone();
             
-            let task = tokio::spawn(async move {
-                match client.request_vote(&request).await {
-                    Ok(response) => Some((peer_id, response)),
-                    Err(e) => {
-                        warn!("Failed to get vote from {}: {}", peer_id, e);
-                        None
+            tokio::spawn(async move {
+                match transport.request_vote(&peer, &request).await {
+                    Ok(response) => {
+                        let _ = ballot_tx.send(Ballot::Vote(peer_id, response));
                     }
+                    Err(e) => warn!("Failed to get vote from {}: {}", peer_id, e),
                 }
             });
-            
-            vote_tasks.push(task);
         }
+    }
+    
+    /// Send pre-vote requests to all peers; the answers come back as ballots
+    fn request_pre_votes(&mut self) {
+        let request = self.node.pre_vote_request();
+        info!("Starting pre-vote for term {}", request.term);
         
-        // Collect vote responses
-        for task in vote_tasks {
-            if let Ok(Some((peer_id, response))) = task.await {
-                let mut node = self.node.write().await;
-                node.handle_vote_response(&peer_id, response)?;
+        for (peer_id, peer) in &self.peers {
+            let transport = self.transport.clone();
+            let peer = peer.clone();
+            let request = request.clone();
+            let peer_id = peer_id.clone();
+            let ballot_tx = self.ballot_tx.clone();
+            
+            tokio::spawn(async move {
+                match transport.pre_vote(&peer, &request).await {
+                    Ok(response) => {
+                        let _ = ballot_tx.send(Ballot::PreVote(peer_id, response));
+                    }
+                    Err(e) => warn!("Failed to get pre-vote from {}: {}", peer_id, e),
+                }
+            });
+        }
+    }
+    
+    /// Count a peer's vote or pre-vote
+    ///
+    /// A pre-vote that completes a quorum starts the real election, which
+    /// sends out the vote requests.
+    fn handle_ballot(&mut self, ballot: Ballot) -> RaftResult<()> {
+        match ballot {
+            Ballot::Vote(peer_id, response) => self.node.handle_vote_response(&peer_id, response),
+            Ballot::PreVote(peer_id, response) => {
+                let was_pre_candidate = self.node.state() == NodeState::PreCandidate;
+                self.node.handle_pre_vote_response(&peer_id, response)?;
+                if was_pre_candidate && self.node.state() == NodeState::Candidate {
+                    self.request_votes();
+                }
+                Ok(())
             }
         }
-        
-        Ok(())
     }
     
-    /// Send heartbeats to all peers (if leader)
-    async fn send_heartbeats(&mut self) -> RaftResult<()> {
-        let should_send = {
-            let node = self.node.read().await;
-            node.state() == NodeState::Leader && node.should_send_heartbeat()
-        };
-        
-        if !should_send {
+    /// Keep exactly the other members of the node's active configuration as peers
+    fn sync_peers(peers: &mut HashMap<NodeId, PeerInfo>, node: &RaftNode) {
+        let members: Vec<&PeerInfo> = node.cluster()
+            .members()
+            .into_iter()
+            .filter(|peer| &peer.node_id != node.node_id())
+            .collect();
+
+        peers.retain(|peer_id, _| members.iter().any(|m| &m.node_id == peer_id));
+        for member in members {
+            peers.insert(member.node_id.clone(), member.clone());
+        }
+    }
+
+    /// Append the commands queued since the last tick to the log in one write
+    ///
+    /// Replication starts right away rather than waiting for the heartbeat interval.
+    fn append_queued_commands(&mut self) -> RaftResult<()> {
+        if self.queued_commands.is_empty() {
             return Ok(());
         }
-        
-        let append_request = {
-            let node = self.node.read().await;
-            AppendRequest {
-                term: node.current_term(),
-                leader_id: node.node_id().clone(),
-                prev_log_index: 0,
-                prev_log_term: 0,
-                entries: Vec::new(), // Empty for heartbeat
-                leader_commit: node.commit_index(),
+        let commands = std::mem::take(&mut self.queued_commands);
+        let responders = std::mem::take(&mut self.queued_responders);
+        debug!("Appending a batch of {} commands", commands.len());
+
+        let first_index = match self.node.submit_client_commands(commands) {
+            Ok(first_index) => first_index,
+            Err(e) => {
+                for response_tx in responders {
+                    let _ = response_tx.send(Err(batch_error(&e)));
+                }
+                return Ok(());
             }
         };
-        
-        debug!("Sending heartbeats to {} peers", self.peer_clients.len());
-        
-        // Send heartbeats to all peers
-        let mut heartbeat_tasks = Vec::new();
-        
-        for (peer_id, client) in &self.peer_clients {
-            let client = client.clone();
-            let request = append_request.clone();
-            let peer_id = peer_id.clone();
-            
-            let task = tokio::spawn(async move {
-                match client.append_entries(&request).await {
-                    Ok(response) => Some((peer_id, response)),
-                    Err(e) => {
-                        warn!("Failed to send heartbeat to {}: {}", peer_id, e);
-                        None
-                    }
+
+        let term = self.node.current_term();
+        for (index, response_tx) in (first_index..).zip(responders) {
+            let _ = response_tx.send(Ok((index, term)));
+        }
+        self.node.trigger_heartbeat();
+        // A single-node cluster commits as soon as the entries are appended
+        self.node.update_commit_index()
+    }
+    
+    /// Start a heartbeat round (if leader)
+    ///
+    /// Every peer's replicator sends a request in the round: the entries the
+    /// peer is missing, the next chunk of the snapshot if it needs entries
+    /// already compacted away, or an empty heartbeat. Answers come back to
+    /// `handle_replicator_event` as they arrive, so one slow follower never
+    /// holds up the others.
+    fn send_heartbeats(&mut self) {
+        if self.node.state() != NodeState::Leader {
+            self.stop_replicators();
+            self.fail_pending_reads();
+            return;
+        }
+        if !self.node.should_send_heartbeat() {
+            return;
+        }
+        self.node.reset_heartbeat_timer();
+        Self::sync_peers(&mut self.peers, &self.node);
+        self.sync_replicators();
+
+        self.round += 1;
+        self.rounds_started.push_back((self.round, Instant::now()));
+        debug!("Starting heartbeat round {} to {} peers", self.round, self.replicators.len());
+        for replicator in self.replicators.values() {
+            replicator.heartbeat(self.round);
+        }
+
+        // Without peers the round is confirmed at once
+        self.confirm_rounds();
+        self.send_timeout_now();
+    }
+
+    /// Keep one replicator running per peer of the current leadership
+    ///
+    /// Replicators of an earlier term or of peers that left the configuration
+    /// are stopped. New ones start from the progress the node holds for the peer.
+    fn sync_replicators(&mut self) {
+        let node = &self.node;
+        let term = node.current_term();
+        if self.replicators_term != term {
+            self.replicators.clear();
+            self.rounds_started.clear();
+            self.acked_rounds.clear();
+            self.replicators_term = term;
+        }
+
+        let peers = &self.peers;
+        self.replicators.retain(|peer_id, _| peers.contains_key(peer_id));
+        for (peer_id, peer) in &self.peers {
+            if self.replicators.contains_key(peer_id) {
+                continue;
+            }
+            let progress = node
+                .progress(peer_id)
+                .cloned()
+                .unwrap_or_else(|| Progress::new(node.last_log_index() + 1));
+            let replicator = Replicator::spawn(
+                peer.clone(),
+                self.transport.clone(),
+                progress,
+                node.max_inflight_appends(),
+                self.replicator_tx.clone(),
+            );
+            self.replicators.insert(peer_id.clone(), replicator);
+        }
+    }
+
+    /// Stop every replicator and forget the heartbeat rounds they answered
+    fn stop_replicators(&mut self) {
+        self.replicators.clear();
+        self.rounds_started.clear();
+        self.acked_rounds.clear();
+    }
+
+    /// Build a replicator's next requests or apply its peer's answer
+    fn handle_replicator_event(&mut self, event: ReplicatorEvent) -> RaftResult<()> {
+        match event {
+            ReplicatorEvent::Ready { mut progress, capacity, reply } => {
+                let requests = self.node.replication_requests(&mut progress, capacity);
+                let _ = reply.send(Self::assignment(&self.node, progress, requests));
+            }
+
+            ReplicatorEvent::Answered { peer_id, round, mut progress, request, response, reply } => {
+                self.last_heard.insert(peer_id.clone(), Instant::now());
+                let term = self.node.current_term();
+                let current = request.term() == term && response.term() == term;
+                let result = self.node.handle_replication_response(&peer_id, &mut progress, &request, response);
+                if current && self.node.state() == NodeState::Leader {
+                    let acked = self.acked_rounds.entry(peer_id).or_insert(0);
+                    *acked = (*acked).max(round);
                 }
-            });
-            
-            heartbeat_tasks.push(task);
+                let _ = reply.send(Self::assignment(&self.node, progress, Vec::new()));
+                result?;
+
+                self.confirm_rounds();
+                self.release_blocked_reads();
+            }
         }
-        
-        // Process heartbeat responses
-        for task in heartbeat_tasks {
-            if let Ok(Some((_peer_id, _response))) = task.await {
-                // TODO: Process heartbeat response
-                // Update next_index and match_index based on response
+
+        Ok(())
+    }
+
+    /// Hand a replicator its requests and the peer's progress
+    fn assignment(node: &RaftNode, progress: Progress, requests: Vec<ReplicationRequest>) -> Assignment {
+        let behind = node.state() == NodeState::Leader && progress.next_index <= node.last_log_index();
+        Assignment { progress, requests, behind }
+    }
+
+    /// Confirm the newest heartbeat round a quorum has answered in our term
+    ///
+    /// Read indexes queued for that round or an earlier one are answered, and
+    /// the leader lease is renewed from the moment the round started.
+    fn confirm_rounds(&mut self) {
+        let node = &self.node;
+        if node.state() != NodeState::Leader {
+            return;
+        }
+
+        let acked_rounds = &self.acked_rounds;
+        let confirmed = self.rounds_started.iter().rev().copied().find(|(round, _)| {
+            node.cluster().is_quorum(|id| {
+                id == node.node_id() || acked_rounds.get(id).is_some_and(|acked| acked >= round)
+            })
+        });
+        let Some((round, started)) = confirmed else {
+            return;
+        };
+
+        self.lease = Some((node.current_term(), started + node.lease_duration()));
+        self.rounds_started.retain(|(started_round, _)| *started_round > round);
+        let (confirmed_reads, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending_reads)
+            .into_iter()
+            .partition(|(read_round, ..)| *read_round <= round);
+        self.pending_reads = waiting;
+        for (_, index, response_tx) in confirmed_reads {
+            let _ = response_tx.send(Ok((index, ReadMode::ReadIndex)));
+        }
+    }
+
+    /// Queue the reads blocked on the election NoOp for the next round once it has committed
+    fn release_blocked_reads(&mut self) {
+        if self.blocked_reads.is_empty() || !self.node.committed_in_current_term() {
+            return;
+        }
+
+        let lease_valid = self.lease_valid();
+        for (allow_lease, response_tx) in std::mem::take(&mut self.blocked_reads) {
+            match self.node.read_index() {
+                Ok(index) if allow_lease && lease_valid => {
+                    let _ = response_tx.send(Ok((index, ReadMode::Lease)));
+                }
+                Ok(index) => self.pending_reads.push((self.round + 1, index, response_tx)),
+                Err(e) => {
+                    let _ = response_tx.send(Err(e));
+                }
             }
         }
+        self.node.trigger_heartbeat();
+    }
+    
+    /// Fail every read still waiting for a heartbeat round or the election NoOp
+    fn fail_pending_reads(&mut self) {
+        let blocked = self.blocked_reads.drain(..).map(|(_, response_tx)| response_tx);
+        for response_tx in self.pending_reads.drain(..).map(|(_, _, response_tx)| response_tx).chain(blocked) {
+            let _ = response_tx.send(Err(RaftError::NotLeader));
+        }
+    }
+    
+    /// Send TimeoutNow to the leadership transfer target once it has caught up
+    fn send_timeout_now(&self) {
+        let Some((target, request)) = self.node.timeout_now_request() else {
+            return;
+        };
+        let Some(peer) = self.peers.get(&target).cloned() else {
+            return;
+        };
         
-        Ok(())
+        info!("Sending TimeoutNow to {} for term {}", target, request.term);
+        let transport = self.transport.clone();
+        tokio::spawn(async move {
+            if let Err(e) = transport.timeout_now(&peer, &request).await {
+                warn!("Failed to send TimeoutNow to {}: {}", target, e);
+            }
+        });
     }
 }
 
-// Make RaftPeerClient cloneable
-impl Clone for RaftPeerClient {
-    fn clone(&self) -> Self {
-        Self {
-            node_id: self.node_id.clone(),
-            address: self.address.clone(),
-            client: reqwest::Client::new(),
-        }
+/// The error to answer every command of a batch that failed to append with
+fn batch_error(error: &RaftError) -> RaftError {
+    match error {
+        RaftError::NotLeader => RaftError::NotLeader,
+        RaftError::TransferInProgress { target } => RaftError::TransferInProgress { target: target.clone() },
+        RaftError::Overloaded(reason) => RaftError::Overloaded(reason.clone()),
+        e => RaftError::Storage(e.to_string()),
     }
 }
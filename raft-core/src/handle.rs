@@ -0,0 +1,164 @@
+//! Handle to a running event loop
+//!
+//! The event loop owns its `RaftNode` outright; everything else reaches the
+//! node through a [`RaftHandle`], which sends `RaftEvent`s over the loop's
+//! bounded queue and waits for the answers. Handles are cheap to clone, so the
+//! HTTP and gRPC layers, the apply loop and in-memory transports each keep one.
+//!
+//! Peer RPCs and the apply loop wait for room when the queue is full, but
+//! client requests are rejected with `RaftError::Overloaded` instead, so an
+//! overloaded node sheds load rather than piling up waiting requests.
+
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Duration;
+
+use crate::error::RaftError;
+use crate::event_loop::{NodeStatus, RaftEvent};
+use crate::types::*;
+use crate::RaftResult;
+
+/// Cloneable handle to a `RaftEventLoop`
+#[derive(Debug, Clone)]
+pub struct RaftHandle {
+    event_tx: mpsc::Sender<RaftEvent>,
+    status_rx: watch::Receiver<NodeStatus>,
+}
+
+impl RaftHandle {
+    pub(crate) fn new(event_tx: mpsc::Sender<RaftEvent>, status_rx: watch::Receiver<NodeStatus>) -> Self {
+        Self { event_tx, status_rx }
+    }
+
+    /// Subscribe to status changes of the node
+    pub fn subscribe(&self) -> watch::Receiver<NodeStatus> {
+        self.status_rx.clone()
+    }
+
+    /// Queue an event for the event loop, waiting for room if the queue is full
+    pub async fn send(&self, event: RaftEvent) -> RaftResult<()> {
+        self.event_tx.send(event).await.map_err(|_| RaftError::Unavailable)
+    }
+
+    /// Queue an event for the event loop, failing with `Overloaded` if the queue is full
+    pub fn try_send(&self, event: RaftEvent) -> RaftResult<()> {
+        self.event_tx.try_send(event).map_err(|e| match e {
+            TrySendError::Full(_) => RaftError::Overloaded("event queue is full".to_string()),
+            TrySendError::Closed(_) => RaftError::Unavailable,
+        })
+    }
+
+    /// Get the number of events waiting in the queue
+    pub fn queue_depth(&self) -> usize {
+        self.event_tx.max_capacity() - self.event_tx.capacity()
+    }
+
+    /// Send an event carrying a reply channel and wait for the answer
+    async fn ask<T>(&self, event: impl FnOnce(oneshot::Sender<T>) -> RaftEvent) -> RaftResult<T> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.send(event(response_tx)).await?;
+        response_rx.await.map_err(|_| RaftError::Unavailable)
+    }
+
+    /// Like `ask`, but rejected with `Overloaded` rather than waiting for room in the queue
+    async fn try_ask<T>(&self, event: impl FnOnce(oneshot::Sender<T>) -> RaftEvent) -> RaftResult<T> {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.try_send(event(response_tx))?;
+        response_rx.await.map_err(|_| RaftError::Unavailable)
+    }
+
+    /// Handle a vote request from a candidate
+    pub async fn request_vote(&self, request: VoteRequest) -> RaftResult<VoteResponse> {
+        self.ask(|response_tx| RaftEvent::VoteRequest { request, response_tx }).await
+    }
+
+    /// Handle a pre-vote request from a would-be candidate
+    pub async fn pre_vote(&self, request: PreVoteRequest) -> RaftResult<PreVoteResponse> {
+        self.ask(|response_tx| RaftEvent::PreVoteRequest { request, response_tx }).await
+    }
+
+    /// Handle an append entries request from the leader
+    pub async fn append_entries(&self, request: AppendRequest) -> RaftResult<AppendResponse> {
+        self.ask(|response_tx| RaftEvent::AppendRequest { request, response_tx }).await
+    }
+
+    /// Handle a snapshot chunk from the leader
+    pub async fn install_snapshot(&self, request: InstallSnapshotRequest) -> RaftResult<InstallSnapshotResponse> {
+        self.ask(|response_tx| RaftEvent::InstallSnapshot { request, response_tx }).await
+    }
+
+    /// Handle TimeoutNow from a leader handing leadership over to us
+    pub async fn timeout_now(&self, request: TimeoutNowRequest) -> RaftResult<TimeoutNowResponse> {
+        self.ask(|response_tx| RaftEvent::TimeoutNow { request, response_tx }).await
+    }
+
+    /// Submit a command; answers with the log index and term it was appended at
+    pub async fn submit_command(
+        &self,
+        command: Vec<u8>,
+        client_id: Option<String>,
+        sequence_number: Option<u64>,
+    ) -> RaftResult<(LogIndex, Term)> {
+        self.try_ask(|response_tx| RaftEvent::SubmitCommand { command, client_id, sequence_number, response_tx }).await?
+    }
+
+    /// Change cluster membership to the given nodes (leaders only)
+    pub async fn change_membership(&self, nodes: Vec<PeerInfo>) -> RaftResult<LogIndex> {
+        self.try_ask(|response_tx| RaftEvent::ChangeMembership { nodes, response_tx }).await?
+    }
+
+    /// Add a non-voting learner (leaders only)
+    pub async fn add_learner(&self, learner: PeerInfo) -> RaftResult<LogIndex> {
+        self.try_ask(|response_tx| RaftEvent::AddLearner { learner, response_tx }).await?
+    }
+
+    /// Promote a caught-up learner to a voter (leaders only)
+    pub async fn promote_learner(&self, node_id: NodeId) -> RaftResult<LogIndex> {
+        self.try_ask(|response_tx| RaftEvent::PromoteLearner { node_id, response_tx }).await?
+    }
+
+    /// Confirm leadership for a linearizable read (leaders only)
+    pub async fn read_index(&self, allow_lease: bool) -> RaftResult<(LogIndex, ReadMode)> {
+        self.try_ask(|response_tx| RaftEvent::ReadIndex { allow_lease, response_tx }).await?
+    }
+
+    /// Hand leadership over to another voter (leaders only)
+    pub async fn transfer_leadership(&self, target: NodeId) -> RaftResult<()> {
+        self.try_ask(|response_tx| RaftEvent::TransferLeadership { target, response_tx }).await?
+    }
+
+    /// Get the node's current status
+    pub async fn status(&self) -> RaftResult<NodeStatus> {
+        self.ask(|response_tx| RaftEvent::GetStatus { response_tx }).await
+    }
+
+    /// Get how long ago the node last heard from a leader, if it knows one
+    pub async fn leader_contact(&self) -> RaftResult<Option<Duration>> {
+        self.ask(|response_tx| RaftEvent::GetLeaderContact { response_tx }).await
+    }
+
+    /// Get the committed entries that have not been applied yet
+    pub async fn entries_to_apply(&self) -> RaftResult<Vec<LogEntry>> {
+        self.ask(|response_tx| RaftEvent::GetEntriesToApply { response_tx }).await
+    }
+
+    /// Get the snapshot to restore the state machine from, if it is behind one
+    pub async fn snapshot_to_restore(&self) -> RaftResult<Option<Snapshot>> {
+        self.ask(|response_tx| RaftEvent::GetSnapshotToRestore { response_tx }).await
+    }
+
+    /// Record that the state machine has applied the log up to `index`
+    pub async fn set_last_applied(&self, index: LogIndex) -> RaftResult<()> {
+        self.send(RaftEvent::SetLastApplied { index }).await
+    }
+
+    /// Replace the log up to `index` with a snapshot of the state machine
+    pub async fn compact(&self, index: LogIndex, data: Vec<u8>) -> RaftResult<()> {
+        self.ask(|response_tx| RaftEvent::Compact { index, data, response_tx }).await?
+    }
+
+    /// Stop the event loop
+    pub async fn shutdown(&self) -> RaftResult<()> {
+        self.send(RaftEvent::Shutdown).await
+    }
+}
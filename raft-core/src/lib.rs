@@ -8,23 +8,36 @@
 //! - Term management
 //! - State transitions
 //! - Heartbeat mechanism
+//! - Per-peer replication actors pipelining AppendEntries
+//! - Durable term, vote and log storage
+//! - Pluggable transports for peer RPCs
+//! - Versioned binary encoding of peer RPCs and log entries
 
 pub mod node;
 pub mod log;
 pub mod state;
 pub mod election;
 pub mod replication;
+mod replicator;
+pub mod storage;
 pub mod types;
 pub mod error;
+pub mod codec;
 pub mod event_loop;
+pub mod handle;
+pub mod transport;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
 pub use node::RaftNode;
 pub use types::*;
 pub use error::RaftError;
+pub use storage::{RaftStorage, FileStorage, MemoryStorage};
 pub use event_loop::{RaftEventLoop, RaftEvent, NodeStatus};
+pub use handle::RaftHandle;
+pub use transport::{Transport, HttpTransport, GrpcTransport, InMemoryNetwork, InMemoryTransport};
 
 /// Result type for Raft operations
 pub type RaftResult<T> = Result<T, RaftError>;
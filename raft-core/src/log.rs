@@ -1,11 +1,16 @@
 use crate::types::*;
-use crate::error::RaftError;
 use crate::RaftResult;
 
 /// Raft log implementation
+///
+/// After compaction the log only holds entries after `last_included_index`;
+/// everything up to and including it lives in the snapshot. Lookups use
+/// absolute log indices either way.
 pub struct RaftLog {
     entries: Vec<LogEntry>,
     commit_index: LogIndex,
+    last_included_index: LogIndex,
+    last_included_term: Term,
 }
 
 impl RaftLog {
@@ -14,60 +19,157 @@ impl RaftLog {
         Self {
             entries: Vec::new(),
             commit_index: 0,
+            last_included_index: 0,
+            last_included_term: 0,
         }
     }
-    
-    /// Get the length of the log
+
+    /// Create a log that starts right after a snapshot
+    ///
+    /// Entries at or before the snapshot's last included index are dropped.
+    pub fn from_snapshot(
+        last_included_index: LogIndex,
+        last_included_term: Term,
+        entries: Vec<LogEntry>,
+    ) -> Self {
+        let mut log = Self::new();
+        log.last_included_index = last_included_index;
+        log.last_included_term = last_included_term;
+        log.entries = entries
+            .into_iter()
+            .filter(|e| e.index > last_included_index)
+            .collect();
+        log
+    }
+
+    /// Get the number of entries held in memory (excluding the snapshot)
     pub fn len(&self) -> usize {
         self.entries.len()
     }
-    
-    /// Check if the log is empty
+
+    /// Check if the log holds no entries after the snapshot
     pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
-    
+
+    /// Get the index of the first entry still in the log
+    pub fn first_index(&self) -> LogIndex {
+        self.last_included_index + 1
+    }
+
     /// Get the last log index
     pub fn last_index(&self) -> LogIndex {
-        self.entries.len() as LogIndex
+        self.last_included_index + self.entries.len() as LogIndex
     }
-    
+
     /// Get the term of the last log entry
     pub fn last_term(&self) -> Term {
-        self.entries.last().map(|e| e.term).unwrap_or(0)
+        self.entries.last().map(|e| e.term).unwrap_or(self.last_included_term)
+    }
+
+    /// Get the index of the last entry covered by the snapshot
+    pub fn last_included_index(&self) -> LogIndex {
+        self.last_included_index
     }
-    
+
+    /// Get the term of the last entry covered by the snapshot
+    pub fn last_included_term(&self) -> Term {
+        self.last_included_term
+    }
+
     /// Get the commit index
     pub fn commit_index(&self) -> LogIndex {
         self.commit_index
     }
-    
+
     /// Append entries to the log
     pub fn append(&mut self, entries: Vec<LogEntry>) -> RaftResult<()> {
-        // TODO: Implement log append logic
         self.entries.extend(entries);
         Ok(())
     }
-    
+
     /// Get an entry at a specific index
     pub fn get(&self, index: LogIndex) -> Option<&LogEntry> {
-        if index == 0 || index > self.entries.len() as LogIndex {
+        if index <= self.last_included_index || index > self.last_index() {
             None
         } else {
-            self.entries.get((index - 1) as usize)
+            self.entries.get((index - self.first_index()) as usize)
+        }
+    }
+
+    /// Get the term of the entry at `index`
+    ///
+    /// Also answers for the snapshot's last included index and for index 0;
+    /// returns `None` for compacted or missing entries.
+    pub fn term(&self, index: LogIndex) -> Option<Term> {
+        if index == 0 {
+            Some(0)
+        } else if index == self.last_included_index {
+            Some(self.last_included_term)
+        } else {
+            self.get(index).map(|e| e.term)
         }
     }
-    
+
+    /// Get the entries in `[from, to]` that are still held in memory
+    pub fn slice(&self, from: LogIndex, to: LogIndex) -> &[LogEntry] {
+        let from = from.max(self.first_index());
+        let to = to.min(self.last_index());
+        if from > to {
+            return &[];
+        }
+
+        let start = (from - self.first_index()) as usize;
+        let end = (to - self.first_index()) as usize + 1;
+        &self.entries[start..end]
+    }
+
+    /// Find the index of the first in-memory entry with the given term
+    pub fn first_index_of_term(&self, term: Term) -> Option<LogIndex> {
+        self.entries.iter().find(|e| e.term == term).map(|e| e.index)
+    }
+
+    /// Find the index of the last in-memory entry with the given term
+    pub fn last_index_of_term(&self, term: Term) -> Option<LogIndex> {
+        self.entries.iter().rev().find(|e| e.term == term).map(|e| e.index)
+    }
+
     /// Truncate the log from a specific index
     pub fn truncate(&mut self, from_index: LogIndex) -> RaftResult<()> {
-        if from_index <= self.entries.len() as LogIndex {
-            self.entries.truncate((from_index - 1) as usize);
+        if from_index <= self.last_index() {
+            let keep = from_index.saturating_sub(self.first_index()) as usize;
+            self.entries.truncate(keep);
         }
         Ok(())
     }
-    
+
+    /// Discard every entry up to and including `index`, which a snapshot now covers
+    pub fn compact(&mut self, index: LogIndex, term: Term) {
+        if index <= self.last_included_index {
+            return;
+        }
+
+        let drop = (index.min(self.last_index()) - self.last_included_index) as usize;
+        self.entries.drain(..drop);
+        self.last_included_index = index;
+        self.last_included_term = term;
+    }
+
+    /// Replace the whole log with a snapshot boundary
+    pub fn reset(&mut self, last_included_index: LogIndex, last_included_term: Term) {
+        self.entries.clear();
+        self.last_included_index = last_included_index;
+        self.last_included_term = last_included_term;
+    }
+
     /// Update the commit index
     pub fn set_commit_index(&mut self, index: LogIndex) {
-        self.commit_index = index.min(self.entries.len() as LogIndex);
+        self.commit_index = index.min(self.last_index());
+    }
+}
+
+impl Default for RaftLog {
+    fn default() -> Self {
+        Self::new()
     }
 }
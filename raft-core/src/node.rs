@@ -1,39 +1,53 @@
+use bytes::Bytes;
 use crate::types::*;
 use crate::error::RaftError;
+use crate::log::RaftLog;
+use crate::replication::{Progress, ReplicationManager, ReplicationRequest, ReplicationResponse};
+use crate::storage::{RaftStorage, MemoryStorage};
 use crate::RaftResult;
 use std::time::{Duration, Instant};
-use tracing::{info, debug};
+use tracing::{info, debug, warn};
 
 /// Main Raft node implementation
 pub struct RaftNode {
     // Persistent state on all servers
     current_term: Term,
     voted_for: Option<NodeId>,
-    log: Vec<LogEntry>,
+    log: RaftLog,
+    storage: Box<dyn RaftStorage>,
+    snapshot: Option<Snapshot>,
 
     // Volatile state on all servers
     commit_index: LogIndex,
     last_applied: LogIndex,
 
-    // Volatile state on leaders
-    next_index: std::collections::HashMap<NodeId, LogIndex>,
+    // Volatile state on leaders: each peer's replication progress, and how far
+    // every voter (ourselves included) is known to match our log
+    progress: std::collections::HashMap<NodeId, Progress>,
     match_index: std::collections::HashMap<NodeId, LogIndex>,
 
     // Node configuration and state
     config: NodeConfig,
+    cluster: ClusterConfig,
     state: NodeState,
 
     // Election and timing
     last_heartbeat: Instant,
     election_timeout: Duration,
     votes_received: std::collections::HashSet<NodeId>,
+    leadership_transfer: bool,
 
     // Leader state
     leader_id: Option<NodeId>,
+    replication: ReplicationManager,
+    transfer: Option<(NodeId, Instant)>,
+
+    // Snapshot transfer: chunks received so far (follower)
+    incoming_snapshot: Option<Snapshot>,
 }
 
 impl RaftNode {
-    /// Create a new Raft node
+    /// Create a new Raft node with in-memory storage
     pub fn new(config: NodeConfig) -> Self {
         use rand::Rng;
         let mut rng = rand::thread_rng();
@@ -41,20 +55,77 @@ impl RaftNode {
             rng.gen_range(config.election_timeout_min..=config.election_timeout_max)
         );
 
+        let replication = ReplicationManager::new(config.heartbeat_interval);
+        let cluster = Self::initial_cluster(&config);
+
         Self {
             current_term: 0,
             voted_for: None,
-            log: Vec::new(),
+            log: RaftLog::new(),
+            storage: Box::new(MemoryStorage::new()),
+            snapshot: None,
             commit_index: 0,
             last_applied: 0,
-            next_index: std::collections::HashMap::new(),
+            progress: std::collections::HashMap::new(),
             match_index: std::collections::HashMap::new(),
             config,
+            cluster,
             state: NodeState::Follower,
             last_heartbeat: Instant::now(),
             election_timeout,
             votes_received: std::collections::HashSet::new(),
+            leadership_transfer: false,
             leader_id: None,
+            replication,
+            transfer: None,
+            incoming_snapshot: None,
+        }
+    }
+
+    /// Create a Raft node that recovers its term, vote, snapshot and log from storage
+    ///
+    /// Everything up to the snapshot is known to be committed; the state machine
+    /// has to be restored from `snapshot_to_restore` before later entries apply.
+    pub fn with_storage(config: NodeConfig, mut storage: Box<dyn RaftStorage>) -> RaftResult<Self> {
+        let persistent = storage.load()?;
+        info!("Recovered term {} (voted for {:?}) with {} log entries",
+              persistent.current_term, persistent.voted_for, persistent.log.len());
+
+        let mut node = Self::new(config);
+        node.current_term = persistent.current_term;
+        node.voted_for = persistent.voted_for;
+        node.log = match &persistent.snapshot {
+            Some(snapshot) => RaftLog::from_snapshot(
+                snapshot.last_included_index,
+                snapshot.last_included_term,
+                persistent.log,
+            ),
+            None => RaftLog::from_snapshot(0, 0, persistent.log),
+        };
+        node.commit_index = node.log.last_included_index();
+        node.snapshot = persistent.snapshot;
+        node.storage = storage;
+        node.refresh_cluster();
+        Ok(node)
+    }
+
+    /// Build the configuration the cluster starts from, before any configuration entry
+    fn initial_cluster(config: &NodeConfig) -> ClusterConfig {
+        let own = PeerInfo {
+            node_id: config.node_id.clone(),
+            address: config.address.clone(),
+            voting: true,
+            grpc_address: config.grpc_address.clone(),
+        };
+        let peers = config.peers
+            .iter()
+            .filter(|peer| peer.node_id != config.node_id)
+            .cloned();
+
+        ClusterConfig {
+            nodes: std::iter::once(own).chain(peers).collect(),
+            old_nodes: None,
+            config_index: 0,
         }
     }
     
@@ -77,6 +148,17 @@ impl RaftNode {
     pub fn handle_vote_request(&mut self, request: VoteRequest) -> RaftResult<VoteResponse> {
         debug!("Received vote request from {} for term {}", request.candidate_id, request.term);
 
+        // Leader stickiness: while a current leader is known to be alive, ignore
+        // the request entirely instead of letting it bump our term. Elections
+        // the leader asked for itself are the exception.
+        if !request.leadership_transfer && self.leader_recently_heard() {
+            debug!("Ignoring vote request from {}: leader is alive", request.candidate_id);
+            return Ok(VoteResponse {
+                term: self.current_term,
+                vote_granted: false,
+            });
+        }
+
         // If term is outdated, reject
         if request.term < self.current_term {
             return Ok(VoteResponse {
@@ -86,11 +168,13 @@ impl RaftNode {
         }
 
         // If term is newer, update our term and become follower
+        let mut hard_state_changed = false;
         if request.term > self.current_term {
             self.current_term = request.term;
             self.voted_for = None;
             self.state = NodeState::Follower;
             self.leader_id = None;
+            hard_state_changed = true;
         }
 
         // Check if we can vote for this candidate
@@ -98,8 +182,8 @@ impl RaftNode {
                       self.voted_for.as_ref() == Some(&request.candidate_id);
 
         // Check if candidate's log is at least as up-to-date as ours
-        let last_log_term = self.log.last().map(|e| e.term).unwrap_or(0);
-        let last_log_index = self.log.len() as LogIndex;
+        let last_log_term = self.last_log_term();
+        let last_log_index = self.last_log_index();
 
         let log_ok = request.last_log_term > last_log_term ||
                     (request.last_log_term == last_log_term &&
@@ -110,12 +194,18 @@ impl RaftNode {
         if vote_granted {
             self.voted_for = Some(request.candidate_id.clone());
             self.reset_election_timeout();
+            hard_state_changed = true;
             info!("Granted vote to {} for term {}", request.candidate_id, request.term);
         } else {
             debug!("Denied vote to {} for term {} (can_vote: {}, log_ok: {})",
                    request.candidate_id, request.term, can_vote, log_ok);
         }
 
+        // The vote must be durable before the response goes out
+        if hard_state_changed {
+            self.persist_hard_state()?;
+        }
+
         Ok(VoteResponse {
             term: self.current_term,
             vote_granted,
@@ -126,9 +216,6 @@ impl RaftNode {
     pub fn handle_append_request(&mut self, request: AppendRequest) -> RaftResult<AppendResponse> {
         debug!("Received append entries from {} for term {}", request.leader_id, request.term);
 
-        // Reset election timeout since we heard from a leader
-        self.reset_election_timeout();
-
         // If term is outdated, reject
         if request.term < self.current_term {
             return Ok(AppendResponse {
@@ -139,79 +226,101 @@ impl RaftNode {
             });
         }
 
-        // If term is newer or equal, update our term and become follower
-        if request.term >= self.current_term {
+        // Reset election timeout since we heard from the current leader
+        self.reset_election_timeout();
+
+        // If term is newer, adopt it; the vote is only cleared for a new term
+        if request.term > self.current_term {
             self.current_term = request.term;
-            self.state = NodeState::Follower;
-            self.leader_id = Some(request.leader_id.clone());
             self.voted_for = None;
+            self.persist_hard_state()?;
+        }
+        self.state = NodeState::Follower;
+        self.leader_id = Some(request.leader_id.clone());
+
+        // Entries up to the snapshot are already committed here; skip past them
+        let mut prev_log_index = request.prev_log_index;
+        let mut prev_log_term = request.prev_log_term;
+        let mut entries = &request.entries[..];
+        let snapshot_index = self.log.last_included_index();
+        if prev_log_index < snapshot_index {
+            let skip = ((snapshot_index - prev_log_index) as usize).min(entries.len());
+            entries = &entries[skip..];
+            prev_log_index += skip as LogIndex;
+            if prev_log_index < snapshot_index {
+                return Ok(AppendResponse {
+                    term: self.current_term,
+                    success: true,
+                    conflict_index: None,
+                    conflict_term: None,
+                });
+            }
+            prev_log_term = self.log.last_included_term();
         }
 
         // Check if we have the previous log entry
-        if request.prev_log_index > 0 {
-            if request.prev_log_index > self.log.len() as LogIndex {
+        match self.log.term(prev_log_index) {
+            None => {
                 // We don't have enough entries
                 return Ok(AppendResponse {
                     term: self.current_term,
                     success: false,
-                    conflict_index: Some(self.log.len() as LogIndex + 1),
+                    conflict_index: Some(self.last_log_index() + 1),
                     conflict_term: None,
                 });
             }
-
-            let prev_entry = &self.log[(request.prev_log_index - 1) as usize];
-            if prev_entry.term != request.prev_log_term {
-                // Term mismatch - find the first entry with conflicting term
-                let conflict_term = prev_entry.term;
-                let mut conflict_index = request.prev_log_index;
-
-                // Find first entry of the conflicting term
-                for (i, entry) in self.log.iter().enumerate() {
-                    if entry.term == conflict_term {
-                        conflict_index = (i + 1) as LogIndex;
-                        break;
-                    }
-                }
+            Some(term) if term != prev_log_term => {
+                // Term mismatch - point the leader at the first entry of the conflicting term
+                let conflict_index = self.log
+                    .first_index_of_term(term)
+                    .unwrap_or(prev_log_index);
 
                 return Ok(AppendResponse {
                     term: self.current_term,
                     success: false,
                     conflict_index: Some(conflict_index),
-                    conflict_term: Some(conflict_term),
+                    conflict_term: Some(term),
                 });
             }
+            Some(_) => {}
         }
 
         // If we have conflicting entries, remove them
-        if !request.entries.is_empty() {
-            let start_index = request.prev_log_index as usize;
+        if !entries.is_empty() {
+            let mut config_changed = false;
 
             // Check for conflicts and truncate if necessary
-            for (i, new_entry) in request.entries.iter().enumerate() {
-                let log_index = start_index + i;
-                if log_index < self.log.len() {
-                    if self.log[log_index].term != new_entry.term {
+            for new_entry in entries {
+                match self.log.term(new_entry.index) {
+                    Some(term) if term != new_entry.term => {
                         // Conflict found - truncate from here
-                        self.log.truncate(log_index);
+                        self.storage.truncate(new_entry.index)?;
+                        self.log.truncate(new_entry.index)?;
+                        config_changed = true;
                         break;
                     }
-                } else {
-                    break;
+                    Some(_) => {}
+                    None => break,
                 }
             }
 
-            // Append new entries
-            for (i, new_entry) in request.entries.iter().enumerate() {
-                let log_index = start_index + i;
-                if log_index >= self.log.len() {
-                    self.log.push(new_entry.clone());
-                }
+            // Append new entries, durably, before acknowledging them
+            let already_present = self.last_log_index().saturating_sub(prev_log_index) as usize;
+            let new_entries = &entries[already_present.min(entries.len())..];
+            self.storage.append(new_entries)?;
+            self.log.append(new_entries.to_vec())?;
+
+            // Configuration entries take effect as soon as they are in the log
+            config_changed |= new_entries.iter().any(|e| e.entry_type == EntryType::Configuration);
+            if config_changed {
+                self.refresh_cluster();
             }
         }
 
-        // Update commit index
+        // Update commit index, but never past the last entry this request vouched for
+        let last_new_index = prev_log_index + entries.len() as LogIndex;
         if request.leader_commit > self.commit_index {
-            self.commit_index = std::cmp::min(request.leader_commit, self.log.len() as LogIndex);
+            self.commit_index = std::cmp::min(request.leader_commit, last_new_index);
         }
 
         Ok(AppendResponse {
@@ -222,6 +331,94 @@ impl RaftNode {
         })
     }
     
+    /// Campaign for leadership after an election timeout
+    ///
+    /// With `pre_vote` enabled this only starts a pre-vote round; the real
+    /// election follows once a quorum has granted a pre-vote.
+    pub fn campaign(&mut self) -> RaftResult<()> {
+        self.leadership_transfer = false;
+        if self.config.pre_vote {
+            self.start_pre_vote()
+        } else {
+            self.start_election()
+        }
+    }
+
+    /// Start a pre-vote round (Raft thesis §9.6)
+    ///
+    /// Neither the term nor the vote changes, so a node that cannot win (for
+    /// example one that was partitioned away) never disrupts the cluster.
+    pub fn start_pre_vote(&mut self) -> RaftResult<()> {
+        info!("Starting pre-vote for term {}", self.current_term + 1);
+
+        self.state = NodeState::PreCandidate;
+        self.leader_id = None;
+        self.reset_election_timeout();
+
+        self.votes_received.clear();
+        self.votes_received.insert(self.config.node_id.clone());
+
+        if self.cluster.is_quorum(|id| self.votes_received.contains(id)) {
+            return self.start_election();
+        }
+
+        Ok(())
+    }
+
+    /// Build the pre-vote request for the current pre-vote round
+    pub fn pre_vote_request(&self) -> PreVoteRequest {
+        PreVoteRequest {
+            term: self.current_term + 1,
+            candidate_id: self.config.node_id.clone(),
+            last_log_index: self.last_log_index(),
+            last_log_term: self.last_log_term(),
+        }
+    }
+
+    /// Handle a pre-vote request
+    ///
+    /// Grants the pre-vote if a real vote would be granted in the requested term
+    /// and we have not heard from a leader within the minimum election timeout.
+    /// Our own term and vote are left untouched.
+    pub fn handle_pre_vote_request(&self, request: PreVoteRequest) -> RaftResult<PreVoteResponse> {
+        debug!("Received pre-vote request from {} for term {}", request.candidate_id, request.term);
+
+        let leader_alive = self.leader_recently_heard();
+
+        let last_log_term = self.last_log_term();
+        let log_ok = request.last_log_term > last_log_term ||
+                    (request.last_log_term == last_log_term &&
+                     request.last_log_index >= self.last_log_index());
+
+        let vote_granted = request.term > self.current_term && log_ok && !leader_alive;
+        debug!("Pre-vote for {} in term {}: {} (log_ok: {}, leader_alive: {})",
+               request.candidate_id, request.term, vote_granted, log_ok, leader_alive);
+
+        Ok(PreVoteResponse {
+            term: self.current_term,
+            vote_granted,
+        })
+    }
+
+    /// Handle a pre-vote response; a quorum of grants starts the real election
+    pub fn handle_pre_vote_response(&mut self, from: &NodeId, response: PreVoteResponse) -> RaftResult<()> {
+        // A rejection from a newer term means we are behind
+        if response.term > self.current_term && !response.vote_granted {
+            return self.become_follower(response.term);
+        }
+
+        if self.state != NodeState::PreCandidate || !response.vote_granted {
+            return Ok(());
+        }
+
+        self.votes_received.insert(from.clone());
+        if self.cluster.is_quorum(|id| self.votes_received.contains(id)) {
+            return self.start_election();
+        }
+
+        Ok(())
+    }
+
     /// Start an election
     pub fn start_election(&mut self) -> RaftResult<()> {
         info!("Starting election for term {}", self.current_term + 1);
@@ -232,37 +429,140 @@ impl RaftNode {
         self.voted_for = Some(self.config.node_id.clone());
         self.leader_id = None;
         self.reset_election_timeout();
+        self.persist_hard_state()?;
 
         // Clear votes and vote for ourselves
         self.votes_received.clear();
         self.votes_received.insert(self.config.node_id.clone());
 
-        // If we're the only node, become leader immediately
-        if self.config.peers.is_empty() {
-            self.become_leader();
+        // If our own vote is a quorum, become leader immediately
+        if self.cluster.is_quorum(|id| self.votes_received.contains(id)) {
+            self.become_leader()?;
         }
 
         Ok(())
     }
 
+    /// Build the vote request for the current election
+    pub fn vote_request(&self) -> VoteRequest {
+        VoteRequest {
+            term: self.current_term,
+            candidate_id: self.config.node_id.clone(),
+            last_log_index: self.last_log_index(),
+            last_log_term: self.last_log_term(),
+            leadership_transfer: self.leadership_transfer,
+        }
+    }
+
+    /// Handle a TimeoutNow request by starting an election immediately
+    ///
+    /// The leader only sends it once our log matches its own, so the election
+    /// skips the pre-vote round and its vote requests bypass leader stickiness.
+    pub fn handle_timeout_now(&mut self, request: TimeoutNowRequest) -> RaftResult<TimeoutNowResponse> {
+        if request.term < self.current_term {
+            debug!("Ignoring TimeoutNow from {} for stale term {}", request.leader_id, request.term);
+            return Ok(TimeoutNowResponse { term: self.current_term });
+        }
+
+        if request.term > self.current_term {
+            self.become_follower(request.term)?;
+        }
+
+        if self.state == NodeState::Leader || !self.is_voter() {
+            return Ok(TimeoutNowResponse { term: self.current_term });
+        }
+
+        info!("Received TimeoutNow from {}, starting election", request.leader_id);
+        let term = self.current_term;
+        self.leadership_transfer = true;
+        self.start_election()?;
+
+        Ok(TimeoutNowResponse { term })
+    }
+
     /// Become the leader
-    fn become_leader(&mut self) {
+    ///
+    /// Appends a NoOp entry in the new term right away. Entries from earlier
+    /// terms only commit along with one from the current term, so without it
+    /// they would wait for the next client command.
+    fn become_leader(&mut self) -> RaftResult<()> {
         info!("Becoming leader for term {}", self.current_term);
         self.state = NodeState::Leader;
         self.leader_id = Some(self.config.node_id.clone());
+        self.transfer = None;
 
         // Initialize leader state
-        let next_index = self.log.len() as LogIndex + 1;
-        self.next_index.clear();
+        let next_index = self.last_log_index() + 1;
+        self.progress.clear();
         self.match_index.clear();
 
-        for peer in &self.config.peers {
-            self.next_index.insert(peer.clone(), next_index);
-            self.match_index.insert(peer.clone(), 0);
+        for peer in self.cluster.members() {
+            if peer.node_id != self.config.node_id {
+                self.progress.insert(peer.node_id.clone(), Progress::new(next_index));
+                self.match_index.insert(peer.node_id.clone(), 0);
+            }
+        }
+
+        let entry = LogEntry {
+            index: next_index,
+            term: self.current_term,
+            entry_type: EntryType::NoOp,
+            data: Bytes::new(),
+            client_id: None,
+            sequence_number: None,
+        };
+        self.storage.append(std::slice::from_ref(&entry))?;
+        self.log.append(vec![entry])?;
+        self.match_index.insert(self.config.node_id.clone(), next_index);
+
+        // Replicate the NoOp on the next tick; a single-node cluster commits it now
+        self.replication.trigger_heartbeat();
+        self.update_commit_index()
+    }
+
+    /// Step down to follower in a newer term
+    fn become_follower(&mut self, term: Term) -> RaftResult<()> {
+        info!("Stepping down to follower for term {}", term);
+        self.current_term = term;
+        self.state = NodeState::Follower;
+        self.voted_for = None;
+        self.leader_id = None;
+        self.persist_hard_state()
+    }
+
+    /// Persist the current term and vote
+    fn persist_hard_state(&mut self) -> RaftResult<()> {
+        self.storage.save_hard_state(self.current_term, self.voted_for.as_ref())
+    }
+
+    /// Check if we are the leader or heard from one within the minimum election timeout
+    fn leader_recently_heard(&self) -> bool {
+        self.state == NodeState::Leader
+            || (self.leader_id.is_some()
+                && self.last_heartbeat.elapsed() < Duration::from_millis(self.config.election_timeout_min))
+    }
+
+    /// Get the longest possible election timeout
+    pub fn max_election_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.election_timeout_max)
+    }
+
+    /// Step down if the peers heard from recently no longer form a quorum (CheckQuorum)
+    ///
+    /// A leader cut off from the majority can never commit anything, so it stops
+    /// accepting commands instead of staying leader forever. The term is kept.
+    pub fn check_quorum(&mut self, recently_heard: impl Fn(&NodeId) -> bool) {
+        if self.state != NodeState::Leader {
+            return;
         }
 
-        // Send initial heartbeat (empty append entries)
-        // This would be handled by the server layer
+        let node_id = &self.config.node_id;
+        if !self.cluster.is_quorum(|id| id == node_id || recently_heard(id)) {
+            info!("Stepping down in term {}: lost contact with a quorum", self.current_term);
+            self.state = NodeState::Follower;
+            self.leader_id = None;
+            self.reset_election_timeout();
+        }
     }
 
     /// Check if election timeout has occurred
@@ -284,17 +584,13 @@ impl RaftNode {
 
     /// Handle a vote response
     pub fn handle_vote_response(&mut self, from: &NodeId, response: VoteResponse) -> RaftResult<()> {
-        // Only process if we're still a candidate and the term matches
-        if self.state != NodeState::Candidate || response.term != self.current_term {
-            return Ok(());
-        }
-
         // If term is newer, step down
         if response.term > self.current_term {
-            self.current_term = response.term;
-            self.state = NodeState::Follower;
-            self.voted_for = None;
-            self.leader_id = None;
+            return self.become_follower(response.term);
+        }
+
+        // Only process if we're still a candidate and the term matches
+        if self.state != NodeState::Candidate || response.term != self.current_term {
             return Ok(());
         }
 
@@ -302,82 +598,756 @@ impl RaftNode {
         if response.vote_granted {
             self.votes_received.insert(from.clone());
 
-            // Check if we have majority
-            let total_nodes = 1 + self.config.peers.len(); // Include ourselves
-            let majority = total_nodes / 2 + 1;
-
-            if self.votes_received.len() >= majority {
-                self.become_leader();
+            // Check if we have a majority of the active configuration (both halves when joint)
+            if self.cluster.is_quorum(|id| self.votes_received.contains(id)) {
+                self.become_leader()?;
             }
         }
 
         Ok(())
     }
     
+    /// Start handing leadership over to `target` (leaders only)
+    ///
+    /// New commands and membership changes are rejected until the transfer
+    /// ends. Once the target's log has caught up, `timeout_now_request` yields
+    /// the TimeoutNow message that makes it start an election.
+    pub fn transfer_leadership(&mut self, target: &NodeId) -> RaftResult<()> {
+        if self.state != NodeState::Leader {
+            return Err(RaftError::NotLeader);
+        }
+
+        if let Some(target) = self.transfer_target() {
+            return Err(RaftError::TransferInProgress { target: target.clone() });
+        }
+
+        if target == &self.config.node_id || !self.cluster.is_voter(target) {
+            return Err(RaftError::Configuration(format!(
+                "{} is not another voting member of the cluster", target
+            )));
+        }
+
+        info!("Transferring leadership to {} in term {}", target, self.current_term);
+        self.transfer = Some((target.clone(), Instant::now()));
+        self.replication.trigger_heartbeat();
+        Ok(())
+    }
+
+    /// Get the target of the leadership transfer in progress, if any
+    pub fn transfer_target(&self) -> Option<&NodeId> {
+        match &self.transfer {
+            Some((target, _)) if self.state == NodeState::Leader => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Build the TimeoutNow request once the transfer target has the whole log
+    pub fn timeout_now_request(&self) -> Option<(NodeId, TimeoutNowRequest)> {
+        let target = self.transfer_target()?;
+        if self.match_index(target)? < self.last_log_index() {
+            return None;
+        }
+
+        Some((target.clone(), TimeoutNowRequest {
+            term: self.current_term,
+            leader_id: self.config.node_id.clone(),
+        }))
+    }
+
+    /// Abandon a leadership transfer that has not finished within an election timeout
+    ///
+    /// Returns the error to report for the abandoned transfer.
+    pub fn check_transfer_timeout(&mut self) -> Option<RaftError> {
+        let (_, started) = self.transfer.as_ref()?;
+        if started.elapsed() < self.max_election_timeout() {
+            return None;
+        }
+
+        let (target, _) = self.transfer.take()?;
+        warn!("Leadership transfer to {} timed out", target);
+        Some(RaftError::TransferTimeout { target })
+    }
+
     /// Submit a command to the log
     pub fn submit_command(&mut self, command: Vec<u8>) -> RaftResult<LogIndex> {
+        self.submit_client_command(command, None, None)
+    }
+
+    /// Submit a command to the log on behalf of a client session
+    ///
+    /// The client ID and sequence number travel with the entry so that every
+    /// replica can recognise a retried command when applying it.
+    pub fn submit_client_command(
+        &mut self,
+        command: Vec<u8>,
+        client_id: Option<String>,
+        sequence_number: Option<u64>,
+    ) -> RaftResult<LogIndex> {
+        self.submit_client_commands(vec![(command, client_id, sequence_number)])
+    }
+
+    /// Append a batch of client commands to the log in one write
+    ///
+    /// Each command comes with its optional client ID and sequence number.
+    /// Returns the index of the first entry; the others follow it in order.
+    pub fn submit_client_commands(
+        &mut self,
+        commands: Vec<(Vec<u8>, Option<String>, Option<u64>)>,
+    ) -> RaftResult<LogIndex> {
         // Only leaders can accept commands
         if self.state != NodeState::Leader {
             return Err(RaftError::NotLeader);
         }
 
-        // Create new log entry
-        let entry = LogEntry {
-            index: self.log.len() as LogIndex + 1,
-            term: self.current_term,
-            entry_type: EntryType::Command,
-            data: command,
-            client_id: None,
-            sequence_number: None,
+        if let Some(target) = self.transfer_target() {
+            return Err(RaftError::TransferInProgress { target: target.clone() });
+        }
+
+        let bytes = commands.iter().map(|(data, _, _)| data.len()).sum();
+        self.check_uncommitted_limits(self.uncommitted_load(), commands.len(), bytes)?;
+
+        // Create new log entries
+        let first_index = self.last_log_index() + 1;
+        let entries: Vec<LogEntry> = commands
+            .into_iter()
+            .zip(first_index..)
+            .map(|((data, client_id, sequence_number), index)| LogEntry {
+                index,
+                term: self.current_term,
+                entry_type: EntryType::Command,
+                data: data.into(),
+                client_id,
+                sequence_number,
+            })
+            .collect();
+        let Some(last_index) = entries.last().map(|entry| entry.index) else {
+            return Ok(first_index);
         };
 
-        let log_index = entry.index;
-        self.log.push(entry);
+        self.storage.append(&entries)?;
+        self.log.append(entries)?;
 
-        info!("Added command to log at index {}", log_index);
+        if first_index == last_index {
+            info!("Added command to log at index {}", first_index);
+        } else {
+            info!("Added commands to log at indexes {}..={}", first_index, last_index);
+        }
 
         // Update match index for ourselves
-        self.match_index.insert(self.config.node_id.clone(), log_index);
+        self.match_index.insert(self.config.node_id.clone(), last_index);
 
-        Ok(log_index)
+        Ok(first_index)
+    }
+
+    /// Record the read index for a linearizable read (leaders only)
+    ///
+    /// Reads are safe once `last_applied` reaches the returned commit index and
+    /// a heartbeat round sent after this call has been acknowledged by a quorum
+    /// (or the leader's lease covers it). Until the NoOp appended on election
+    /// has committed the leader's commit index may be stale, so it refuses
+    /// with `ReadIndexNotReady`.
+    pub fn read_index(&self) -> RaftResult<LogIndex> {
+        if self.state != NodeState::Leader {
+            return Err(RaftError::NotLeader);
+        }
+
+        if !self.committed_in_current_term() {
+            return Err(RaftError::ReadIndexNotReady);
+        }
+
+        Ok(self.commit_index)
+    }
+
+    /// Check if an entry from the current term has committed
+    ///
+    /// On a leader this becomes true once the NoOp appended on election commits.
+    pub fn committed_in_current_term(&self) -> bool {
+        self.term_at(self.commit_index) == self.current_term
+    }
+
+    /// How long a quorum-acknowledged heartbeat round keeps the leader's lease
+    ///
+    /// Followers ignore vote requests for `election_timeout_min` after hearing
+    /// from the leader, so no other leader can be elected within that window
+    /// of the round being sent, less the allowance for clock drift.
+    pub fn lease_duration(&self) -> Duration {
+        Duration::from_millis(self.config.election_timeout_min.saturating_sub(self.config.clock_drift))
+    }
+
+    /// Get how long ago this node last heard from a leader (zero on the leader)
+    ///
+    /// `None` when no leader is known.
+    pub fn last_leader_contact(&self) -> Option<Duration> {
+        match (&self.state, &self.leader_id) {
+            (NodeState::Leader, _) => Some(Duration::ZERO),
+            (_, Some(_)) => Some(self.last_heartbeat.elapsed()),
+            _ => None,
+        }
+    }
+
+    /// Make the next heartbeat check fire immediately
+    pub fn trigger_heartbeat(&mut self) {
+        self.replication.trigger_heartbeat();
     }
 
     /// Check if we should send heartbeats (for leaders)
     pub fn should_send_heartbeat(&self) -> bool {
+        self.state == NodeState::Leader && self.replication.should_send_heartbeat()
+    }
+
+    /// Record that a round of heartbeats has just been sent
+    pub fn reset_heartbeat_timer(&mut self) {
+        self.replication.reset_heartbeat_timer();
+    }
+
+    /// Build the next append entries request for a peer (leaders only)
+    ///
+    /// The request starts at the peer's `next_index` and carries at most
+    /// `max_append_entries` entries; it is an empty heartbeat when the peer
+    /// is already up to date. Returns `None` when the entries the peer needs
+    /// have been compacted away; `snapshot_request_for` covers that case.
+    pub fn append_request_for(&self, peer_id: &NodeId) -> Option<AppendRequest> {
+        self.append_request_from(&self.progress_of(peer_id))
+    }
+
+    /// Build the append entries request starting at `progress.next_index`
+    fn append_request_from(&self, progress: &Progress) -> Option<AppendRequest> {
+        if self.state != NodeState::Leader {
+            return None;
+        }
+
+        let last_index = self.last_log_index();
+        let next_index = progress.next_index.clamp(1, last_index + 1);
+        if next_index <= self.log.last_included_index() {
+            return None;
+        }
+        let prev_log_index = next_index - 1;
+        let prev_log_term = self.term_at(prev_log_index);
+
+        let end = std::cmp::min(
+            last_index,
+            prev_log_index + self.config.max_append_entries as LogIndex,
+        );
+        let entries = self.log.slice(next_index, end).to_vec();
+
+        Some(self.replication.create_append_request(
+            self.current_term,
+            &self.config.node_id,
+            prev_log_index,
+            prev_log_term,
+            entries,
+            self.commit_index,
+        ))
+    }
+
+    /// Build the next requests for a peer's replicator (leaders only)
+    ///
+    /// Up to `capacity` consecutive batches are pipelined, moving
+    /// `progress.next_index` past each one without waiting for the answers; a
+    /// rejection rewinds it in `handle_replication_response`. A peer that is
+    /// still being probed gets a single request, and one that needs entries
+    /// compacted away gets the next snapshot chunk. Up-to-date peers get an
+    /// empty heartbeat.
+    pub fn replication_requests(&self, progress: &mut Progress, capacity: usize) -> Vec<ReplicationRequest> {
+        if self.state != NodeState::Leader || capacity == 0 {
+            return Vec::new();
+        }
+        if let Some(request) = self.snapshot_request_from(progress) {
+            return vec![ReplicationRequest::Snapshot(request)];
+        }
+
+        let depth = if progress.probing { 1 } else { capacity };
+        let mut requests = Vec::new();
+        while requests.len() < depth {
+            let Some(request) = self.append_request_from(progress) else {
+                break;
+            };
+            let last_sent = request.prev_log_index + request.entries.len() as LogIndex;
+            progress.next_index = last_sent + 1;
+            requests.push(ReplicationRequest::Append(request));
+            if last_sent >= self.last_log_index() {
+                break;
+            }
+        }
+        requests
+    }
+
+    /// Handle a peer's answer to a request built by `replication_requests`
+    ///
+    /// Updates `progress` and the leader's view of how far the peer matches
+    /// our log, which may advance the commit index.
+    pub fn handle_replication_response(
+        &mut self,
+        peer_id: &NodeId,
+        progress: &mut Progress,
+        request: &ReplicationRequest,
+        response: ReplicationResponse,
+    ) -> RaftResult<()> {
+        match (request, response) {
+            (ReplicationRequest::Append(request), ReplicationResponse::Append(response)) => {
+                self.apply_append_response(peer_id, progress, request, response)
+            }
+            (ReplicationRequest::Snapshot(request), ReplicationResponse::Snapshot(response)) => {
+                self.apply_install_snapshot_response(peer_id, progress, request, response)
+            }
+            _ => unreachable!("response kind always matches the request"),
+        }
+    }
+
+    /// Handle an append entries response from a peer
+    ///
+    /// `request` must be the request the response answers; it tells us which
+    /// entries the peer now holds. A successful response may advance the commit index.
+    pub fn handle_append_response(
+        &mut self,
+        peer_id: &NodeId,
+        request: &AppendRequest,
+        response: AppendResponse,
+    ) -> RaftResult<()> {
+        let mut progress = self.progress_of(peer_id);
+        self.apply_append_response(peer_id, &mut progress, request, response)
+    }
+
+    fn apply_append_response(
+        &mut self,
+        peer_id: &NodeId,
+        progress: &mut Progress,
+        request: &AppendRequest,
+        response: AppendResponse,
+    ) -> RaftResult<()> {
+        if response.term > self.current_term {
+            return self.become_follower(response.term);
+        }
+
+        // Ignore responses to requests from an earlier leadership
+        if self.state != NodeState::Leader || request.term != self.current_term {
+            return Ok(());
+        }
+
+        let log = &self.log;
+        self.replication.process_append_response(
+            progress,
+            request,
+            &response,
+            |term| log.last_index_of_term(term),
+        )?;
+        self.record_progress(peer_id, progress);
+
+        if response.success {
+            self.update_commit_index()?;
+        } else {
+            debug!("Append entries rejected by {}, next index now {}",
+                   peer_id, progress.next_index);
+        }
+
+        Ok(())
+    }
+
+    /// Build the next snapshot chunk for a peer that is behind the log start (leaders only)
+    ///
+    /// Chunks are at most `snapshot_chunk_size` bytes and are sent one at a
+    /// time; the transfer restarts from offset 0 when a newer snapshot
+    /// replaces the one being sent.
+    pub fn snapshot_request_for(&self, peer_id: &NodeId) -> Option<InstallSnapshotRequest> {
+        self.snapshot_request_from(&self.progress_of(peer_id))
+    }
+
+    fn snapshot_request_from(&self, progress: &Progress) -> Option<InstallSnapshotRequest> {
         if self.state != NodeState::Leader {
-            return false;
+            return None;
+        }
+
+        let snapshot = self.snapshot.as_ref()?;
+        if progress.next_index > snapshot.last_included_index {
+            return None;
         }
 
-        let heartbeat_interval = Duration::from_millis(self.config.heartbeat_interval);
-        self.last_heartbeat.elapsed() >= heartbeat_interval
+        let offset = match progress.snapshot_offset {
+            Some((index, offset)) if index == snapshot.last_included_index => offset,
+            _ => 0,
+        };
+        let start = (offset as usize).min(snapshot.data.len());
+        let end = std::cmp::min(snapshot.data.len(), start + self.config.snapshot_chunk_size.max(1));
+
+        Some(InstallSnapshotRequest {
+            term: self.current_term,
+            leader_id: self.config.node_id.clone(),
+            last_included_index: snapshot.last_included_index,
+            last_included_term: snapshot.last_included_term,
+            config: snapshot.config.clone(),
+            offset: start as u64,
+            data: Bytes::copy_from_slice(&snapshot.data[start..end]),
+            done: end == snapshot.data.len(),
+        })
     }
 
-    /// Update commit index based on majority replication
-    pub fn update_commit_index(&mut self) {
+    /// Handle an install snapshot response from a peer
+    ///
+    /// After the last chunk the peer's `next_index` moves past the snapshot;
+    /// its `match_index` only advances once an append entries request confirms it.
+    pub fn handle_install_snapshot_response(
+        &mut self,
+        peer_id: &NodeId,
+        request: &InstallSnapshotRequest,
+        response: InstallSnapshotResponse,
+    ) -> RaftResult<()> {
+        let mut progress = self.progress_of(peer_id);
+        self.apply_install_snapshot_response(peer_id, &mut progress, request, response)
+    }
+
+    fn apply_install_snapshot_response(
+        &mut self,
+        peer_id: &NodeId,
+        progress: &mut Progress,
+        request: &InstallSnapshotRequest,
+        response: InstallSnapshotResponse,
+    ) -> RaftResult<()> {
+        if response.term > self.current_term {
+            return self.become_follower(response.term);
+        }
+
+        if self.state != NodeState::Leader || request.term != self.current_term {
+            return Ok(());
+        }
+
+        if request.done {
+            progress.snapshot_offset = None;
+            progress.next_index = progress.next_index.max(request.last_included_index + 1);
+            info!("Finished sending snapshot up to index {} to {}", request.last_included_index, peer_id);
+        } else {
+            let offset = request.offset + request.data.len() as u64;
+            progress.snapshot_offset = Some((request.last_included_index, offset));
+        }
+        self.record_progress(peer_id, progress);
+
+        Ok(())
+    }
+
+    /// Get a peer's replication progress, or a fresh one starting at the log end
+    fn progress_of(&self, peer_id: &NodeId) -> Progress {
+        self.progress
+            .get(peer_id)
+            .cloned()
+            .unwrap_or_else(|| Progress::new(self.last_log_index() + 1))
+    }
+
+    /// Remember a peer's replication progress
+    fn record_progress(&mut self, peer_id: &NodeId, progress: &Progress) {
+        self.match_index.insert(peer_id.clone(), progress.match_index);
+        self.progress.insert(peer_id.clone(), progress.clone());
+    }
+
+    /// Handle an install snapshot request from the leader
+    ///
+    /// Chunks are buffered until the last one arrives; then the snapshot is made
+    /// durable and replaces the log prefix it covers. Log entries that follow it
+    /// are kept only if the log agrees with the snapshot's last entry.
+    pub fn handle_install_snapshot(&mut self, request: InstallSnapshotRequest) -> RaftResult<InstallSnapshotResponse> {
+        debug!("Received snapshot chunk at offset {} from {}", request.offset, request.leader_id);
+
+        if request.term < self.current_term {
+            return Ok(InstallSnapshotResponse { term: self.current_term });
+        }
+
+        self.reset_election_timeout();
+        if request.term > self.current_term {
+            self.current_term = request.term;
+            self.voted_for = None;
+            self.persist_hard_state()?;
+        }
+        self.state = NodeState::Follower;
+        self.leader_id = Some(request.leader_id.clone());
+
+        // Nothing to do for a snapshot that is not ahead of what we have committed
+        if request.last_included_index <= self.commit_index {
+            self.incoming_snapshot = None;
+            return Ok(InstallSnapshotResponse { term: self.current_term });
+        }
+
+        if request.offset == 0 {
+            self.incoming_snapshot = Some(Snapshot {
+                last_included_index: request.last_included_index,
+                last_included_term: request.last_included_term,
+                config: request.config.clone(),
+                data: Vec::new(),
+            });
+        }
+
+        // Drop chunks that do not continue the snapshot being received
+        let Some(incoming) = self.incoming_snapshot.as_mut() else {
+            return Ok(InstallSnapshotResponse { term: self.current_term });
+        };
+        if incoming.last_included_index != request.last_included_index
+            || incoming.data.len() as u64 != request.offset
+        {
+            debug!("Ignoring out-of-order snapshot chunk at offset {}", request.offset);
+            return Ok(InstallSnapshotResponse { term: self.current_term });
+        }
+        incoming.data.extend_from_slice(&request.data);
+
+        if !request.done {
+            return Ok(InstallSnapshotResponse { term: self.current_term });
+        }
+
+        let snapshot = self.incoming_snapshot.take().expect("snapshot is being received");
+        let (index, term) = (snapshot.last_included_index, snapshot.last_included_term);
+        self.storage.save_snapshot(&snapshot)?;
+        if self.log.term(index) == Some(term) {
+            self.log.compact(index, term);
+        } else {
+            self.storage.truncate(index + 1)?;
+            self.log.reset(index, term);
+        }
+        self.commit_index = self.commit_index.max(index);
+        self.snapshot = Some(snapshot);
+        self.refresh_cluster();
+        info!("Installed snapshot up to index {} (term {})", index, term);
+
+        Ok(InstallSnapshotResponse { term: self.current_term })
+    }
+
+    /// Take a snapshot of the state machine at `index` and discard the log up to it
+    ///
+    /// `data` must be the state machine's snapshot after applying exactly the
+    /// entries up to `index`, which must already be applied.
+    pub fn compact(&mut self, index: LogIndex, data: Vec<u8>) -> RaftResult<()> {
+        if index > self.last_applied {
+            return Err(RaftError::Storage(format!(
+                "cannot snapshot at index {} past last applied {}", index, self.last_applied
+            )));
+        }
+        if index <= self.log.last_included_index() {
+            return Ok(());
+        }
+
+        let term = self.log.term(index).ok_or(RaftError::LogInconsistency { index })?;
+        let snapshot = Snapshot {
+            last_included_index: index,
+            last_included_term: term,
+            config: self.config_at(index),
+            data,
+        };
+        self.storage.save_snapshot(&snapshot)?;
+        self.log.compact(index, term);
+        self.snapshot = Some(snapshot);
+        info!("Compacted log up to index {} (term {})", index, term);
+
+        Ok(())
+    }
+
+    /// Get the snapshot the state machine must be restored from, if it is ahead of it
+    pub fn snapshot_to_restore(&self) -> Option<&Snapshot> {
+        self.snapshot
+            .as_ref()
+            .filter(|snapshot| snapshot.last_included_index > self.last_applied)
+    }
+
+    /// Start a membership change to the given set of nodes (leaders only)
+    ///
+    /// Appends the joint configuration C_old,new, which takes effect at once;
+    /// C_new follows automatically once the joint configuration commits.
+    /// Changes that leave the voters untouched (adding or removing learners)
+    /// append C_new directly. Returns the index of the first configuration entry.
+    pub fn propose_membership(&mut self, nodes: Vec<PeerInfo>) -> RaftResult<LogIndex> {
         if self.state != NodeState::Leader {
-            return;
+            return Err(RaftError::NotLeader);
         }
 
-        // Find the highest index that's replicated on a majority of servers
-        let mut indices: Vec<LogIndex> = self.match_index.values().cloned().collect();
-        indices.push(self.log.len() as LogIndex); // Include our own log
-        indices.sort_unstable();
-        indices.reverse();
+        if let Some(target) = self.transfer_target() {
+            return Err(RaftError::TransferInProgress { target: target.clone() });
+        }
 
-        let majority_index = indices.len() / 2;
-        if majority_index < indices.len() {
-            let new_commit_index = indices[majority_index];
+        if self.cluster.is_joint() || self.cluster.config_index > self.commit_index {
+            return Err(RaftError::Configuration(
+                "a membership change is already in progress".to_string(),
+            ));
+        }
 
-            // Only commit entries from current term
-            if new_commit_index > self.commit_index {
-                if let Some(entry) = self.log.get((new_commit_index - 1) as usize) {
-                    if entry.term == self.current_term {
-                        self.commit_index = new_commit_index;
-                        info!("Updated commit index to {}", self.commit_index);
-                    }
+        if !nodes.iter().any(|n| n.voting) {
+            return Err(RaftError::Configuration(
+                "the new configuration has no voting members".to_string(),
+            ));
+        }
+
+        let voters = |nodes: &[PeerInfo]| -> std::collections::BTreeSet<NodeId> {
+            nodes.iter().filter(|n| n.voting).map(|n| n.node_id.clone()).collect()
+        };
+        let old_nodes = if voters(&nodes) == voters(&self.cluster.nodes) {
+            None
+        } else {
+            Some(self.cluster.nodes.clone())
+        };
+
+        let index = self.append_config(ClusterConfig { nodes, old_nodes, config_index: 0 })?;
+        self.update_commit_index()?;
+
+        Ok(index)
+    }
+
+    /// Add a non-voting learner that receives the log but is left out of quorums
+    pub fn add_learner(&mut self, mut learner: PeerInfo) -> RaftResult<LogIndex> {
+        if self.cluster.nodes.iter().any(|n| n.node_id == learner.node_id) {
+            return Err(RaftError::Configuration(format!(
+                "{} is already a member of the cluster", learner.node_id
+            )));
+        }
+
+        learner.voting = false;
+        let mut nodes = self.cluster.nodes.clone();
+        nodes.push(learner);
+        self.propose_membership(nodes)
+    }
+
+    /// Promote a learner to a voter once it is within `max_learner_lag` entries of the leader
+    pub fn promote_learner(&mut self, node_id: &NodeId) -> RaftResult<LogIndex> {
+        if self.state != NodeState::Leader {
+            return Err(RaftError::NotLeader);
+        }
+
+        let is_learner = self.cluster.nodes
+            .iter()
+            .any(|n| &n.node_id == node_id && !n.voting);
+        if !is_learner {
+            return Err(RaftError::Configuration(format!("{} is not a learner", node_id)));
+        }
+
+        let lag = self.last_log_index() - self.match_index(node_id).unwrap_or(0);
+        if lag > self.config.max_learner_lag {
+            return Err(RaftError::Configuration(format!(
+                "learner {} is {} entries behind the leader", node_id, lag
+            )));
+        }
+
+        let nodes = self.cluster.nodes
+            .iter()
+            .cloned()
+            .map(|mut n| {
+                if &n.node_id == node_id {
+                    n.voting = true;
                 }
+                n
+            })
+            .collect();
+        self.propose_membership(nodes)
+    }
+
+    /// Append a configuration entry and switch to it immediately
+    fn append_config(&mut self, mut cluster: ClusterConfig) -> RaftResult<LogIndex> {
+        let index = self.last_log_index() + 1;
+        cluster.config_index = index;
+
+        let entry = LogEntry {
+            index,
+            term: self.current_term,
+            entry_type: EntryType::Configuration,
+            data: serde_json::to_vec(&cluster)?.into(),
+            client_id: None,
+            sequence_number: None,
+        };
+        self.storage.append(std::slice::from_ref(&entry))?;
+        self.log.append(vec![entry])?;
+
+        // New members start replicating from the end of the log and back off from there
+        for peer in cluster.members() {
+            if peer.node_id != self.config.node_id {
+                self.progress.entry(peer.node_id.clone()).or_insert_with(|| Progress::new(index + 1));
+                self.match_index.entry(peer.node_id.clone()).or_insert(0);
+            }
+        }
+
+        info!("Appended configuration at index {}: {:?}", index, cluster);
+        self.cluster = cluster;
+        Ok(index)
+    }
+
+    /// Update commit index based on majority replication
+    ///
+    /// The majority is taken in the active configuration, in both halves of a
+    /// joint configuration. Committing C_old,new appends C_new; once C_new
+    /// commits, a leader that is not part of it steps down.
+    pub fn update_commit_index(&mut self) -> RaftResult<()> {
+        if self.state != NodeState::Leader {
+            return Ok(());
+        }
+
+        // Find the highest index that's replicated on a quorum, including our own log
+        let node_id = &self.config.node_id;
+        let last_log_index = self.last_log_index();
+        let match_index = &self.match_index;
+        let new_commit_index = self.cluster.quorum_index(|id| {
+            if id == node_id {
+                last_log_index
+            } else {
+                match_index.get(id).copied().unwrap_or(0)
             }
+        });
+
+        // Only commit entries from current term
+        if new_commit_index > self.commit_index && self.term_at(new_commit_index) == self.current_term {
+            self.commit_index = new_commit_index;
+            info!("Updated commit index to {}", self.commit_index);
+        }
+
+        if self.cluster.config_index > self.commit_index {
+            return Ok(());
+        }
+
+        if self.cluster.is_joint() {
+            let new_config = ClusterConfig {
+                nodes: self.cluster.nodes.clone(),
+                old_nodes: None,
+                config_index: 0,
+            };
+            self.append_config(new_config)?;
+            return self.update_commit_index();
         }
+
+        if !self.cluster.is_voter(&self.config.node_id) {
+            info!("Stepping down: removed from the cluster configuration");
+            self.state = NodeState::Follower;
+            self.leader_id = None;
+        }
+
+        Ok(())
+    }
+
+    /// Get the active cluster configuration
+    pub fn cluster(&self) -> &ClusterConfig {
+        &self.cluster
+    }
+
+    /// Check if this node is a voting member of the active configuration
+    pub fn is_voter(&self) -> bool {
+        self.cluster.is_voter(&self.config.node_id)
+    }
+
+    /// Get the latest configuration at or before `index`, if one is recorded
+    /// in the log or the snapshot
+    fn config_at(&self, index: LogIndex) -> Option<ClusterConfig> {
+        let from_log = self.log
+            .slice(self.log.first_index(), index)
+            .iter()
+            .rev()
+            .filter(|e| e.entry_type == EntryType::Configuration)
+            .find_map(|entry| match serde_json::from_slice::<ClusterConfig>(&entry.data) {
+                Ok(mut cluster) => {
+                    cluster.config_index = entry.index;
+                    Some(cluster)
+                }
+                Err(e) => {
+                    warn!("Ignoring undecodable configuration at index {}: {}", entry.index, e);
+                    None
+                }
+            });
+
+        from_log.or_else(|| self.snapshot.as_ref().and_then(|s| s.config.clone()))
+    }
+
+    /// Switch to the latest configuration in the log, falling back to the static one
+    fn refresh_cluster(&mut self) {
+        self.cluster = self
+            .config_at(self.log.last_index())
+            .unwrap_or_else(|| Self::initial_cluster(&self.config));
     }
 
     /// Get the current leader ID
@@ -385,6 +1355,11 @@ impl RaftNode {
         self.leader_id.as_ref()
     }
 
+    /// Get the address of the current leader, if it is known and in the configuration
+    pub fn leader_address(&self) -> Option<&str> {
+        self.leader_id.as_ref().and_then(|leader| self.cluster.address_of(leader))
+    }
+
     /// Get the commit index
     pub fn commit_index(&self) -> LogIndex {
         self.commit_index
@@ -395,25 +1370,102 @@ impl RaftNode {
         self.last_applied
     }
 
-    /// Get the log length
+    /// Get the log length, including entries compacted into the snapshot
     pub fn log_length(&self) -> usize {
-        self.log.len()
+        self.log.last_index() as usize
+    }
+
+    /// Get the index of the last log entry
+    pub fn last_log_index(&self) -> LogIndex {
+        self.log.last_index()
+    }
+
+    /// Get the term of the last log entry
+    pub fn last_log_term(&self) -> Term {
+        self.log.last_term()
+    }
+
+    /// Get the index of the last entry covered by the latest snapshot
+    pub fn snapshot_index(&self) -> LogIndex {
+        self.log.last_included_index()
+    }
+
+    /// Get the term of the entry at `index` (0 for the empty prefix)
+    fn term_at(&self, index: LogIndex) -> Term {
+        self.log.term(index).unwrap_or(0)
+    }
+
+    /// Get the next index to send to a peer (leaders only)
+    pub fn next_index(&self, peer_id: &NodeId) -> Option<LogIndex> {
+        self.progress.get(peer_id).map(|progress| progress.next_index)
+    }
+
+    /// Get a peer's replication progress (leaders only)
+    pub fn progress(&self, peer_id: &NodeId) -> Option<&Progress> {
+        self.progress.get(peer_id)
+    }
+
+    /// Get the number of entries past the commit index and the bytes of
+    /// command data they hold
+    pub fn uncommitted_load(&self) -> (usize, usize) {
+        let entries = self.log.slice(self.commit_index + 1, self.log.last_index());
+        (entries.len(), entries.iter().map(|entry| entry.data.len()).sum())
+    }
+
+    /// Check that appending `entries` entries holding `bytes` bytes on top of
+    /// an uncommitted `load` stays within the configured limits
+    ///
+    /// A lone command larger than the byte limit is still admitted when
+    /// nothing else is uncommitted, so it is not rejected forever.
+    pub fn check_uncommitted_limits(&self, load: (usize, usize), entries: usize, bytes: usize) -> RaftResult<()> {
+        let (load_entries, load_bytes) = load;
+        if load_entries + entries > self.config.max_uncommitted_entries {
+            return Err(RaftError::Overloaded(format!(
+                "{} uncommitted entries, limit is {}", load_entries, self.config.max_uncommitted_entries
+            )));
+        }
+        if load_bytes + bytes > self.config.max_uncommitted_bytes && load_entries + entries > 1 {
+            return Err(RaftError::Overloaded(format!(
+                "{} uncommitted bytes, limit is {}", load_bytes, self.config.max_uncommitted_bytes
+            )));
+        }
+        Ok(())
+    }
+
+    /// Get the number of events the event loop's queue holds
+    pub fn event_queue_capacity(&self) -> usize {
+        self.config.event_queue_capacity.max(1)
+    }
+
+    /// Get the number of append entries requests a peer may have in flight
+    pub fn max_inflight_appends(&self) -> usize {
+        self.config.max_inflight_appends.max(1)
+    }
+
+    /// Get the highest index known to be replicated on a peer (leaders only)
+    pub fn match_index(&self, peer_id: &NodeId) -> Option<LogIndex> {
+        self.match_index.get(peer_id).copied()
     }
 
     /// Mark entries as applied up to the given index
+    ///
+    /// `last_applied` only moves forward and never past the commit index.
     pub fn set_last_applied(&mut self, index: LogIndex) {
-        self.last_applied = index;
+        let index = index.min(self.commit_index);
+        if index > self.last_applied {
+            self.last_applied = index;
+        }
     }
 
     /// Get entries that need to be applied to the state machine
+    ///
+    /// Empty while the state machine is behind the snapshot; it has to be
+    /// restored from `snapshot_to_restore` first.
     pub fn get_entries_to_apply(&self) -> &[LogEntry] {
-        let start = self.last_applied as usize;
-        let end = std::cmp::min(self.commit_index as usize, self.log.len());
-
-        if start < end {
-            &self.log[start..end]
-        } else {
-            &[]
+        if self.last_applied < self.log.last_included_index() {
+            return &[];
         }
+
+        self.log.slice(self.last_applied + 1, self.commit_index)
     }
 }
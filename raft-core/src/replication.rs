@@ -1,11 +1,11 @@
 use crate::types::*;
-use crate::error::RaftError;
 use crate::RaftResult;
+use std::time::Instant;
 
 /// Log replication manager for Raft leaders
 pub struct ReplicationManager {
     heartbeat_interval: u64,
-    last_heartbeat: std::time::Instant,
+    last_heartbeat: Option<Instant>,
 }
 
 impl ReplicationManager {
@@ -13,19 +13,26 @@ impl ReplicationManager {
     pub fn new(heartbeat_interval: u64) -> Self {
         Self {
             heartbeat_interval,
-            last_heartbeat: std::time::Instant::now(),
+            last_heartbeat: Some(Instant::now()),
         }
     }
     
     /// Check if it's time to send heartbeats
     pub fn should_send_heartbeat(&self) -> bool {
-        let elapsed = self.last_heartbeat.elapsed().as_millis() as u64;
-        elapsed >= self.heartbeat_interval
+        match self.last_heartbeat {
+            Some(last) => last.elapsed().as_millis() as u64 >= self.heartbeat_interval,
+            None => true,
+        }
     }
     
     /// Reset the heartbeat timer
     pub fn reset_heartbeat_timer(&mut self) {
-        self.last_heartbeat = std::time::Instant::now();
+        self.last_heartbeat = Some(Instant::now());
+    }
+
+    /// Make the next heartbeat check fire immediately (e.g. right after winning an election)
+    pub fn trigger_heartbeat(&mut self) {
+        self.last_heartbeat = None;
     }
     
     /// Create an append entries request for a peer
@@ -49,14 +56,121 @@ impl ReplicationManager {
     }
     
     /// Process append entries response
+    ///
+    /// On success the peer's `match_index` moves up to the last entry that was
+    /// sent and `next_index` follows it. On rejection `next_index` backs off using
+    /// the follower's conflict hints: if the leader has entries from
+    /// `conflict_term` it resumes just after the last of them, otherwise it jumps
+    /// to `conflict_index`. `last_index_of_term` looks up the leader's last index
+    /// for a term.
     pub fn process_append_response(
-        &mut self,
-        peer_id: &NodeId,
-        response: AppendResponse,
-        next_index: &mut std::collections::HashMap<NodeId, LogIndex>,
-        match_index: &mut std::collections::HashMap<NodeId, LogIndex>,
+        &self,
+        progress: &mut Progress,
+        request: &AppendRequest,
+        response: &AppendResponse,
+        last_index_of_term: impl Fn(Term) -> Option<LogIndex>,
     ) -> RaftResult<()> {
-        // TODO: Implement response processing logic
+        let matched = progress.match_index;
+
+        if response.success {
+            let last_sent = request.prev_log_index + request.entries.len() as LogIndex;
+            progress.match_index = matched.max(last_sent);
+            progress.next_index = progress.next_index.max(progress.match_index + 1);
+            progress.probing = false;
+            return Ok(());
+        }
+
+        // A rejection at or below the known match point is a stale response
+        if request.prev_log_index <= matched {
+            return Ok(());
+        }
+
+        let hinted = match (response.conflict_term, response.conflict_index) {
+            (Some(term), conflict_index) => last_index_of_term(term)
+                .map(|index| index + 1)
+                .or(conflict_index),
+            (None, conflict_index) => conflict_index,
+        };
+
+        // The entry at prev_log_index did not match, so never retry from at or above it
+        progress.next_index = hinted
+            .unwrap_or(request.prev_log_index)
+            .min(request.prev_log_index)
+            .min(progress.next_index)
+            .max(matched + 1);
+        progress.probing = true;
+
         Ok(())
     }
 }
+
+/// How far replication to one peer has got, as the leader sees it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// Index of the next entry to send
+    pub next_index: LogIndex,
+    /// Highest index known to be in the peer's log
+    pub match_index: LogIndex,
+    /// Whether the peer's log has yet to be found to line up at `next_index`;
+    /// requests are not pipelined while probing
+    pub probing: bool,
+    /// Snapshot being streamed to the peer: its last included index and the
+    /// offset of the next chunk
+    pub snapshot_offset: Option<(LogIndex, u64)>,
+}
+
+impl Progress {
+    /// Progress of a peer about which nothing is known yet
+    pub fn new(next_index: LogIndex) -> Self {
+        Self {
+            next_index,
+            match_index: 0,
+            probing: true,
+            snapshot_offset: None,
+        }
+    }
+
+    /// Rewind `next_index` after an append entries request got no answer
+    ///
+    /// Pipelined requests move `next_index` past their entries when they are
+    /// built, so the entries of a lost request have to be sent again.
+    pub fn rewind(&mut self, request: &AppendRequest) {
+        self.next_index = self.next_index
+            .min(request.prev_log_index + 1)
+            .max(self.match_index + 1);
+    }
+}
+
+/// A replication message for one peer, chosen from its progress
+#[derive(Debug, Clone)]
+pub enum ReplicationRequest {
+    Append(AppendRequest),
+    Snapshot(InstallSnapshotRequest),
+}
+
+impl ReplicationRequest {
+    /// Term the request was sent in
+    pub fn term(&self) -> Term {
+        match self {
+            ReplicationRequest::Append(request) => request.term,
+            ReplicationRequest::Snapshot(request) => request.term,
+        }
+    }
+}
+
+/// The peer's answer to a `ReplicationRequest`
+#[derive(Debug, Clone)]
+pub enum ReplicationResponse {
+    Append(AppendResponse),
+    Snapshot(InstallSnapshotResponse),
+}
+
+impl ReplicationResponse {
+    /// Term of the peer that answered
+    pub fn term(&self) -> Term {
+        match self {
+            ReplicationResponse::Append(response) => response.term,
+            ReplicationResponse::Snapshot(response) => response.term,
+        }
+    }
+}
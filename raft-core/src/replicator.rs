@@ -0,0 +1,253 @@
+//! Per-peer replication actors
+//!
+//! While this node leads, every peer has a replicator task of its own. It
+//! keeps the peer's transport, its replication [`Progress`], the requests in
+//! flight and the backoff after failed sends, and reports each answer to the
+//! event loop over a channel. The event loop owns the log, so it builds the
+//! requests and applies the answers, but it never waits on a peer: a slow or
+//! unreachable follower only holds up its own replicator.
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{sleep_until, Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::replication::{Progress, ReplicationRequest, ReplicationResponse};
+use crate::transport::Transport;
+use crate::types::*;
+use crate::RaftResult;
+
+/// Wait before the first retry after a failed send; it doubles up to `MAX_BACKOFF`
+const MIN_BACKOFF: Duration = Duration::from_millis(50);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// What a replicator asks of the event loop
+pub(crate) enum ReplicatorEvent {
+    /// Build up to `capacity` requests to send the peer next
+    Ready {
+        progress: Progress,
+        capacity: usize,
+        reply: oneshot::Sender<Assignment>,
+    },
+    /// Apply the peer's answer to a request sent in heartbeat round `round`
+    Answered {
+        peer_id: NodeId,
+        round: u64,
+        progress: Progress,
+        request: Box<ReplicationRequest>,
+        response: ReplicationResponse,
+        reply: oneshot::Sender<Assignment>,
+    },
+}
+
+/// The event loop's reply to a [`ReplicatorEvent`]
+pub(crate) struct Assignment {
+    /// The peer's progress once the requests are built or the answer applied
+    pub progress: Progress,
+    /// Requests to send, in order
+    pub requests: Vec<ReplicationRequest>,
+    /// Whether the peer still lacks entries after these requests
+    pub behind: bool,
+}
+
+/// Handle to a running replicator; dropping it stops the replicator
+pub(crate) struct ReplicatorHandle {
+    rounds: mpsc::UnboundedSender<u64>,
+    task: JoinHandle<()>,
+}
+
+impl ReplicatorHandle {
+    /// Have the replicator send a request in the given heartbeat round, even
+    /// if the peer is up to date
+    pub fn heartbeat(&self, round: u64) {
+        let _ = self.rounds.send(round);
+    }
+}
+
+impl Drop for ReplicatorHandle {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Replication actor for one peer
+pub(crate) struct Replicator<T: Transport> {
+    peer: PeerInfo,
+    transport: T,
+    progress: Progress,
+    max_in_flight: usize,
+    events: mpsc::UnboundedSender<ReplicatorEvent>,
+    rounds: mpsc::UnboundedReceiver<u64>,
+    /// Latest heartbeat round, and whether a request has yet to go out in it
+    round: u64,
+    heartbeat_due: bool,
+    /// Whether the peer lacks entries the leader has
+    behind: bool,
+    /// Snapshot chunks go one at a time
+    sending_snapshot: bool,
+    backoff: Duration,
+    retry_at: Option<Instant>,
+}
+
+impl<T: Transport> Replicator<T> {
+    /// Start replicating to `peer` from `progress`, with at most
+    /// `max_in_flight` requests outstanding
+    pub fn spawn(
+        peer: PeerInfo,
+        transport: T,
+        progress: Progress,
+        max_in_flight: usize,
+        events: mpsc::UnboundedSender<ReplicatorEvent>,
+    ) -> ReplicatorHandle {
+        let (rounds_tx, rounds) = mpsc::unbounded_channel();
+        let replicator = Self {
+            peer,
+            transport,
+            progress,
+            max_in_flight: max_in_flight.max(1),
+            events,
+            rounds,
+            round: 0,
+            heartbeat_due: false,
+            behind: false,
+            sending_snapshot: false,
+            backoff: MIN_BACKOFF,
+            retry_at: None,
+        };
+
+        ReplicatorHandle {
+            rounds: rounds_tx,
+            task: tokio::spawn(replicator.run()),
+        }
+    }
+
+    /// Send requests and hand their answers to the event loop until stopped
+    async fn run(mut self) {
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            if self.wants_to_send(in_flight.len()) {
+                let capacity = self.max_in_flight - in_flight.len();
+                let progress = self.progress.clone();
+                let Some(assignment) = self.ask(|reply| ReplicatorEvent::Ready { progress, capacity, reply }).await else {
+                    break;
+                };
+
+                self.heartbeat_due = false;
+                self.progress = assignment.progress;
+                self.behind = assignment.behind && !assignment.requests.is_empty();
+                for request in assignment.requests {
+                    self.sending_snapshot |= matches!(request, ReplicationRequest::Snapshot(_));
+                    in_flight.push(send(self.transport.clone(), self.peer.clone(), self.round, request));
+                }
+                continue;
+            }
+
+            tokio::select! {
+                round = self.rounds.recv() => match round {
+                    Some(round) => {
+                        self.round = round;
+                        self.heartbeat_due = true;
+                    }
+                    None => break,
+                },
+
+                Some((round, request, result)) = in_flight.next(), if !in_flight.is_empty() => {
+                    if !self.handle_result(round, request, result).await {
+                        break;
+                    }
+                }
+
+                _ = sleep_until(self.retry_at.unwrap_or_else(Instant::now)), if self.retry_at.is_some() => {
+                    self.retry_at = None;
+                }
+            }
+        }
+
+        debug!("Replicator for {} stopped", self.peer.node_id);
+    }
+
+    /// Check if another request should go out now
+    ///
+    /// Nothing is sent while backing off or streaming a snapshot chunk, and a
+    /// peer still being probed only ever has one request outstanding.
+    fn wants_to_send(&self, in_flight: usize) -> bool {
+        self.retry_at.is_none()
+            && !self.sending_snapshot
+            && in_flight < self.max_in_flight
+            && !(self.progress.probing && in_flight > 0)
+            && (self.heartbeat_due || self.behind)
+    }
+
+    /// Handle the outcome of a request; false once the event loop is gone
+    async fn handle_result(
+        &mut self,
+        round: u64,
+        request: ReplicationRequest,
+        result: RaftResult<ReplicationResponse>,
+    ) -> bool {
+        if matches!(request, ReplicationRequest::Snapshot(_)) {
+            self.sending_snapshot = false;
+        }
+
+        match result {
+            Ok(response) => {
+                self.backoff = MIN_BACKOFF;
+                let peer_id = self.peer.node_id.clone();
+                let progress = self.progress.clone();
+                let Some(assignment) = self.ask(|reply| ReplicatorEvent::Answered {
+                    peer_id,
+                    round,
+                    progress,
+                    request: Box::new(request),
+                    response,
+                    reply,
+                }).await else {
+                    return false;
+                };
+                self.progress = assignment.progress;
+                self.behind = assignment.behind;
+            }
+            Err(e) => {
+                warn!("Failed to replicate to {}: {}", self.peer.node_id, e);
+                if let ReplicationRequest::Append(request) = &request {
+                    self.progress.rewind(request);
+                }
+                // The round still needs an answer once the backoff is over
+                self.heartbeat_due = true;
+                self.retry_at = Some(Instant::now() + self.backoff);
+                self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+            }
+        }
+
+        true
+    }
+
+    /// Send an event to the event loop and wait for its assignment
+    async fn ask(&self, event: impl FnOnce(oneshot::Sender<Assignment>) -> ReplicatorEvent) -> Option<Assignment> {
+        let (reply, assignment) = oneshot::channel();
+        self.events.send(event(reply)).ok()?;
+        assignment.await.ok()
+    }
+}
+
+/// Send one request to the peer, keeping the round it went out in
+async fn send<T: Transport>(
+    transport: T,
+    peer: PeerInfo,
+    round: u64,
+    request: ReplicationRequest,
+) -> (u64, ReplicationRequest, RaftResult<ReplicationResponse>) {
+    let result = match &request {
+        ReplicationRequest::Append(append) => transport
+            .append_entries(&peer, append)
+            .await
+            .map(ReplicationResponse::Append),
+        ReplicationRequest::Snapshot(snapshot) => transport
+            .install_snapshot(&peer, snapshot)
+            .await
+            .map(ReplicationResponse::Snapshot),
+    };
+    (round, request, result)
+}
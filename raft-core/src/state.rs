@@ -6,6 +6,7 @@ pub struct PersistentState {
     pub current_term: Term,
     pub voted_for: Option<NodeId>,
     pub log: Vec<LogEntry>,
+    pub snapshot: Option<Snapshot>,
 }
 
 impl PersistentState {
@@ -15,10 +16,17 @@ impl PersistentState {
             current_term: 0,
             voted_for: None,
             log: Vec::new(),
+            snapshot: None,
         }
     }
 }
 
+impl Default for PersistentState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Volatile state maintained by all servers
 #[derive(Debug, Clone)]
 pub struct VolatileState {
@@ -36,6 +44,12 @@ impl VolatileState {
     }
 }
 
+impl Default for VolatileState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Volatile state maintained by leaders
 #[derive(Debug, Clone)]
 pub struct LeaderState {
@@ -60,3 +74,9 @@ impl LeaderState {
         }
     }
 }
+
+impl Default for LeaderState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,473 @@
+use crate::types::*;
+use crate::codec;
+use crate::state::PersistentState;
+use crate::error::RaftError;
+use crate::RaftResult;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Durable storage for the state Raft must not forget across restarts
+///
+/// Every method must have reached stable storage before it returns, because
+/// `RaftNode` replies to RPCs as soon as these calls succeed.
+pub trait RaftStorage: Send + Sync {
+    /// Load the persisted term, vote and log (empty for a fresh node)
+    fn load(&mut self) -> RaftResult<PersistentState>;
+
+    /// Persist the current term and the vote cast in it
+    fn save_hard_state(&mut self, current_term: Term, voted_for: Option<&NodeId>) -> RaftResult<()>;
+
+    /// Append entries to the end of the log
+    fn append(&mut self, entries: &[LogEntry]) -> RaftResult<()>;
+
+    /// Remove all entries from `from_index` onwards
+    fn truncate(&mut self, from_index: LogIndex) -> RaftResult<()>;
+
+    /// Persist a snapshot and discard the log entries it covers
+    fn save_snapshot(&mut self, snapshot: &Snapshot) -> RaftResult<()>;
+}
+
+/// Storage that keeps everything in memory (for tests and throwaway nodes)
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    state: PersistentState,
+}
+
+impl MemoryStorage {
+    /// Create new empty memory storage
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RaftStorage for MemoryStorage {
+    fn load(&mut self) -> RaftResult<PersistentState> {
+        Ok(self.state.clone())
+    }
+
+    fn save_hard_state(&mut self, current_term: Term, voted_for: Option<&NodeId>) -> RaftResult<()> {
+        self.state.current_term = current_term;
+        self.state.voted_for = voted_for.cloned();
+        Ok(())
+    }
+
+    fn append(&mut self, entries: &[LogEntry]) -> RaftResult<()> {
+        self.state.log.extend_from_slice(entries);
+        Ok(())
+    }
+
+    fn truncate(&mut self, from_index: LogIndex) -> RaftResult<()> {
+        self.state.log.retain(|e| e.index < from_index);
+        Ok(())
+    }
+
+    fn save_snapshot(&mut self, snapshot: &Snapshot) -> RaftResult<()> {
+        self.state.log.retain(|e| e.index > snapshot.last_included_index);
+        self.state.snapshot = Some(snapshot.clone());
+        Ok(())
+    }
+}
+
+/// Term and vote, written atomically to the hard state file
+#[derive(Debug, Serialize, Deserialize)]
+struct HardState {
+    current_term: Term,
+    voted_for: Option<NodeId>,
+}
+
+/// One file of the segmented log
+#[derive(Debug)]
+struct Segment {
+    first_index: LogIndex,
+    path: PathBuf,
+    /// Byte offset of every record in the file, in index order
+    offsets: Vec<u64>,
+    size: u64,
+}
+
+impl Segment {
+    fn last_index(&self) -> LogIndex {
+        self.first_index + self.offsets.len() as LogIndex - 1
+    }
+}
+
+/// File-backed storage: a hard state file plus an append-only segmented log
+///
+/// Log records are `[len: u32][crc32: u32][entry]` with little-endian headers,
+/// the entry in the versioned binary encoding of [`codec`]; records holding
+/// JSON entries from older releases are still read. A new segment file starts
+/// once the current one passes `segment_size` bytes. A torn record at the tail of the last segment (a crash mid-append, before the
+/// append was acknowledged) is cut off during `load`. The latest snapshot lives
+/// in its own file; segments it fully covers are deleted.
+pub struct FileStorage {
+    dir: PathBuf,
+    segment_size: u64,
+    segments: Vec<Segment>,
+    snapshot_index: LogIndex,
+}
+
+const HARD_STATE_FILE: &str = "hard_state";
+const SNAPSHOT_FILE: &str = "snapshot";
+const SNAPSHOT_HEADER_LEN: usize = 24;
+const SEGMENT_PREFIX: &str = "log-";
+const SEGMENT_SUFFIX: &str = ".wal";
+const RECORD_HEADER_LEN: u64 = 8;
+
+/// Default size at which a new log segment is started
+pub const DEFAULT_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+
+impl FileStorage {
+    /// Open (or create) storage in the given directory
+    pub fn open<P: AsRef<Path>>(dir: P) -> RaftResult<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+            segments: Vec::new(),
+            snapshot_index: 0,
+        })
+    }
+
+    /// Set the size at which a new log segment is started
+    pub fn with_segment_size(mut self, segment_size: u64) -> Self {
+        self.segment_size = segment_size;
+        self
+    }
+
+    fn segment_path(&self, first_index: LogIndex) -> PathBuf {
+        self.dir.join(format!("{}{:020}{}", SEGMENT_PREFIX, first_index, SEGMENT_SUFFIX))
+    }
+
+    fn last_index(&self) -> LogIndex {
+        self.segments
+            .last()
+            .map(|s| s.last_index())
+            .unwrap_or(self.snapshot_index)
+    }
+
+    fn load_hard_state(&self) -> RaftResult<HardState> {
+        let path = self.dir.join(HARD_STATE_FILE);
+        if !path.exists() {
+            return Ok(HardState { current_term: 0, voted_for: None });
+        }
+
+        let bytes = fs::read(path)?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// Read the snapshot file:
+    /// `[index: u64][term: u64][config len: u32][crc32: u32][config][data]`
+    fn load_snapshot(&self) -> RaftResult<Option<Snapshot>> {
+        let path = self.dir.join(SNAPSHOT_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let bytes = fs::read(path)?;
+        if bytes.len() < SNAPSHOT_HEADER_LEN {
+            return Err(RaftError::Storage("snapshot file is truncated".to_string()));
+        }
+
+        let config_len = u32::from_le_bytes(bytes[16..20].try_into().unwrap()) as usize;
+        let checksum = u32::from_le_bytes(bytes[20..24].try_into().unwrap());
+        let body = &bytes[SNAPSHOT_HEADER_LEN..];
+        if config_len > body.len() || crc32(body) != checksum {
+            return Err(RaftError::Storage("snapshot checksum mismatch".to_string()));
+        }
+
+        Ok(Some(Snapshot {
+            last_included_index: u64::from_le_bytes(bytes[0..8].try_into().unwrap()),
+            last_included_term: u64::from_le_bytes(bytes[8..16].try_into().unwrap()),
+            config: serde_json::from_slice(&body[..config_len])?,
+            data: body[config_len..].to_vec(),
+        }))
+    }
+
+    /// List segment files sorted by their first index
+    fn segment_files(&self) -> RaftResult<Vec<(LogIndex, PathBuf)>> {
+        let mut files = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let path = dir_entry?.path();
+            let name = match path.file_name().and_then(|n| n.to_str()) {
+                Some(name) => name,
+                None => continue,
+            };
+            let index = name
+                .strip_prefix(SEGMENT_PREFIX)
+                .and_then(|rest| rest.strip_suffix(SEGMENT_SUFFIX))
+                .and_then(|index| index.parse::<LogIndex>().ok());
+            if let Some(index) = index {
+                files.push((index, path));
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Read every intact record of a segment, truncating a torn tail if allowed
+    fn read_segment(
+        &self,
+        first_index: LogIndex,
+        path: PathBuf,
+        repair_tail: bool,
+        log: &mut Vec<LogEntry>,
+    ) -> RaftResult<Segment> {
+        let mut bytes = Vec::new();
+        File::open(&path)?.read_to_end(&mut bytes)?;
+
+        let mut offsets = Vec::new();
+        let mut pos = 0u64;
+        while pos < bytes.len() as u64 {
+            let expected_index = first_index + offsets.len() as LogIndex;
+            match decode_record(&bytes[pos as usize..]) {
+                Some((entry, record_len)) if entry.index == expected_index => {
+                    offsets.push(pos);
+                    log.push(entry);
+                    pos += record_len;
+                }
+                _ if repair_tail => {
+                    warn!("Discarding torn log tail in {} at byte {}", path.display(), pos);
+                    let file = OpenOptions::new().write(true).open(&path)?;
+                    file.set_len(pos)?;
+                    file.sync_all()?;
+                    break;
+                }
+                _ => {
+                    return Err(RaftError::Storage(format!(
+                        "corrupt log record in {} at byte {}", path.display(), pos
+                    )));
+                }
+            }
+        }
+
+        Ok(Segment { first_index, path, offsets, size: pos })
+    }
+
+    /// Start a new, empty segment whose first entry will be `first_index`
+    fn start_segment(&mut self, first_index: LogIndex) -> RaftResult<()> {
+        let path = self.segment_path(first_index);
+        File::create(&path)?.sync_all()?;
+        sync_dir(&self.dir)?;
+
+        self.segments.push(Segment { first_index, path, offsets: Vec::new(), size: 0 });
+        Ok(())
+    }
+}
+
+impl RaftStorage for FileStorage {
+    fn load(&mut self) -> RaftResult<PersistentState> {
+        let hard_state = self.load_hard_state()?;
+        let snapshot = self.load_snapshot()?;
+        self.snapshot_index = snapshot.as_ref().map(|s| s.last_included_index).unwrap_or(0);
+
+        let files = self.segment_files()?;
+        let mut log: Vec<LogEntry> = Vec::new();
+        self.segments.clear();
+
+        // The first segment may start anywhere up to just after the snapshot
+        let count = files.len();
+        for (i, (first_index, path)) in files.into_iter().enumerate() {
+            let expected = log.last().map(|e| e.index + 1);
+            let contiguous = match expected {
+                Some(expected) => first_index == expected,
+                None => first_index <= self.snapshot_index + 1,
+            };
+            if !contiguous {
+                return Err(RaftError::Storage(format!(
+                    "log segment {} leaves a gap in the log", path.display()
+                )));
+            }
+            let segment = self.read_segment(first_index, path, i + 1 == count, &mut log)?;
+            self.segments.push(segment);
+        }
+        log.retain(|e| e.index > self.snapshot_index);
+
+        // Drop a trailing segment left empty by a torn first record
+        if let Some(segment) = self.segments.last() {
+            if segment.offsets.is_empty() {
+                let segment = self.segments.pop().unwrap();
+                fs::remove_file(&segment.path)?;
+                sync_dir(&self.dir)?;
+            }
+        }
+
+        info!("Recovered term {} and {} log entries from {}",
+              hard_state.current_term, log.len(), self.dir.display());
+
+        Ok(PersistentState {
+            current_term: hard_state.current_term,
+            voted_for: hard_state.voted_for,
+            log,
+            snapshot,
+        })
+    }
+
+    fn save_hard_state(&mut self, current_term: Term, voted_for: Option<&NodeId>) -> RaftResult<()> {
+        let hard_state = HardState { current_term, voted_for: voted_for.cloned() };
+        let bytes = serde_json::to_vec(&hard_state)?;
+
+        // Write-then-rename so a crash leaves either the old or the new state
+        let tmp_path = self.dir.join(format!("{}.tmp", HARD_STATE_FILE));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, self.dir.join(HARD_STATE_FILE))?;
+        sync_dir(&self.dir)
+    }
+
+    fn append(&mut self, entries: &[LogEntry]) -> RaftResult<()> {
+        let Some(first) = entries.first() else {
+            return Ok(());
+        };
+        if first.index != self.last_index() + 1 {
+            return Err(RaftError::LogInconsistency { index: first.index });
+        }
+
+        let needs_segment = self.segments
+            .last()
+            .map(|s| s.size >= self.segment_size)
+            .unwrap_or(true);
+        if needs_segment {
+            self.start_segment(first.index)?;
+        }
+
+        let segment = self.segments.last_mut().expect("segment was just ensured");
+        let mut buffer = Vec::new();
+        for entry in entries {
+            segment.offsets.push(segment.size + buffer.len() as u64);
+            encode_record(entry, &mut buffer);
+        }
+
+        let mut file = OpenOptions::new().append(true).open(&segment.path)?;
+        file.write_all(&buffer)?;
+        file.sync_data()?;
+        segment.size += buffer.len() as u64;
+
+        Ok(())
+    }
+
+    fn truncate(&mut self, from_index: LogIndex) -> RaftResult<()> {
+        while let Some(segment) = self.segments.last_mut() {
+            if from_index > segment.last_index() {
+                break;
+            }
+
+            if from_index <= segment.first_index {
+                // The whole segment goes
+                fs::remove_file(&segment.path)?;
+                self.segments.pop();
+                sync_dir(&self.dir)?;
+            } else {
+                let keep = (from_index - segment.first_index) as usize;
+                let new_size = segment.offsets[keep];
+                let file = OpenOptions::new().write(true).open(&segment.path)?;
+                file.set_len(new_size)?;
+                file.sync_all()?;
+                segment.offsets.truncate(keep);
+                segment.size = new_size;
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn save_snapshot(&mut self, snapshot: &Snapshot) -> RaftResult<()> {
+        let mut body = serde_json::to_vec(&snapshot.config)?;
+        let config_len = body.len() as u32;
+        body.extend_from_slice(&snapshot.data);
+
+        let mut bytes = Vec::with_capacity(SNAPSHOT_HEADER_LEN + body.len());
+        bytes.extend_from_slice(&snapshot.last_included_index.to_le_bytes());
+        bytes.extend_from_slice(&snapshot.last_included_term.to_le_bytes());
+        bytes.extend_from_slice(&config_len.to_le_bytes());
+        bytes.extend_from_slice(&crc32(&body).to_le_bytes());
+        bytes.extend_from_slice(&body);
+
+        let tmp_path = self.dir.join(format!("{}.tmp", SNAPSHOT_FILE));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, self.dir.join(SNAPSHOT_FILE))?;
+        sync_dir(&self.dir)?;
+        self.snapshot_index = snapshot.last_included_index;
+
+        // Only now that the snapshot and its rename are durable can the segments it covers go
+        let covered = self.segments
+            .iter()
+            .take_while(|s| s.last_index() <= snapshot.last_included_index)
+            .count();
+        for segment in self.segments.drain(..covered) {
+            fs::remove_file(&segment.path)?;
+        }
+
+        // Entries past the snapshot that no longer follow it must not survive either
+        if self.segments.first().map(|s| s.first_index > self.snapshot_index + 1).unwrap_or(false) {
+            for segment in self.segments.drain(..) {
+                fs::remove_file(&segment.path)?;
+            }
+        }
+
+        sync_dir(&self.dir)
+    }
+}
+
+fn encode_record(entry: &LogEntry, buffer: &mut Vec<u8>) {
+    let payload = codec::encode(entry);
+    buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(&crc32(&payload).to_le_bytes());
+    buffer.extend_from_slice(&payload);
+}
+
+/// Decode one record, returning the entry and the record's total length
+fn decode_record(bytes: &[u8]) -> Option<(LogEntry, u64)> {
+    if (bytes.len() as u64) < RECORD_HEADER_LEN {
+        return None;
+    }
+
+    let len = u32::from_le_bytes(bytes[0..4].try_into().ok()?) as u64;
+    let checksum = u32::from_le_bytes(bytes[4..8].try_into().ok()?);
+    let end = RECORD_HEADER_LEN + len;
+    if (bytes.len() as u64) < end {
+        return None;
+    }
+
+    let payload = &bytes[RECORD_HEADER_LEN as usize..end as usize];
+    if crc32(payload) != checksum {
+        return None;
+    }
+
+    // Logs written before the binary encoding hold JSON objects
+    let entry = match payload.first() {
+        Some(b'{') => serde_json::from_slice(payload).ok()?,
+        _ => codec::decode(payload).ok()?,
+    };
+    Some((entry, end))
+}
+
+/// CRC-32 (IEEE) checksum used to detect torn or corrupted records
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Make a rename or file creation in `dir` durable
+fn sync_dir(dir: &Path) -> RaftResult<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
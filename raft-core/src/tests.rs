@@ -2,15 +2,36 @@
 mod tests {
     use crate::types::*;
     use crate::node::RaftNode;
+    use crate::error::RaftError;
+    use crate::storage::{RaftStorage, FileStorage};
+    use crate::codec;
+    use crate::replication::{ReplicationRequest, ReplicationResponse};
+    use crate::event_loop::{NodeStatus, RaftEvent, RaftEventLoop};
+    use crate::handle::RaftHandle;
+    use crate::transport::InMemoryNetwork;
+    use tokio::sync::watch;
+    use tokio::task::JoinHandle;
+    use std::path::PathBuf;
+    use std::time::Duration;
 
     fn create_test_config(node_id: &str) -> NodeConfig {
         NodeConfig {
             node_id: node_id.to_string(),
             address: format!("127.0.0.1:500{}", node_id.chars().last().unwrap()),
+            grpc_address: None,
             peers: vec![],
             election_timeout_min: 150,
             election_timeout_max: 300,
             heartbeat_interval: 50,
+            max_append_entries: 100,
+            max_inflight_appends: 4,
+            snapshot_chunk_size: 64 * 1024,
+            max_learner_lag: 10,
+            pre_vote: false,
+            clock_drift: 10,
+            max_uncommitted_entries: 10_000,
+            max_uncommitted_bytes: 64 * 1024 * 1024,
+            event_queue_capacity: 1024,
         }
     }
 
@@ -36,6 +57,7 @@ mod tests {
             candidate_id: "2".to_string(),
             last_log_index: 0,
             last_log_term: 0,
+            leadership_transfer: false,
         };
         
         let response = node.handle_vote_request(vote_request).unwrap();
@@ -59,6 +81,7 @@ mod tests {
             candidate_id: "2".to_string(),
             last_log_index: 0,
             last_log_term: 0,
+            leadership_transfer: false,
         };
         
         let response = node.handle_vote_request(vote_request).unwrap();
@@ -100,10 +123,11 @@ mod tests {
         let command = b"test command".to_vec();
         let result = node.submit_command(command);
         
+        // The NoOp appended on election takes index 1
         assert!(result.is_ok());
         let log_index = result.unwrap();
-        assert_eq!(log_index, 1);
-        assert_eq!(node.log_length(), 1);
+        assert_eq!(log_index, 2);
+        assert_eq!(node.log_length(), 2);
     }
 
     #[tokio::test]
@@ -131,14 +155,14 @@ mod tests {
         node.submit_command(b"command1".to_vec()).unwrap();
         node.submit_command(b"command2".to_vec()).unwrap();
         
-        assert_eq!(node.log_length(), 2);
+        assert_eq!(node.log_length(), 3);
         
         // Test append entries with new entries
         let new_entry = LogEntry {
-            index: 3,
+            index: 4,
             term: 1,
             entry_type: EntryType::Command,
-            data: b"command3".to_vec(),
+            data: b"command3".to_vec().into(),
             client_id: None,
             sequence_number: None,
         };
@@ -146,15 +170,15 @@ mod tests {
         let append_request = AppendRequest {
             term: 1,
             leader_id: "2".to_string(),
-            prev_log_index: 2,
+            prev_log_index: 3,
             prev_log_term: 1,
             entries: vec![new_entry],
-            leader_commit: 2,
+            leader_commit: 3,
         };
         
         let response = node.handle_append_request(append_request).unwrap();
         assert!(response.success);
-        assert_eq!(node.log_length(), 3);
+        assert_eq!(node.log_length(), 4);
     }
 
     #[tokio::test]
@@ -167,20 +191,20 @@ mod tests {
         node.submit_command(b"command1".to_vec()).unwrap();
         node.submit_command(b"command2".to_vec()).unwrap();
         
-        // Initially commit index should be 0
-        assert_eq!(node.commit_index(), 0);
+        // Only the election NoOp has committed so far
+        assert_eq!(node.commit_index(), 1);
         
         // Update commit index
-        node.update_commit_index();
+        node.update_commit_index().unwrap();
         
         // Since we're the only node, commit index should advance
-        assert!(node.commit_index() > 0);
+        assert_eq!(node.commit_index(), 3);
     }
 
     #[tokio::test]
     async fn test_state_transitions() {
         let mut config = create_test_config("1");
-        config.peers = vec!["node-2".to_string()]; // Add a peer so it doesn't immediately become leader
+        config.peers = peer_list(&["node-2"]); // Add a peer so it doesn't immediately become leader
         let mut node = RaftNode::new(config);
 
         // Start as follower
@@ -194,7 +218,7 @@ mod tests {
     #[tokio::test]
     async fn test_vote_response_handling() {
         let mut config = create_test_config("1");
-        config.peers = vec!["node-2".to_string(), "node-3".to_string(), "node-4".to_string()];
+        config.peers = peer_list(&["node-2", "node-3", "node-4"]);
         let mut node = RaftNode::new(config);
 
         // Start election
@@ -223,4 +247,1450 @@ mod tests {
         // Now should be leader (have majority: self + 2 peers = 3/4)
         assert_eq!(node.state(), NodeState::Leader);
     }
+    /// Voting peers with the given IDs, each at its own address
+    fn peer_list(node_ids: &[&str]) -> Vec<PeerInfo> {
+        node_ids.iter().map(|id| PeerInfo::new(*id, format!("raft-{}:5000", id))).collect()
+    }
+
+    fn create_leader(node_id: &str, peers: &[&str]) -> RaftNode {
+        let mut config = create_test_config(node_id);
+        config.peers = peer_list(peers);
+        let mut node = RaftNode::new(config);
+
+        node.start_election().unwrap();
+        for peer in peers {
+            let response = VoteResponse { term: node.current_term(), vote_granted: true };
+            node.handle_vote_response(&peer.to_string(), response).unwrap();
+        }
+        assert_eq!(node.state(), NodeState::Leader);
+        node
+    }
+
+    fn entry(index: LogIndex, term: Term) -> LogEntry {
+        LogEntry {
+            index,
+            term,
+            entry_type: EntryType::Command,
+            data: format!("command{}", index).into_bytes().into(),
+            client_id: None,
+            sequence_number: None,
+        }
+    }
+
+    /// Run one request/response exchange between a leader and a follower
+    fn replicate_once(leader: &mut RaftNode, follower: &mut RaftNode) -> AppendResponse {
+        let peer_id = follower.node_id().clone();
+        let request = leader.append_request_for(&peer_id).unwrap();
+        let response = follower.handle_append_request(request.clone()).unwrap();
+        leader.handle_append_response(&peer_id, &request, response.clone()).unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_append_request_batches_missing_entries() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        for i in 0..150 {
+            leader.submit_command(format!("command{}", i).into_bytes()).unwrap();
+        }
+
+        let request = leader.append_request_for(&"2".to_string()).unwrap();
+        assert_eq!(request.prev_log_index, 0);
+        assert_eq!(request.entries.len(), 100);
+        assert_eq!(request.entries[0].index, 1);
+
+        // Only leaders build requests
+        let follower = RaftNode::new(create_test_config("2"));
+        assert!(follower.append_request_for(&"1".to_string()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_successful_append_advances_commit_index() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let mut follower = RaftNode::new(create_test_config("2"));
+
+        leader.submit_command(b"command1".to_vec()).unwrap();
+        leader.submit_command(b"command2".to_vec()).unwrap();
+        assert_eq!(leader.commit_index(), 0);
+
+        // Both commands follow the election NoOp
+        let response = replicate_once(&mut leader, &mut follower);
+        assert!(response.success);
+        assert_eq!(leader.match_index(&"2".to_string()), Some(3));
+        assert_eq!(leader.next_index(&"2".to_string()), Some(4));
+
+        // Leader plus one follower is a majority of three
+        assert_eq!(leader.commit_index(), 3);
+
+        // The next heartbeat carries the new commit index to the follower
+        replicate_once(&mut leader, &mut follower);
+        assert_eq!(follower.commit_index(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_next_index_backs_off_using_conflict_hints() {
+        // Follower holds a long uncommitted suffix from term 2
+        let mut follower = RaftNode::new(create_test_config("2"));
+        follower.handle_append_request(AppendRequest {
+            term: 2,
+            leader_id: "3".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![entry(1, 1), entry(2, 2), entry(3, 2), entry(4, 2), entry(5, 2), entry(6, 2)],
+            leader_commit: 0,
+        }).unwrap();
+
+        // Leader only kept the first two term 2 entries, then got entries from term 4
+        let mut config = create_test_config("1");
+        config.peers = peer_list(&["2", "3"]);
+        let mut leader = RaftNode::new(config);
+        leader.handle_append_request(AppendRequest {
+            term: 4,
+            leader_id: "3".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![entry(1, 1), entry(2, 2), entry(3, 2), entry(4, 4), entry(5, 4)],
+            leader_commit: 0,
+        }).unwrap();
+        leader.start_election().unwrap();
+        let term = leader.current_term();
+        leader.handle_vote_response(&"3".to_string(), VoteResponse { term, vote_granted: true }).unwrap();
+        assert_eq!(leader.state(), NodeState::Leader);
+        leader.submit_command(b"command7".to_vec()).unwrap();
+
+        // The probe at the leader's old last index is rejected with a term 2 conflict
+        let response = replicate_once(&mut leader, &mut follower);
+        assert!(!response.success);
+        assert_eq!(response.conflict_term, Some(2));
+        assert_eq!(response.conflict_index, Some(2));
+
+        // The leader also has term 2 entries, so it resumes right after its last one
+        assert_eq!(leader.next_index(&"2".to_string()), Some(4));
+
+        let response = replicate_once(&mut leader, &mut follower);
+        assert!(response.success);
+        assert_eq!(follower.log_length(), 7);
+        assert_eq!(follower.last_log_term(), 5);
+        assert_eq!(leader.match_index(&"2".to_string()), Some(7));
+        assert_eq!(leader.commit_index(), 7);
+    }
+
+    #[tokio::test]
+    async fn test_next_index_jumps_to_follower_log_end() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        for i in 0..5 {
+            leader.submit_command(format!("command{}", i).into_bytes()).unwrap();
+        }
+
+        // Pretend an earlier round already pushed next_index past the follower's log
+        let mut follower = RaftNode::new(create_test_config("2"));
+        let request = AppendRequest {
+            prev_log_index: 4,
+            prev_log_term: 1,
+            entries: vec![],
+            ..leader.append_request_for(&"2".to_string()).unwrap()
+        };
+        let response = follower.handle_append_request(request.clone()).unwrap();
+        assert_eq!(response.conflict_index, Some(1));
+
+        leader.handle_append_response(&"2".to_string(), &request, response).unwrap();
+        assert_eq!(leader.next_index(&"2".to_string()), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_batched_commands_are_appended_in_order() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let first_index = leader.submit_client_commands(vec![
+            (b"command1".to_vec(), None, None),
+            (b"command2".to_vec(), Some("client-7".to_string()), Some(3)),
+            (b"command3".to_vec(), None, None),
+        ]).unwrap();
+
+        // The election NoOp comes first
+        assert_eq!(first_index, 2);
+        assert_eq!(leader.last_log_index(), 4);
+        let request = leader.append_request_for(&"2".to_string()).unwrap();
+        assert_eq!(request.entries[2].client_id.as_deref(), Some("client-7"));
+        assert_eq!(request.entries[2].sequence_number, Some(3));
+
+        let mut follower = create_follower("2", &["1", "3"]);
+        assert!(matches!(
+            follower.submit_client_commands(vec![(b"command".to_vec(), None, None)]),
+            Err(RaftError::NotLeader)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_uncommitted_limits_reject_commands_until_commit() {
+        let mut config = create_test_config("1");
+        config.peers = peer_list(&["2", "3"]);
+        config.max_uncommitted_entries = 3;
+        config.max_uncommitted_bytes = 16;
+        let mut leader = RaftNode::new(config);
+        leader.start_election().unwrap();
+        let response = VoteResponse { term: leader.current_term(), vote_granted: true };
+        leader.handle_vote_response(&"2".to_string(), response).unwrap();
+        let mut follower = create_follower("2", &["1", "3"]);
+
+        // The election NoOp already counts as one uncommitted entry
+        leader.submit_client_commands(vec![(vec![0; 8], None, None)]).unwrap();
+        assert!(matches!(
+            leader.submit_client_commands(vec![(vec![0; 16], None, None)]),
+            Err(RaftError::Overloaded(_))
+        ));
+        leader.submit_client_commands(vec![(vec![0; 8], None, None)]).unwrap();
+        assert!(matches!(
+            leader.submit_client_commands(vec![(vec![0; 1], None, None)]),
+            Err(RaftError::Overloaded(_))
+        ));
+        assert_eq!(leader.uncommitted_load(), (3, 16));
+
+        // Committing frees the room, and a lone oversized command gets through
+        replicate_until_success(&mut leader, &mut follower);
+        assert_eq!(leader.uncommitted_load(), (0, 0));
+        leader.submit_client_commands(vec![(vec![0; 64], None, None)]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_appends_rewind_on_rejection() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let mut follower = create_follower("2", &["1", "3"]);
+        let peer_id = "2".to_string();
+
+        let mut progress = leader.progress(&peer_id).cloned().unwrap();
+
+        // Still probing: a single request at a time
+        assert_eq!(leader.replication_requests(&mut progress, 4).len(), 1);
+        replicate_until_success(&mut leader, &mut follower);
+        assert_eq!(leader.match_index(&peer_id), Some(1));
+
+        for i in 0..350 {
+            leader.submit_command(format!("command{}", i).into_bytes()).unwrap();
+        }
+
+        // Four batches go out at once, next_index already past all of them
+        let mut progress = leader.progress(&peer_id).cloned().unwrap();
+        assert!(!progress.probing);
+        let requests = leader.replication_requests(&mut progress, 4);
+        let first_entries: Vec<LogIndex> = requests.iter().map(|request| match request {
+            ReplicationRequest::Append(request) => request.entries[0].index,
+            ReplicationRequest::Snapshot(_) => panic!("unexpected snapshot"),
+        }).collect();
+        assert_eq!(first_entries, vec![2, 102, 202, 302]);
+        assert_eq!(progress.next_index, 352);
+
+        // The second batch is lost, so the third arrives out of order and is rejected
+        let answer = |follower: &mut RaftNode, request: &ReplicationRequest| match request {
+            ReplicationRequest::Append(request) => {
+                ReplicationResponse::Append(follower.handle_append_request(request.clone()).unwrap())
+            }
+            ReplicationRequest::Snapshot(_) => panic!("unexpected snapshot"),
+        };
+        let response = answer(&mut follower, &requests[0]);
+        leader.handle_replication_response(&peer_id, &mut progress, &requests[0], response).unwrap();
+        if let ReplicationRequest::Append(lost) = &requests[1] {
+            progress.rewind(lost);
+        }
+        let response = answer(&mut follower, &requests[2]);
+        assert!(matches!(&response, ReplicationResponse::Append(response) if !response.success));
+        leader.handle_replication_response(&peer_id, &mut progress, &requests[2], response).unwrap();
+
+        assert_eq!(leader.match_index(&peer_id), Some(101));
+        assert_eq!(progress.next_index, 102);
+        assert!(progress.probing);
+
+        // Probing again: one request, picking up right after the match point
+        let requests = leader.replication_requests(&mut progress, 4);
+        assert_eq!(requests.len(), 1);
+        let response = answer(&mut follower, &requests[0]);
+        leader.handle_replication_response(&peer_id, &mut progress, &requests[0], response).unwrap();
+        assert_eq!(leader.match_index(&peer_id), Some(201));
+
+        // Then the rest is pipelined again
+        let requests = leader.replication_requests(&mut progress, 4);
+        assert_eq!(requests.len(), 2);
+        for request in &requests {
+            let response = answer(&mut follower, request);
+            leader.handle_replication_response(&peer_id, &mut progress, request, response).unwrap();
+        }
+        assert_eq!(leader.match_index(&peer_id), Some(351));
+        assert_eq!(leader.commit_index(), 351);
+    }
+
+    #[tokio::test]
+    async fn test_append_response_with_newer_term_steps_down() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let request = leader.append_request_for(&"2".to_string()).unwrap();
+
+        let response = AppendResponse {
+            term: leader.current_term() + 1,
+            success: false,
+            conflict_index: None,
+            conflict_term: None,
+        };
+        leader.handle_append_response(&"2".to_string(), &request, response).unwrap();
+
+        assert_eq!(leader.state(), NodeState::Follower);
+        assert_eq!(leader.current_term(), 2);
+    }
+    #[tokio::test]
+    async fn test_follower_entries_to_apply() {
+        let mut follower = RaftNode::new(create_test_config("2"));
+        follower.handle_append_request(AppendRequest {
+            term: 1,
+            leader_id: "1".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![entry(1, 1), entry(2, 1), entry(3, 1)],
+            leader_commit: 2,
+        }).unwrap();
+
+        // Only committed entries are handed to the state machine
+        let to_apply: Vec<LogIndex> = follower.get_entries_to_apply().iter().map(|e| e.index).collect();
+        assert_eq!(to_apply, vec![1, 2]);
+
+        // last_applied never runs ahead of the commit index or moves backwards
+        follower.set_last_applied(3);
+        assert_eq!(follower.last_applied(), 2);
+        follower.set_last_applied(1);
+        assert_eq!(follower.last_applied(), 2);
+        assert!(follower.get_entries_to_apply().is_empty());
+    }
+    fn temp_storage_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("raft-storage-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_file_storage_roundtrip() {
+        let dir = temp_storage_dir();
+        {
+            let mut storage = FileStorage::open(&dir).unwrap().with_segment_size(200);
+            storage.load().unwrap();
+            storage.save_hard_state(3, Some(&"2".to_string())).unwrap();
+            storage.append(&[entry(1, 1), entry(2, 1), entry(3, 2)]).unwrap();
+            storage.append(&[entry(4, 3), entry(5, 3)]).unwrap();
+
+            // Appends must be contiguous
+            assert!(storage.append(&[entry(7, 3)]).is_err());
+        }
+
+        let mut storage = FileStorage::open(&dir).unwrap().with_segment_size(200);
+        let state = storage.load().unwrap();
+        assert_eq!(state.current_term, 3);
+        assert_eq!(state.voted_for.as_deref(), Some("2"));
+        let indices: Vec<LogIndex> = state.log.iter().map(|e| e.index).collect();
+        assert_eq!(indices, vec![1, 2, 3, 4, 5]);
+        assert_eq!(state.log[2].term, 2);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_file_storage_truncate_across_segments() {
+        let dir = temp_storage_dir();
+        {
+            // Tiny segments so every couple of appends starts a new file
+            let mut storage = FileStorage::open(&dir).unwrap().with_segment_size(100);
+            storage.load().unwrap();
+            for i in 1..=6 {
+                storage.append(&[entry(i, 1)]).unwrap();
+            }
+            storage.truncate(3).unwrap();
+            storage.append(&[entry(3, 2)]).unwrap();
+        }
+
+        let mut storage = FileStorage::open(&dir).unwrap().with_segment_size(100);
+        let state = storage.load().unwrap();
+        let terms: Vec<Term> = state.log.iter().map(|e| e.term).collect();
+        assert_eq!(terms, vec![1, 1, 2]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_file_storage_discards_torn_tail() {
+        let dir = temp_storage_dir();
+        {
+            let mut storage = FileStorage::open(&dir).unwrap();
+            storage.load().unwrap();
+            storage.append(&[entry(1, 1), entry(2, 1)]).unwrap();
+        }
+
+        // Simulate a crash halfway through writing a third record
+        let segment = std::fs::read_dir(&dir).unwrap()
+            .map(|e| e.unwrap().path())
+            .find(|p| p.extension().map(|e| e == "wal").unwrap_or(false))
+            .unwrap();
+        let mut file = std::fs::OpenOptions::new().append(true).open(&segment).unwrap();
+        std::io::Write::write_all(&mut file, &[42, 0, 0, 0, 1, 2, 3]).unwrap();
+        drop(file);
+
+        let mut storage = FileStorage::open(&dir).unwrap();
+        assert_eq!(storage.load().unwrap().log.len(), 2);
+        storage.append(&[entry(3, 1)]).unwrap();
+        assert_eq!(storage.load().unwrap().log.len(), 3);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_client_command_keeps_session() {
+        let dir = temp_storage_dir();
+        {
+            let storage = FileStorage::open(&dir).unwrap();
+            let mut node = RaftNode::with_storage(create_test_config("1"), Box::new(storage)).unwrap();
+            node.start_election().unwrap();
+            node.submit_command(b"command1".to_vec()).unwrap();
+            let index = node.submit_client_command(b"command2".to_vec(), Some("1".to_string()), Some(7)).unwrap();
+            assert_eq!(index, 3);
+        }
+
+        // Replicas need the session on the entry to deduplicate retries
+        let mut storage = FileStorage::open(&dir).unwrap();
+        let state = storage.load().unwrap();
+        assert_eq!(state.log[0].entry_type, EntryType::NoOp);
+        assert_eq!(state.log[1].client_id, None);
+        assert_eq!(state.log[1].sequence_number, None);
+        assert_eq!(state.log[2].client_id.as_deref(), Some("1"));
+        assert_eq!(state.log[2].sequence_number, Some(7));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restarted_node_remembers_vote() {
+        let dir = temp_storage_dir();
+        {
+            let storage = FileStorage::open(&dir).unwrap();
+            let mut node = RaftNode::with_storage(create_test_config("1"), Box::new(storage)).unwrap();
+            let response = node.handle_vote_request(VoteRequest {
+                term: 1,
+                candidate_id: "2".to_string(),
+                last_log_index: 0,
+                last_log_term: 0,
+                leadership_transfer: false,
+            }).unwrap();
+            assert!(response.vote_granted);
+
+            node.handle_append_request(AppendRequest {
+                term: 1,
+                leader_id: "2".to_string(),
+                prev_log_index: 0,
+                prev_log_term: 0,
+                entries: vec![entry(1, 1)],
+                leader_commit: 0,
+            }).unwrap();
+        }
+
+        let storage = FileStorage::open(&dir).unwrap();
+        let mut node = RaftNode::with_storage(create_test_config("1"), Box::new(storage)).unwrap();
+        assert_eq!(node.current_term(), 1);
+        assert_eq!(node.log_length(), 1);
+
+        // A second candidate in the same term must not get a vote after the restart
+        let response = node.handle_vote_request(VoteRequest {
+            term: 1,
+            candidate_id: "3".to_string(),
+            last_log_index: 1,
+            last_log_term: 1,
+            leadership_transfer: false,
+        }).unwrap();
+        assert!(!response.vote_granted);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_keeps_vote_in_same_term() {
+        let mut node = RaftNode::new(create_test_config("1"));
+        node.handle_vote_request(VoteRequest {
+            term: 1,
+            candidate_id: "2".to_string(),
+            last_log_index: 0,
+            last_log_term: 0,
+            leadership_transfer: false,
+        }).unwrap();
+
+        // A heartbeat from the winner must not free the vote for someone else
+        node.handle_append_request(AppendRequest {
+            term: 1,
+            leader_id: "2".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![],
+            leader_commit: 0,
+        }).unwrap();
+
+        let response = node.handle_vote_request(VoteRequest {
+            term: 1,
+            candidate_id: "3".to_string(),
+            last_log_index: 0,
+            last_log_term: 0,
+            leadership_transfer: false,
+        }).unwrap();
+        assert!(!response.vote_granted);
+    }
+
+    #[tokio::test]
+    async fn test_compacted_log_keeps_term_lookups() {
+        let mut follower = RaftNode::new(create_test_config("2"));
+        follower.handle_append_request(AppendRequest {
+            term: 1,
+            leader_id: "1".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: (1..=5).map(|i| entry(i, 1)).collect(),
+            leader_commit: 5,
+        }).unwrap();
+        follower.set_last_applied(5);
+
+        // Entries past last_applied cannot be snapshotted
+        assert!(follower.compact(6, b"state".to_vec()).is_err());
+        follower.compact(3, b"state".to_vec()).unwrap();
+        assert_eq!(follower.snapshot_index(), 3);
+        assert_eq!(follower.last_log_index(), 5);
+        assert!(follower.get_entries_to_apply().is_empty());
+
+        // A retransmission reaching back into the snapshot still lines up
+        let response = follower.handle_append_request(AppendRequest {
+            term: 1,
+            leader_id: "1".to_string(),
+            prev_log_index: 2,
+            prev_log_term: 1,
+            entries: (3..=6).map(|i| entry(i, 1)).collect(),
+            leader_commit: 5,
+        }).unwrap();
+        assert!(response.success);
+        assert_eq!(follower.last_log_index(), 6);
+
+        // The snapshot boundary answers the consistency check
+        let response = follower.handle_append_request(AppendRequest {
+            term: 1,
+            leader_id: "1".to_string(),
+            prev_log_index: 3,
+            prev_log_term: 1,
+            entries: vec![],
+            leader_commit: 5,
+        }).unwrap();
+        assert!(response.success);
+
+        let response = follower.handle_append_request(AppendRequest {
+            term: 2,
+            leader_id: "3".to_string(),
+            prev_log_index: 6,
+            prev_log_term: 2,
+            entries: vec![],
+            leader_commit: 5,
+        }).unwrap();
+        assert!(!response.success);
+        assert_eq!(response.conflict_term, Some(1));
+        assert_eq!(response.conflict_index, Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_leader_streams_snapshot_to_lagging_follower() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let mut lagging = RaftNode::new(create_test_config("2"));
+        let mut follower = RaftNode::new(create_test_config("3"));
+
+        // Three commands after the election NoOp
+        for i in 1..4 {
+            leader.submit_command(format!("command{}", i).into_bytes()).unwrap();
+        }
+        replicate_once(&mut leader, &mut follower);
+        assert_eq!(leader.commit_index(), 4);
+        leader.set_last_applied(4);
+
+        // Larger than one chunk, so the transfer takes several requests
+        let data: Vec<u8> = (0..150_000u32).map(|i| i as u8).collect();
+        leader.compact(4, data.clone()).unwrap();
+        assert!(leader.append_request_for(&"2".to_string()).is_none());
+
+        let peer_id = "2".to_string();
+        let mut chunks = 0;
+        while let Some(request) = leader.snapshot_request_for(&peer_id) {
+            let response = lagging.handle_install_snapshot(request.clone()).unwrap();
+            leader.handle_install_snapshot_response(&peer_id, &request, response).unwrap();
+            chunks += 1;
+        }
+        assert_eq!(chunks, 3);
+        assert_eq!(leader.next_index(&peer_id), Some(5));
+
+        let snapshot = lagging.snapshot_to_restore().unwrap();
+        assert_eq!(snapshot.last_included_index, 4);
+        assert_eq!(snapshot.data, data);
+        assert_eq!(lagging.commit_index(), 4);
+        lagging.set_last_applied(4);
+
+        // Replication continues from the log after the snapshot
+        leader.submit_command(b"command4".to_vec()).unwrap();
+        let response = replicate_once(&mut leader, &mut lagging);
+        assert!(response.success);
+        assert_eq!(leader.match_index(&peer_id), Some(5));
+        assert_eq!(lagging.last_log_index(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_file_storage_recovers_snapshot() {
+        let dir = temp_storage_dir();
+        {
+            let mut storage = FileStorage::open(&dir).unwrap().with_segment_size(100);
+            storage.load().unwrap();
+            for i in 1..=6 {
+                storage.append(&[entry(i, 1)]).unwrap();
+            }
+            storage.save_snapshot(&Snapshot {
+                last_included_index: 4,
+                last_included_term: 1,
+                config: None,
+                data: b"state".to_vec(),
+            }).unwrap();
+        }
+
+        let storage = FileStorage::open(&dir).unwrap().with_segment_size(100);
+        let mut node = RaftNode::with_storage(create_test_config("1"), Box::new(storage)).unwrap();
+        assert_eq!(node.snapshot_index(), 4);
+        assert_eq!(node.commit_index(), 4);
+        assert_eq!(node.last_log_index(), 6);
+        assert_eq!(node.snapshot_to_restore().unwrap().data, b"state".to_vec());
+
+        // A snapshot past the end of the log replaces the log entirely
+        let response = node.handle_install_snapshot(InstallSnapshotRequest {
+            term: 2,
+            leader_id: "2".to_string(),
+            last_included_index: 10,
+            last_included_term: 2,
+            config: None,
+            offset: 0,
+            data: b"newer".to_vec().into(),
+            done: true,
+        }).unwrap();
+        assert_eq!(response.term, 2);
+        drop(node);
+
+        let mut storage = FileStorage::open(&dir).unwrap().with_segment_size(100);
+        let state = storage.load().unwrap();
+        assert!(state.log.is_empty());
+        assert_eq!(state.snapshot.unwrap().last_included_index, 10);
+        storage.append(&[entry(11, 2)]).unwrap();
+        assert_eq!(storage.load().unwrap().log[0].index, 11);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn member(node_id: &str) -> PeerInfo {
+        PeerInfo {
+            node_id: node_id.to_string(),
+            address: node_id.to_string(),
+            voting: true,
+            grpc_address: None,
+        }
+    }
+
+    /// Replicate to a follower until it accepts, letting the leader back off
+    fn replicate_until_success(leader: &mut RaftNode, follower: &mut RaftNode) {
+        for _ in 0..10 {
+            if replicate_once(leader, follower).success {
+                return;
+            }
+        }
+        panic!("follower never caught up");
+    }
+
+    fn member_ids(node: &RaftNode) -> Vec<NodeId> {
+        node.cluster().members().iter().map(|m| m.node_id.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_joint_consensus_needs_both_majorities() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let mut node2 = RaftNode::new(create_test_config("2"));
+        let mut node4 = RaftNode::new(create_test_config("4"));
+
+        // Replace node 3 with node 4; the joint configuration applies at once
+        let index = leader.propose_membership(vec![member("1"), member("2"), member("4")]).unwrap();
+        assert_eq!(index, 2);
+        assert!(leader.cluster().is_joint());
+        assert_eq!(member_ids(&leader), vec!["1", "2", "4", "3"]);
+        assert!(leader.propose_membership(vec![member("1")]).is_err());
+
+        // Node 4 alone is a majority of the new configuration but not of the old one
+        replicate_until_success(&mut leader, &mut node4);
+        assert!(node4.cluster().is_joint());
+        assert_eq!(leader.commit_index(), 0);
+
+        // With node 2 both halves agree; C_new is appended straight away
+        replicate_until_success(&mut leader, &mut node2);
+        assert_eq!(leader.commit_index(), 2);
+        assert!(!leader.cluster().is_joint());
+        assert_eq!(leader.cluster().config_index, 3);
+        assert_eq!(member_ids(&leader), vec!["1", "2", "4"]);
+
+        // Only the new configuration counts for C_new
+        replicate_until_success(&mut leader, &mut node4);
+        assert_eq!(leader.commit_index(), 3);
+        assert_eq!(member_ids(&node4), vec!["1", "2", "4"]);
+        assert_eq!(leader.state(), NodeState::Leader);
+    }
+
+    #[tokio::test]
+    async fn test_removed_leader_steps_down() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let mut node2 = RaftNode::new(create_test_config("2"));
+        let mut node3 = RaftNode::new(create_test_config("3"));
+
+        leader.propose_membership(vec![member("2"), member("3")]).unwrap();
+        replicate_until_success(&mut leader, &mut node2);
+        assert_eq!(leader.commit_index(), 0);
+        replicate_until_success(&mut leader, &mut node3);
+        assert_eq!(leader.commit_index(), 2);
+
+        // The leader keeps running C_new without counting itself, then steps down
+        replicate_until_success(&mut leader, &mut node2);
+        assert_eq!(leader.state(), NodeState::Leader);
+        replicate_until_success(&mut leader, &mut node3);
+        assert_eq!(leader.commit_index(), 3);
+        assert_eq!(leader.state(), NodeState::Follower);
+        assert!(!leader.is_voter());
+        assert!(node2.is_voter());
+    }
+
+    #[tokio::test]
+    async fn test_learner_is_left_out_of_quorums() {
+        let mut leader = create_leader("1", &["2"]);
+        let mut node2 = RaftNode::new(create_test_config("2"));
+        let mut learner = RaftNode::new(create_test_config("3"));
+
+        // Adding a learner leaves the voters alone, so no joint phase is needed
+        leader.add_learner(member("3")).unwrap();
+        assert!(!leader.cluster().is_joint());
+        assert!(!leader.cluster().is_voter(&"3".to_string()));
+        assert!(!leader.cluster().is_quorum(|id| id == "1" || id == "3"));
+
+        // The learner gets the log, but its acknowledgement commits nothing
+        replicate_until_success(&mut leader, &mut learner);
+        assert_eq!(learner.last_log_index(), 2);
+        assert!(!learner.is_voter());
+        assert_eq!(leader.commit_index(), 0);
+
+        replicate_until_success(&mut leader, &mut node2);
+        assert_eq!(leader.commit_index(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_learner_promoted_once_caught_up() {
+        let mut leader = create_leader("1", &["2"]);
+        let mut node2 = RaftNode::new(create_test_config("2"));
+        let mut learner = RaftNode::new(create_test_config("3"));
+
+        leader.add_learner(member("3")).unwrap();
+        for i in 0..20 {
+            leader.submit_command(format!("command{}", i).into_bytes()).unwrap();
+        }
+        replicate_until_success(&mut leader, &mut node2);
+        assert_eq!(leader.commit_index(), 22);
+
+        // Too far behind (max_learner_lag is 10 in tests)
+        let error = leader.promote_learner(&"3".to_string()).unwrap_err();
+        assert!(error.to_string().contains("22 entries behind"));
+        assert!(leader.promote_learner(&"2".to_string()).is_err());
+
+        replicate_until_success(&mut leader, &mut learner);
+        let index = leader.promote_learner(&"3".to_string()).unwrap();
+        assert_eq!(index, 23);
+        assert!(leader.cluster().is_joint());
+        assert!(leader.cluster().is_voter(&"3".to_string()));
+    }
+
+    fn create_pre_vote_node(node_id: &str, peers: &[&str]) -> RaftNode {
+        let mut config = create_test_config(node_id);
+        config.peers = peer_list(peers);
+        config.pre_vote = true;
+        RaftNode::new(config)
+    }
+
+    #[tokio::test]
+    async fn test_pre_vote_precedes_election() {
+        let mut node = create_pre_vote_node("1", &["2", "3"]);
+        let peer = RaftNode::new(create_test_config("2"));
+
+        node.campaign().unwrap();
+        assert_eq!(node.state(), NodeState::PreCandidate);
+        assert_eq!(node.current_term(), 0);
+
+        let request = node.pre_vote_request();
+        assert_eq!(request.term, 1);
+        let response = peer.handle_pre_vote_request(request).unwrap();
+        assert!(response.vote_granted);
+        assert_eq!(peer.current_term(), 0);
+
+        // A quorum of pre-votes starts the real election
+        node.handle_pre_vote_response(&"2".to_string(), response).unwrap();
+        assert_eq!(node.state(), NodeState::Candidate);
+        assert_eq!(node.current_term(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pre_vote_keeps_partitioned_node_from_disrupting_leader() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let mut follower = RaftNode::new(create_test_config("2"));
+        let mut partitioned = create_pre_vote_node("3", &["1", "2"]);
+        replicate_once(&mut leader, &mut follower);
+
+        // While cut off, node 3 keeps timing out but never bumps its term
+        for _ in 0..5 {
+            partitioned.campaign().unwrap();
+        }
+        assert_eq!(partitioned.current_term(), 0);
+
+        // Back in the cluster, nobody who hears from the leader grants a pre-vote
+        let request = partitioned.pre_vote_request();
+        let response = follower.handle_pre_vote_request(request.clone()).unwrap();
+        assert!(!response.vote_granted);
+        let response = leader.handle_pre_vote_request(request).unwrap();
+        assert!(!response.vote_granted);
+        assert_eq!(leader.state(), NodeState::Leader);
+        assert_eq!(leader.current_term(), 1);
+
+        // The rejection tells the node about the newer term
+        partitioned.handle_pre_vote_response(&"1".to_string(), response).unwrap();
+        assert_eq!(partitioned.state(), NodeState::Follower);
+        assert_eq!(partitioned.current_term(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_leader_steps_down_without_quorum() {
+        let mut leader = create_leader("1", &["2", "3"]);
+
+        // One peer plus the leader is still a majority of three
+        leader.check_quorum(|peer| peer == "2");
+        assert_eq!(leader.state(), NodeState::Leader);
+
+        leader.check_quorum(|_| false);
+        assert_eq!(leader.state(), NodeState::Follower);
+        assert_eq!(leader.current_term(), 1);
+        assert!(leader.leader_id().is_none());
+        assert!(leader.submit_command(b"command".to_vec()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_follower_ignores_votes_while_leader_is_alive() {
+        let mut follower = RaftNode::new(create_test_config("2"));
+        follower.handle_append_request(AppendRequest {
+            term: 1,
+            leader_id: "1".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![],
+            leader_commit: 0,
+        }).unwrap();
+
+        // A disruptive candidate from a newer term is ignored, term and all
+        let response = follower.handle_vote_request(VoteRequest {
+            term: 5,
+            candidate_id: "3".to_string(),
+            last_log_index: 0,
+            last_log_term: 0,
+            leadership_transfer: false,
+        }).unwrap();
+        assert!(!response.vote_granted);
+        assert_eq!(response.term, 1);
+        assert_eq!(follower.current_term(), 1);
+        assert_eq!(follower.leader_id(), Some(&"1".to_string()));
+    }
+
+    fn create_follower(node_id: &str, peers: &[&str]) -> RaftNode {
+        let mut config = create_test_config(node_id);
+        config.peers = peer_list(peers);
+        RaftNode::new(config)
+    }
+
+    #[tokio::test]
+    async fn test_leadership_transfer_hands_over_to_caught_up_voter() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        let mut target = create_follower("2", &["1", "3"]);
+        let mut other = create_follower("3", &["1", "2"]);
+        leader.submit_command(b"command".to_vec()).unwrap();
+        replicate_until_success(&mut leader, &mut other);
+
+        leader.transfer_leadership(&"2".to_string()).unwrap();
+        assert!(matches!(
+            leader.submit_command(b"rejected".to_vec()),
+            Err(RaftError::TransferInProgress { .. })
+        ));
+
+        // TimeoutNow only goes out once the target has the whole log
+        assert!(leader.timeout_now_request().is_none());
+        replicate_until_success(&mut leader, &mut target);
+        let (peer_id, request) = leader.timeout_now_request().unwrap();
+        assert_eq!(peer_id, "2");
+
+        target.handle_timeout_now(request).unwrap();
+        assert_eq!(target.state(), NodeState::Candidate);
+        assert_eq!(target.current_term(), 2);
+
+        // Voters that just heard from the leader still grant the transfer vote
+        let vote_request = target.vote_request();
+        assert!(vote_request.leadership_transfer);
+        let response = other.handle_vote_request(vote_request.clone()).unwrap();
+        assert!(response.vote_granted);
+        target.handle_vote_response(&"3".to_string(), response).unwrap();
+        assert_eq!(target.state(), NodeState::Leader);
+
+        leader.handle_vote_request(vote_request).unwrap();
+        assert_eq!(leader.state(), NodeState::Follower);
+        assert_eq!(leader.current_term(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_leadership_transfer_gives_up_after_election_timeout() {
+        let mut leader = create_leader("1", &["2", "3"]);
+        assert!(leader.transfer_leadership(&"1".to_string()).is_err());
+        assert!(leader.transfer_leadership(&"4".to_string()).is_err());
+
+        leader.transfer_leadership(&"2".to_string()).unwrap();
+        assert!(leader.transfer_leadership(&"3".to_string()).is_err());
+        assert!(leader.check_transfer_timeout().is_none());
+
+        tokio::time::sleep(leader.max_election_timeout()).await;
+        assert!(matches!(
+            leader.check_transfer_timeout(),
+            Some(RaftError::TransferTimeout { .. })
+        ));
+        assert_eq!(leader.state(), NodeState::Leader);
+        assert!(leader.transfer_target().is_none());
+        leader.submit_command(b"command".to_vec()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_index_needs_commit_in_current_term() {
+        let follower = create_follower("2", &["1", "3"]);
+        assert!(matches!(follower.read_index(), Err(RaftError::NotLeader)));
+
+        let mut leader = create_leader("1", &["2", "3"]);
+        let mut peer = create_follower("2", &["1", "3"]);
+        assert!(matches!(leader.read_index(), Err(RaftError::ReadIndexNotReady)));
+        assert!(!leader.committed_in_current_term());
+
+        // The election NoOp is all it takes
+        replicate_until_success(&mut leader, &mut peer);
+        assert_eq!(leader.commit_index(), 1);
+        assert!(leader.committed_in_current_term());
+
+        // Reading appends nothing to the log
+        assert_eq!(leader.read_index().unwrap(), 1);
+        assert_eq!(leader.last_log_index(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_new_leader_commits_earlier_terms_through_noop() {
+        // Entries from term 1 reached nodes 1 and 2 but were never committed
+        let mut config = create_test_config("1");
+        config.peers = peer_list(&["2", "3"]);
+        let mut node1 = RaftNode::new(config);
+        let mut node2 = create_follower("2", &["1", "3"]);
+        for node in [&mut node1, &mut node2] {
+            node.handle_append_request(AppendRequest {
+                term: 1,
+                leader_id: "3".to_string(),
+                prev_log_index: 0,
+                prev_log_term: 0,
+                entries: vec![entry(1, 1), entry(2, 1)],
+                leader_commit: 0,
+            }).unwrap();
+        }
+
+        node1.start_election().unwrap();
+        let term = node1.current_term();
+        node1.handle_vote_response(&"2".to_string(), VoteResponse { term, vote_granted: true }).unwrap();
+        assert_eq!(node1.state(), NodeState::Leader);
+
+        let request = node1.append_request_for(&"3".to_string()).unwrap();
+        assert_eq!(request.prev_log_index, 2);
+        assert_eq!(request.entries.len(), 1);
+        assert_eq!(request.entries[0].entry_type, EntryType::NoOp);
+        assert_eq!(request.entries[0].term, term);
+
+        // Committing the NoOp commits everything before it, without any client command
+        replicate_until_success(&mut node1, &mut node2);
+        assert_eq!(node1.commit_index(), 3);
+        replicate_once(&mut node1, &mut node2);
+        assert_eq!(node2.commit_index(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_single_node_leader_commits_noop_at_once() {
+        let mut node = RaftNode::new(create_test_config("1"));
+        node.start_election().unwrap();
+
+        assert_eq!(node.state(), NodeState::Leader);
+        assert_eq!(node.commit_index(), 1);
+        assert_eq!(node.read_index().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lease_is_shorter_than_election_timeout_by_drift() {
+        let leader = create_leader("1", &["2", "3"]);
+        assert_eq!(leader.lease_duration(), Duration::from_millis(140));
+
+        let mut config = create_test_config("1");
+        config.clock_drift = 500;
+        assert_eq!(RaftNode::new(config).lease_duration(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_last_leader_contact() {
+        let leader = create_leader("1", &["2", "3"]);
+        assert_eq!(leader.last_leader_contact(), Some(Duration::ZERO));
+
+        let mut follower = create_follower("2", &["1", "3"]);
+        assert!(follower.last_leader_contact().is_none());
+
+        follower.handle_append_request(AppendRequest {
+            term: 1,
+            leader_id: "1".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![],
+            leader_commit: 0,
+        }).unwrap();
+        assert!(follower.last_leader_contact().unwrap() < Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_leader_address_comes_from_configuration() {
+        let mut follower = create_follower("2", &["1", "3"]);
+        assert!(follower.leader_address().is_none());
+
+        follower.handle_append_request(AppendRequest {
+            term: 1,
+            leader_id: "1".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![],
+            leader_commit: 0,
+        }).unwrap();
+        assert_eq!(follower.leader_address(), Some("raft-1:5000"));
+
+        // A leader missing from the configuration has no known address
+        follower.handle_append_request(AppendRequest {
+            term: 2,
+            leader_id: "9".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![],
+            leader_commit: 0,
+        }).unwrap();
+        assert_eq!(follower.leader_id().map(String::as_str), Some("9"));
+        assert!(follower.leader_address().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_peer_list_parsing() {
+        let peers = PeerInfo::parse_list("node-2=host-a:8081, node-3=http://host-b:8082 | host-b:9082,").unwrap();
+        assert_eq!(peers, vec![
+            PeerInfo::new("node-2", "host-a:8081"),
+            PeerInfo::new("node-3", "http://host-b:8082").with_grpc_address("host-b:9082"),
+        ]);
+
+        assert!(PeerInfo::parse_list("host-a:8081").is_err());
+        assert!(PeerInfo::parse_list("node-2=").is_err());
+        assert!(PeerInfo::parse_list("node-2=host-a:8081|").is_err());
+        assert_eq!(address_url("host-a:8081/"), "http://host-a:8081");
+        assert_eq!(address_url("https://host-b:8082"), "https://host-b:8082");
+    }
+
+    #[tokio::test]
+    async fn test_node_listed_in_its_own_peers_is_not_its_own_peer() {
+        let node = create_follower("1", &["1", "2", "3"]);
+        let members: Vec<&str> = node.cluster().members().iter().map(|m| m.node_id.as_str()).collect();
+        assert_eq!(members, vec!["1", "2", "3"]);
+        assert_eq!(node.cluster().address_of(&"2".to_string()), Some("raft-2:5000"));
+    }
+
+    /// One node of a cluster running over an `InMemoryNetwork`
+    struct ClusterNode {
+        node_id: NodeId,
+        raft: RaftHandle,
+        status_rx: watch::Receiver<NodeStatus>,
+        handle: JoinHandle<()>,
+    }
+
+    /// Start an event loop for each node, all reaching each other in memory
+    fn start_cluster(ids: &[&str]) -> (InMemoryNetwork, Vec<ClusterNode>) {
+        let network = InMemoryNetwork::new();
+        let nodes = ids
+            .iter()
+            .map(|id| {
+                let peers: Vec<&str> = ids.iter().copied().filter(|peer| peer != id).collect();
+                let transport = network.transport(id.to_string());
+                let (event_loop, raft) = RaftEventLoop::new(create_follower(id, &peers), transport);
+                network.register(id.to_string(), raft.clone());
+                let status_rx = raft.subscribe();
+                let handle = tokio::spawn(async move {
+                    let _ = event_loop.run().await;
+                });
+                ClusterNode { node_id: id.to_string(), raft, status_rx, handle }
+            })
+            .collect();
+        (network, nodes)
+    }
+
+    /// Wait until one of `nodes` other than `except` leads, and return it
+    async fn wait_for_leader<'a>(nodes: &'a [ClusterNode], except: Option<&NodeId>) -> &'a ClusterNode {
+        for _ in 0..200 {
+            let leader = nodes.iter().find(|node| {
+                Some(&node.node_id) != except && node.status_rx.borrow().state == NodeState::Leader
+            });
+            if let Some(leader) = leader {
+                return leader;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("no leader was elected");
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cluster_elects_leader_and_replicates() {
+        let (_network, nodes) = start_cluster(&["1", "2", "3"]);
+        let leader = wait_for_leader(&nodes, None).await;
+
+        let (index, _) = leader.raft.submit_command(b"command".to_vec(), None, None).await.unwrap();
+
+        for _ in 0..200 {
+            if nodes.iter().all(|node| node.status_rx.borrow().commit_index >= index) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        for node in &nodes {
+            assert!(node.status_rx.borrow().commit_index >= index, "{} did not commit", node.node_id);
+            node.handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_feeds_the_apply_loop() {
+        let (_network, nodes) = start_cluster(&["1"]);
+        let leader = wait_for_leader(&nodes, None).await;
+
+        let (index, _) = leader.raft.submit_command(b"command".to_vec(), None, None).await.unwrap();
+        let mut status_rx = leader.raft.subscribe();
+        status_rx.wait_for(|status| status.commit_index >= index).await.unwrap();
+
+        // The election NoOp and the command are both waiting to be applied
+        let entries = leader.raft.entries_to_apply().await.unwrap();
+        assert_eq!(entries.iter().map(|entry| entry.index).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(entries[1].data, b"command".to_vec());
+
+        leader.raft.set_last_applied(index).await.unwrap();
+        assert!(leader.raft.entries_to_apply().await.unwrap().is_empty());
+        assert_eq!(leader.raft.status().await.unwrap().last_applied, index);
+        assert_eq!(leader.raft.leader_contact().await.unwrap(), Some(Duration::ZERO));
+
+        // Once the loop is gone the handle reports it rather than hanging
+        let raft = leader.raft.clone();
+        let node = nodes.into_iter().next().unwrap();
+        node.handle.abort();
+        let _ = node.handle.await;
+        assert!(matches!(raft.status().await, Err(RaftError::Unavailable)));
+    }
+
+    #[tokio::test]
+    async fn test_full_event_queue_rejects_client_requests() {
+        let network = InMemoryNetwork::new();
+        let mut config = create_test_config("1");
+        config.event_queue_capacity = 1;
+        let (_event_loop, raft) = RaftEventLoop::new(RaftNode::new(config), network.transport("1".to_string()));
+
+        // Nothing drains the queue, so one event fills it
+        raft.set_last_applied(0).await.unwrap();
+        assert_eq!(raft.queue_depth(), 1);
+        assert!(matches!(
+            raft.submit_command(b"command".to_vec(), None, None).await,
+            Err(RaftError::Overloaded(_))
+        ));
+        assert!(matches!(raft.read_index(false).await, Err(RaftError::Overloaded(_))));
+
+        // Peer RPCs wait for room instead
+        let request = VoteRequest {
+            term: 1,
+            candidate_id: "2".to_string(),
+            last_log_index: 0,
+            last_log_term: 0,
+            leadership_transfer: false,
+        };
+        assert!(tokio::time::timeout(Duration::from_millis(50), raft.request_vote(request)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cluster_batches_commands_of_one_tick() {
+        let (_network, nodes) = start_cluster(&["1", "2", "3"]);
+        let leader = wait_for_leader(&nodes, None).await;
+
+        let mut responses = Vec::new();
+        for i in 0..20 {
+            let (response_tx, response_rx) = tokio::sync::oneshot::channel();
+            leader.raft.send(RaftEvent::SubmitCommand {
+                command: format!("command{}", i).into_bytes(),
+                client_id: None,
+                sequence_number: None,
+                response_tx,
+            }).await.unwrap();
+            responses.push(response_rx);
+        }
+        let mut appended = Vec::new();
+        for response_rx in responses {
+            appended.push(response_rx.await.unwrap().unwrap());
+        }
+
+        // Submission order is kept and nothing else lands in between
+        let (first_index, term) = appended[0];
+        for (i, (index, entry_term)) in appended.iter().enumerate() {
+            assert_eq!(*index, first_index + i as LogIndex);
+            assert_eq!(*entry_term, term);
+        }
+
+        let last_index = first_index + 19;
+        for _ in 0..200 {
+            if nodes.iter().all(|node| node.status_rx.borrow().commit_index >= last_index) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        for node in &nodes {
+            assert!(node.status_rx.borrow().commit_index >= last_index, "{} did not commit", node.node_id);
+            node.handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cluster_slow_follower_does_not_stall_commits() {
+        let (network, nodes) = start_cluster(&["1", "2", "3"]);
+        let leader = wait_for_leader(&nodes, None).await;
+        let slow = nodes.iter().find(|node| node.node_id != leader.node_id).unwrap();
+        network.set_latency(&slow.node_id, Duration::from_millis(150));
+
+        // Each command commits with the fast follower alone, long before the slow one answers
+        let started = tokio::time::Instant::now();
+        let mut last_index = 0;
+        for i in 0..5 {
+            let command = format!("command{}", i).into_bytes();
+            (last_index, _) = leader.raft.submit_command(command, None, None).await.unwrap();
+
+            let mut status_rx = leader.status_rx.clone();
+            tokio::time::timeout(Duration::from_secs(2), status_rx.wait_for(|status| status.commit_index >= last_index))
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        assert!(started.elapsed() < Duration::from_millis(600), "commits took {:?}", started.elapsed());
+
+        // The slow follower still catches up
+        for _ in 0..200 {
+            if slow.status_rx.borrow().commit_index >= last_index {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(slow.status_rx.borrow().commit_index >= last_index);
+
+        for node in &nodes {
+            node.handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cluster_replaces_partitioned_leader() {
+        let (network, nodes) = start_cluster(&["1", "2", "3"]);
+        let old_leader = wait_for_leader(&nodes, None).await;
+        let old_term = old_leader.status_rx.borrow().current_term;
+
+        network.disconnect(&old_leader.node_id);
+        let new_leader = wait_for_leader(&nodes, Some(&old_leader.node_id)).await;
+        assert!(new_leader.status_rx.borrow().current_term > old_term);
+
+        // CheckQuorum makes the cut-off leader step down on its own
+        for _ in 0..200 {
+            if old_leader.status_rx.borrow().state != NodeState::Leader {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_ne!(old_leader.status_rx.borrow().state, NodeState::Leader);
+
+        for node in &nodes {
+            node.handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cluster_read_waits_for_quorum_round() {
+        let (network, nodes) = start_cluster(&["1", "2", "3"]);
+        let leader = wait_for_leader(&nodes, None).await;
+        let (index, _) = leader.raft.submit_command(b"command".to_vec(), None, None).await.unwrap();
+        let mut status_rx = leader.status_rx.clone();
+        status_rx.wait_for(|status| status.commit_index >= index).await.unwrap();
+
+        // Heartbeats already in flight come back at once, but the read needs a round started after it
+        for node in nodes.iter().filter(|node| node.node_id != leader.node_id) {
+            network.set_latency(&node.node_id, Duration::from_millis(150));
+        }
+        let started = tokio::time::Instant::now();
+        let (read_index, mode) = leader.raft.read_index(false).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(150), "read confirmed after {:?}", started.elapsed());
+        assert_eq!(mode, ReadMode::ReadIndex);
+        assert!(read_index >= index);
+
+        // Without a quorum to answer, the read is never confirmed
+        for node in nodes.iter().filter(|node| node.node_id != leader.node_id) {
+            network.disconnect(&node.node_id);
+        }
+        let read = tokio::time::timeout(Duration::from_secs(2), leader.raft.read_index(false)).await;
+        assert!(matches!(read, Ok(Err(RaftError::NotLeader))), "{:?}", read);
+
+        for node in &nodes {
+            node.handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cluster_read_blocked_until_noop_commits() {
+        let (network, nodes) = start_cluster(&["1", "2", "3"]);
+        // Every AppendEntries takes a while, so a new leader's NoOp commits late
+        for node in &nodes {
+            network.set_latency(&node.node_id, Duration::from_millis(100));
+        }
+
+        let leader = loop {
+            if let Some(leader) = nodes.iter().find(|node| node.status_rx.borrow().state == NodeState::Leader) {
+                break leader;
+            }
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        };
+        assert_eq!(leader.status_rx.borrow().commit_index, 0);
+
+        // Served before the NoOp committed, the read would be at index 0
+        let (read_index, mode) = leader.raft.read_index(false).await.unwrap();
+        assert_eq!(mode, ReadMode::ReadIndex);
+        assert!(read_index >= 1);
+        assert!(leader.status_rx.borrow().commit_index >= read_index);
+
+        for node in &nodes {
+            node.handle.abort();
+        }
+    }
+
+    fn session_entry(index: LogIndex, term: Term) -> LogEntry {
+        LogEntry {
+            client_id: Some("client-7".to_string()),
+            sequence_number: Some(index),
+            ..entry(index, term)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_binary_encoding_roundtrip() {
+        let request = AppendRequest {
+            term: 4,
+            leader_id: "1".to_string(),
+            prev_log_index: 9,
+            prev_log_term: 3,
+            entries: vec![
+                entry(10, 4),
+                session_entry(11, 4),
+                LogEntry { entry_type: EntryType::NoOp, data: Default::default(), ..entry(12, 4) },
+            ],
+            leader_commit: 9,
+        };
+        let bytes = codec::encode(&request);
+        assert_eq!(bytes[0], codec::WIRE_VERSION);
+        let decoded: AppendRequest = codec::decode(&bytes).unwrap();
+        assert_eq!(decoded.entries, request.entries);
+        assert_eq!((decoded.term, decoded.prev_log_index, decoded.leader_commit), (4, 9, 9));
+
+        // Byte vectors are no longer JSON arrays of numbers
+        assert!(bytes.len() * 2 < serde_json::to_vec(&request).unwrap().len());
+
+        let vote = VoteRequest {
+            term: 5,
+            candidate_id: "2".to_string(),
+            last_log_index: 12,
+            last_log_term: 4,
+            leadership_transfer: true,
+        };
+        let decoded: VoteRequest = codec::decode(&codec::encode(&vote)).unwrap();
+        assert_eq!((decoded.term, decoded.candidate_id.as_str()), (5, "2"));
+        assert!(decoded.leadership_transfer);
+
+        let response = AppendResponse { term: 4, success: false, conflict_index: Some(3), conflict_term: None };
+        let decoded: AppendResponse = codec::decode(&codec::encode(&response)).unwrap();
+        assert_eq!((decoded.conflict_index, decoded.conflict_term), (Some(3), None));
+    }
+
+    #[tokio::test]
+    async fn test_binary_encoding_keeps_empty_session_fields() {
+        // Empty and zero session fields are not the same as no session: the
+        // session check must see the same entry on every node
+        let entries = [
+            LogEntry { client_id: Some(String::new()), sequence_number: Some(0), ..entry(1, 1) },
+            LogEntry { client_id: Some("client-1".to_string()), sequence_number: Some(0), ..entry(2, 1) },
+            entry(3, 1),
+        ];
+        for entry in entries {
+            let decoded: LogEntry = codec::decode(&codec::encode(&entry)).unwrap();
+            assert_eq!(decoded, entry);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_binary_encoding_refuses_unknown_version() {
+        let mut bytes = codec::encode(&entry(1, 1));
+        bytes[0] = codec::WIRE_VERSION + 1;
+
+        let result = codec::decode::<LogEntry>(&bytes);
+        assert!(matches!(result, Err(RaftError::UnsupportedWireVersion { version }) if version == codec::WIRE_VERSION + 1));
+        assert!(matches!(codec::decode::<LogEntry>(&[]), Err(RaftError::Decode(_))));
+    }
+
+    #[tokio::test]
+    async fn test_binary_encoding_refuses_unknown_entry_type() {
+        use prost::Message;
+
+        // An entry type from a newer release must not be applied as a command
+        let entry = proto::raft::LogEntry { index: 1, term: 1, entry_type: 7, ..Default::default() };
+        let mut bytes = vec![codec::WIRE_VERSION];
+        entry.encode(&mut bytes).unwrap();
+        assert!(matches!(codec::decode::<LogEntry>(&bytes), Err(RaftError::Decode(_))));
+
+        let request = proto::raft::AppendEntriesRequest { term: 1, entries: vec![entry], ..Default::default() };
+        let mut bytes = vec![codec::WIRE_VERSION];
+        request.encode(&mut bytes).unwrap();
+        assert!(matches!(codec::decode::<AppendRequest>(&bytes), Err(RaftError::Decode(_))));
+    }
+
+    #[tokio::test]
+    async fn test_file_storage_reads_json_records_from_older_releases() {
+        let dir = temp_storage_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // A segment written by a release that stored entries as JSON
+        let mut segment = Vec::new();
+        for entry in [entry(1, 1), session_entry(2, 1)] {
+            let payload = serde_json::to_vec(&entry).unwrap();
+            segment.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            segment.extend_from_slice(&crate::storage::crc32(&payload).to_le_bytes());
+            segment.extend_from_slice(&payload);
+        }
+        std::fs::write(dir.join(format!("log-{:020}.wal", 1)), segment).unwrap();
+
+        // New appends go in the binary encoding after the old records
+        let mut storage = FileStorage::open(&dir).unwrap();
+        assert_eq!(storage.load().unwrap().log, vec![entry(1, 1), session_entry(2, 1)]);
+        storage.append(&[session_entry(3, 2)]).unwrap();
+
+        let mut storage = FileStorage::open(&dir).unwrap();
+        assert_eq!(storage.load().unwrap().log, vec![entry(1, 1), session_entry(2, 1), session_entry(3, 2)]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
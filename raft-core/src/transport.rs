@@ -0,0 +1,43 @@
+//! Transports carrying Raft RPCs between nodes
+//!
+//! The event loop only talks to its peers through a [`Transport`], so the same
+//! loop runs over HTTP/JSON, gRPC, or in-process channels in tests.
+
+use async_trait::async_trait;
+
+use crate::types::*;
+use crate::RaftResult;
+
+pub mod grpc;
+pub mod http;
+pub mod memory;
+
+pub use grpc::GrpcTransport;
+pub use http::HttpTransport;
+pub use memory::{InMemoryNetwork, InMemoryTransport};
+
+/// Sends Raft RPCs to other members of the cluster
+///
+/// Implementations are cloned into the task that waits on each RPC, so they
+/// should be cheap to clone and share their connections between clones.
+#[async_trait]
+pub trait Transport: Clone + Send + Sync + 'static {
+    /// Ask a peer for its vote
+    async fn request_vote(&self, peer: &PeerInfo, request: &VoteRequest) -> RaftResult<VoteResponse>;
+
+    /// Ask a peer whether it would vote for us, without disturbing its term
+    async fn pre_vote(&self, peer: &PeerInfo, request: &PreVoteRequest) -> RaftResult<PreVoteResponse>;
+
+    /// Replicate entries to a peer, or send it a heartbeat
+    async fn append_entries(&self, peer: &PeerInfo, request: &AppendRequest) -> RaftResult<AppendResponse>;
+
+    /// Send a peer one chunk of a snapshot
+    async fn install_snapshot(
+        &self,
+        peer: &PeerInfo,
+        request: &InstallSnapshotRequest,
+    ) -> RaftResult<InstallSnapshotResponse>;
+
+    /// Tell a peer to start an election immediately
+    async fn timeout_now(&self, peer: &PeerInfo, request: &TimeoutNowRequest) -> RaftResult<TimeoutNowResponse>;
+}
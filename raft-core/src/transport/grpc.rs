@@ -0,0 +1,103 @@
+//! gRPC transport built on the `RaftService` from `proto/raft.proto`
+//!
+//! Messages are converted to their protobuf form with the conversions in
+//! [`crate::codec`].
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use proto::raft as pb;
+use proto::raft::raft_service_client::RaftServiceClient;
+use tokio::time::Duration;
+use tonic::transport::{Channel, Endpoint};
+
+use super::Transport;
+use crate::error::RaftError;
+use crate::types::*;
+use crate::RaftResult;
+
+/// Transport sending Raft RPCs to each peer's gRPC `RaftService`
+///
+/// Each peer is dialled at its `grpc_address`, since the gRPC service listens
+/// on a port of its own; a peer without one cannot be reached. Channels
+/// connect lazily and are shared by every clone of the transport.
+#[derive(Debug, Clone, Default)]
+pub struct GrpcTransport {
+    channels: Arc<Mutex<HashMap<String, Channel>>>,
+}
+
+impl GrpcTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a client for the peer, reusing its channel if we have one
+    fn client(&self, peer: &PeerInfo) -> RaftResult<RaftServiceClient<Channel>> {
+        let Some(address) = &peer.grpc_address else {
+            return Err(RaftError::Network(format!("Peer {} has no gRPC address", peer.node_id)));
+        };
+
+        let mut channels = self.channels.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(channel) = channels.get(address) {
+            return Ok(RaftServiceClient::new(channel.clone()));
+        }
+
+        let channel = Endpoint::from_shared(address_url(address))
+            .map_err(|e| RaftError::Network(format!("Invalid address {}: {}", address, e)))?
+            .connect_timeout(Duration::from_millis(1000))
+            .connect_lazy();
+        channels.insert(address.clone(), channel.clone());
+        Ok(RaftServiceClient::new(channel))
+    }
+}
+
+/// Wait at most `timeout` for a call and turn its status into a `RaftError`
+async fn call<T>(
+    timeout: Duration,
+    call: impl Future<Output = Result<tonic::Response<T>, tonic::Status>>,
+) -> RaftResult<T> {
+    match tokio::time::timeout(timeout, call).await {
+        Ok(Ok(response)) => Ok(response.into_inner()),
+        Ok(Err(status)) => Err(RaftError::Network(status.to_string())),
+        Err(_) => Err(RaftError::Network("gRPC call timed out".to_string())),
+    }
+}
+
+#[async_trait]
+impl Transport for GrpcTransport {
+    async fn request_vote(&self, peer: &PeerInfo, request: &VoteRequest) -> RaftResult<VoteResponse> {
+        let mut client = self.client(peer)?;
+        let request = pb::RequestVoteRequest::from(request);
+        call(Duration::from_millis(1000), client.request_vote(request)).await.map(Into::into)
+    }
+
+    async fn pre_vote(&self, peer: &PeerInfo, request: &PreVoteRequest) -> RaftResult<PreVoteResponse> {
+        let mut client = self.client(peer)?;
+        let request = pb::PreVoteRequest::from(request);
+        call(Duration::from_millis(1000), client.pre_vote(request)).await.map(Into::into)
+    }
+
+    async fn append_entries(&self, peer: &PeerInfo, request: &AppendRequest) -> RaftResult<AppendResponse> {
+        let mut client = self.client(peer)?;
+        let request = pb::AppendEntriesRequest::from(request);
+        call(Duration::from_millis(2000), client.append_entries(request)).await.map(Into::into)
+    }
+
+    async fn install_snapshot(
+        &self,
+        peer: &PeerInfo,
+        request: &InstallSnapshotRequest,
+    ) -> RaftResult<InstallSnapshotResponse> {
+        let mut client = self.client(peer)?;
+        let request = pb::InstallSnapshotRequest::from(request);
+        call(Duration::from_millis(5000), client.install_snapshot(request)).await.map(Into::into)
+    }
+
+    async fn timeout_now(&self, peer: &PeerInfo, request: &TimeoutNowRequest) -> RaftResult<TimeoutNowResponse> {
+        let mut client = self.client(peer)?;
+        let request = pb::TimeoutNowRequest::from(request);
+        call(Duration::from_millis(1000), client.timeout_now(request)).await.map(Into::into)
+    }
+}
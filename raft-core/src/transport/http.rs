@@ -0,0 +1,152 @@
+//! HTTP transport
+//!
+//! Each RPC is a POST to `/raft/<rpc>` on the peer's address; the server crate
+//! mounts the matching routes. Bodies use the binary encoding of
+//! [`crate::codec`]. A peer that answers 415 Unsupported Media Type, such as
+//! one on a release from before the binary encoding, is sent JSON instead and
+//! offered the binary encoding again after [`RENEGOTIATE_AFTER`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use async_trait::async_trait;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::time::Duration;
+use tracing::debug;
+
+use super::Transport;
+use crate::codec::{self, WireMessage};
+use crate::error::RaftError;
+use crate::types::*;
+use crate::RaftResult;
+
+/// Path of the vote RPC
+pub const VOTE_PATH: &str = "/raft/vote";
+/// Path of the pre-vote RPC
+pub const PRE_VOTE_PATH: &str = "/raft/prevote";
+/// Path of the append entries RPC
+pub const APPEND_PATH: &str = "/raft/append";
+/// Path of the install snapshot RPC
+pub const SNAPSHOT_PATH: &str = "/raft/snapshot";
+/// Path of the TimeoutNow RPC
+pub const TIMEOUT_NOW_PATH: &str = "/raft/timeout-now";
+
+/// Content type of bodies in the binary encoding
+pub const BINARY_CONTENT_TYPE: &str = "application/x-raft";
+
+/// How long a peer that refused the binary encoding is sent JSON
+pub const RENEGOTIATE_AFTER: Duration = Duration::from_secs(60);
+
+/// Transport sending Raft RPCs over HTTP
+///
+/// Clones share one connection pool, so connections to a peer stay open
+/// between requests.
+#[derive(Debug, Clone, Default)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    /// Peers that refused the binary encoding, by address, and when they did
+    json_peers: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl HttpTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the peer should be sent JSON rather than the binary encoding
+    fn wants_json(&self, peer: &PeerInfo) -> bool {
+        let mut json_peers = self.json_peers.lock().unwrap_or_else(|e| e.into_inner());
+        match json_peers.get(&peer.address) {
+            Some(since) if since.elapsed() < RENEGOTIATE_AFTER => true,
+            Some(_) => {
+                json_peers.remove(&peer.address);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// POST `request` to `path` on the peer and decode its answer
+    async fn post<Req, Resp>(&self, peer: &PeerInfo, path: &str, request: &Req, timeout: Duration) -> RaftResult<Resp>
+    where
+        Req: WireMessage + Serialize + Sync,
+        Resp: WireMessage + DeserializeOwned,
+    {
+        let url = format!("{}{}", address_url(&peer.address), path);
+
+        if !self.wants_json(peer) {
+            let response = self.client
+                .post(&url)
+                .header(CONTENT_TYPE, BINARY_CONTENT_TYPE)
+                .body(codec::encode(request))
+                .timeout(timeout)
+                .send()
+                .await
+                .map_err(|e| RaftError::Network(e.to_string()))?;
+
+            if response.status() != StatusCode::UNSUPPORTED_MEDIA_TYPE {
+                let response = check_status(response)?;
+                let bytes = response.bytes().await.map_err(|e| RaftError::Network(e.to_string()))?;
+                return codec::decode(&bytes);
+            }
+
+            debug!("{} refused the binary encoding, falling back to JSON", peer.node_id);
+            self.json_peers
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(peer.address.clone(), Instant::now());
+        }
+
+        let response = self.client
+            .post(&url)
+            .json(request)
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(|e| RaftError::Network(e.to_string()))?;
+
+        check_status(response)?
+            .json()
+            .await
+            .map_err(|e| RaftError::Network(e.to_string()))
+    }
+}
+
+/// Turn a response other than 2xx into a `RaftError`
+fn check_status(response: reqwest::Response) -> RaftResult<reqwest::Response> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(RaftError::Network(format!("HTTP {}", response.status())))
+    }
+}
+
+#[async_trait]
+impl Transport for HttpTransport {
+    async fn request_vote(&self, peer: &PeerInfo, request: &VoteRequest) -> RaftResult<VoteResponse> {
+        self.post(peer, VOTE_PATH, request, Duration::from_millis(1000)).await
+    }
+
+    async fn pre_vote(&self, peer: &PeerInfo, request: &PreVoteRequest) -> RaftResult<PreVoteResponse> {
+        self.post(peer, PRE_VOTE_PATH, request, Duration::from_millis(1000)).await
+    }
+
+    async fn append_entries(&self, peer: &PeerInfo, request: &AppendRequest) -> RaftResult<AppendResponse> {
+        self.post(peer, APPEND_PATH, request, Duration::from_millis(2000)).await
+    }
+
+    async fn install_snapshot(
+        &self,
+        peer: &PeerInfo,
+        request: &InstallSnapshotRequest,
+    ) -> RaftResult<InstallSnapshotResponse> {
+        self.post(peer, SNAPSHOT_PATH, request, Duration::from_millis(5000)).await
+    }
+
+    async fn timeout_now(&self, peer: &PeerInfo, request: &TimeoutNowRequest) -> RaftResult<TimeoutNowResponse> {
+        self.post(peer, TIMEOUT_NOW_PATH, request, Duration::from_millis(1000)).await
+    }
+}
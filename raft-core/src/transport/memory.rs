@@ -0,0 +1,147 @@
+//! In-process transport for tests
+//!
+//! Every node registers its event loop's handle with a shared
+//! [`InMemoryNetwork`]; RPCs are delivered straight to the target's event loop. Nodes can be cut
+//! off and reconnected to simulate partitions, or slowed down to simulate a
+//! lagging peer.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::time::Duration;
+
+use super::Transport;
+use crate::error::RaftError;
+use crate::handle::RaftHandle;
+use crate::types::*;
+use crate::RaftResult;
+
+/// How long to wait for the target's event loop to answer
+const RPC_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Registered nodes, the ones currently cut off and the delay of RPCs to each
+#[derive(Default)]
+struct NetworkState {
+    nodes: HashMap<NodeId, RaftHandle>,
+    disconnected: HashSet<NodeId>,
+    latency: HashMap<NodeId, Duration>,
+}
+
+/// A set of nodes that reach each other through their event channels
+#[derive(Clone, Default)]
+pub struct InMemoryNetwork {
+    state: Arc<Mutex<NetworkState>>,
+}
+
+impl InMemoryNetwork {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the transport a node sends with
+    pub fn transport(&self, node_id: NodeId) -> InMemoryTransport {
+        InMemoryTransport {
+            node_id,
+            network: self.clone(),
+        }
+    }
+
+    /// Register the handle of a node's event loop so RPCs reach it
+    pub fn register(&self, node_id: NodeId, handle: RaftHandle) {
+        self.lock().nodes.insert(node_id, handle);
+    }
+
+    /// Drop every RPC to or from the node until it is reconnected
+    pub fn disconnect(&self, node_id: &NodeId) {
+        self.lock().disconnected.insert(node_id.clone());
+    }
+
+    /// Deliver RPCs to and from the node again
+    pub fn reconnect(&self, node_id: &NodeId) {
+        self.lock().disconnected.remove(node_id);
+    }
+
+    /// Delay every RPC to the node by `latency` before it is delivered
+    pub fn set_latency(&self, node_id: &NodeId, latency: Duration) {
+        self.lock().latency.insert(node_id.clone(), latency);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, NetworkState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get the handle of `to`, if both ends are connected
+    fn route(&self, from: &NodeId, to: &NodeId) -> RaftResult<RaftHandle> {
+        let state = self.lock();
+        if state.disconnected.contains(from) || state.disconnected.contains(to) {
+            return Err(RaftError::Network(format!("{} is unreachable from {}", to, from)));
+        }
+        state.nodes
+            .get(to)
+            .cloned()
+            .ok_or_else(|| RaftError::Network(format!("{} is not on the network", to)))
+    }
+}
+
+/// Transport of one node on an [`InMemoryNetwork`]
+#[derive(Clone)]
+pub struct InMemoryTransport {
+    node_id: NodeId,
+    network: InMemoryNetwork,
+}
+
+impl InMemoryTransport {
+    /// Hand a request to the peer's event loop and wait for its answer
+    async fn call<F, T>(&self, peer: &PeerInfo, call: impl FnOnce(RaftHandle) -> F) -> RaftResult<T>
+    where
+        F: std::future::Future<Output = RaftResult<T>>,
+    {
+        let latency = self.network.lock().latency.get(&peer.node_id).copied();
+        if let Some(latency) = latency {
+            tokio::time::sleep(latency).await;
+        }
+        let handle = self.network.route(&self.node_id, &peer.node_id)?;
+
+        let response = tokio::time::timeout(RPC_TIMEOUT, call(handle))
+            .await
+            .map_err(|_| RaftError::Network(format!("{} did not answer in time", peer.node_id)))?
+            .map_err(|_| RaftError::Network(format!("{} has shut down", peer.node_id)))?;
+
+        // A partition that started while the request was in flight loses the answer
+        self.network.route(&self.node_id, &peer.node_id)?;
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl Transport for InMemoryTransport {
+    async fn request_vote(&self, peer: &PeerInfo, request: &VoteRequest) -> RaftResult<VoteResponse> {
+        let request = request.clone();
+        self.call(peer, |handle| async move { handle.request_vote(request).await }).await
+    }
+
+    async fn pre_vote(&self, peer: &PeerInfo, request: &PreVoteRequest) -> RaftResult<PreVoteResponse> {
+        let request = request.clone();
+        self.call(peer, |handle| async move { handle.pre_vote(request).await }).await
+    }
+
+    async fn append_entries(&self, peer: &PeerInfo, request: &AppendRequest) -> RaftResult<AppendResponse> {
+        let request = request.clone();
+        self.call(peer, |handle| async move { handle.append_entries(request).await }).await
+    }
+
+    async fn install_snapshot(
+        &self,
+        peer: &PeerInfo,
+        request: &InstallSnapshotRequest,
+    ) -> RaftResult<InstallSnapshotResponse> {
+        let request = request.clone();
+        self.call(peer, |handle| async move { handle.install_snapshot(request).await }).await
+    }
+
+    async fn timeout_now(&self, peer: &PeerInfo, request: &TimeoutNowRequest) -> RaftResult<TimeoutNowResponse> {
+        let request = request.clone();
+        self.call(peer, |handle| async move { handle.timeout_now(request).await }).await
+    }
+}
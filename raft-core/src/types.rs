@@ -1,4 +1,7 @@
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use crate::error::RaftError;
 
 /// Unique identifier for a Raft node
 pub type NodeId = String;
@@ -13,6 +16,8 @@ pub type LogIndex = u64;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum NodeState {
     Follower,
+    /// Asking for pre-votes before starting a real election (only with `pre_vote`)
+    PreCandidate,
     Candidate,
     Leader,
 }
@@ -21,19 +26,47 @@ impl std::fmt::Display for NodeState {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             NodeState::Follower => write!(f, "Follower"),
+            NodeState::PreCandidate => write!(f, "PreCandidate"),
             NodeState::Candidate => write!(f, "Candidate"),
             NodeState::Leader => write!(f, "Leader"),
         }
     }
 }
 
+/// How a read was confirmed to be up to date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadMode {
+    /// Appended to the log and applied like a write
+    Log,
+    /// Leadership confirmed by a heartbeat round after the read arrived
+    ReadIndex,
+    /// Served locally while the leader's lease is valid
+    Lease,
+    /// Served from the local state machine without confirming leadership
+    /// (stale and bounded-staleness reads)
+    Local,
+}
+
+impl std::fmt::Display for ReadMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadMode::Log => write!(f, "log"),
+            ReadMode::ReadIndex => write!(f, "read_index"),
+            ReadMode::Lease => write!(f, "lease"),
+            ReadMode::Local => write!(f, "local"),
+        }
+    }
+}
+
 /// A log entry in the Raft log
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LogEntry {
     pub index: LogIndex,
     pub term: Term,
     pub entry_type: EntryType,
-    pub data: Vec<u8>,
+    /// Shared rather than copied when the entry is encoded or sent
+    pub data: Bytes,
     pub client_id: Option<String>,
     pub sequence_number: Option<u64>,
 }
@@ -50,28 +83,188 @@ pub enum EntryType {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeConfig {
     pub node_id: NodeId,
+    /// Address peers and clients reach this node at, as recorded in the
+    /// cluster configuration; never a wildcard bind address
     pub address: String,
-    pub peers: Vec<String>,
+    /// Address of this node's gRPC `RaftService`, if it serves one
+    pub grpc_address: Option<String>,
+    /// The other voters the cluster starts with, before any configuration entry
+    pub peers: Vec<PeerInfo>,
     pub election_timeout_min: u64,
     pub election_timeout_max: u64,
     pub heartbeat_interval: u64,
+    pub max_append_entries: usize,
+    /// Append entries requests pipelined to one follower before its answers arrive
+    pub max_inflight_appends: usize,
+    pub snapshot_chunk_size: usize,
+    pub max_learner_lag: LogIndex,
+    pub pre_vote: bool,
+    /// Clock drift allowance in milliseconds, subtracted from leader leases
+    pub clock_drift: u64,
+    /// Entries the leader may hold past its commit index before it rejects commands
+    pub max_uncommitted_entries: usize,
+    /// Bytes of command data the leader may hold past its commit index
+    pub max_uncommitted_bytes: usize,
+    /// Events the event loop's queue holds before client requests are rejected
+    pub event_queue_capacity: usize,
 }
 
 /// Information about a peer node
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PeerInfo {
     pub node_id: NodeId,
+    /// Address of the node's HTTP server, which serves clients and peer RPCs
     pub address: String,
     pub voting: bool,
+    /// Address of the node's gRPC `RaftService`, which listens on its own port
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grpc_address: Option<String>,
+}
+
+impl PeerInfo {
+    /// Create a voting peer
+    pub fn new(node_id: impl Into<NodeId>, address: impl Into<String>) -> Self {
+        Self {
+            node_id: node_id.into(),
+            address: address.into(),
+            voting: true,
+            grpc_address: None,
+        }
+    }
+
+    /// Set the address of the peer's gRPC `RaftService`
+    pub fn with_grpc_address(mut self, grpc_address: impl Into<String>) -> Self {
+        self.grpc_address = Some(grpc_address.into());
+        self
+    }
+
+    /// Parse a comma-separated list of `node_id=address` peers, as in `RAFT_PEERS`
+    ///
+    /// A peer serving gRPC can add its gRPC address after a bar, as in
+    /// `node-2=host-a:8081|host-a:9081`.
+    pub fn parse_list(peers: &str) -> Result<Vec<PeerInfo>, RaftError> {
+        peers
+            .split(',')
+            .map(str::trim)
+            .filter(|peer| !peer.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromStr for PeerInfo {
+    type Err = RaftError;
+
+    /// Parse a voting peer written as `node_id=address` or `node_id=address|grpc_address`
+    fn from_str(peer: &str) -> Result<Self, Self::Err> {
+        let invalid = || RaftError::Configuration(format!(
+            "Peer '{}' is not of the form node_id=address or node_id=address|grpc_address", peer
+        ));
+        let (node_id, addresses) = peer.split_once('=').ok_or_else(invalid)?;
+        let (address, grpc_address) = match addresses.split_once('|') {
+            Some((address, grpc_address)) => (address.trim(), Some(grpc_address.trim())),
+            None => (addresses.trim(), None),
+        };
+        if node_id.trim().is_empty() || address.is_empty() || grpc_address == Some("") {
+            return Err(invalid());
+        }
+
+        let peer = PeerInfo::new(node_id.trim(), address);
+        Ok(match grpc_address {
+            Some(grpc_address) => peer.with_grpc_address(grpc_address),
+            None => peer,
+        })
+    }
+}
+
+/// Turn a node address into a URL prefix, defaulting to `http://` when it has no scheme
+pub fn address_url(address: &str) -> String {
+    let address = address.trim_end_matches('/');
+    if address.contains("://") {
+        address.to_string()
+    } else {
+        format!("http://{}", address)
+    }
 }
 
 /// Cluster configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// During a membership change the cluster runs under a joint configuration
+/// (C_old,new): `old_nodes` holds the previous members and every decision needs
+/// a majority of both `nodes` and `old_nodes`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ClusterConfig {
     pub nodes: Vec<PeerInfo>,
+    #[serde(default)]
+    pub old_nodes: Option<Vec<PeerInfo>>,
     pub config_index: LogIndex,
 }
 
+impl ClusterConfig {
+    /// Check if this is a joint configuration
+    pub fn is_joint(&self) -> bool {
+        self.old_nodes.is_some()
+    }
+
+    /// Check if a node is a voting member of either configuration
+    pub fn is_voter(&self, node_id: &NodeId) -> bool {
+        self.configs()
+            .any(|nodes| nodes.iter().any(|n| n.voting && &n.node_id == node_id))
+    }
+
+    /// Get every member of either configuration, each once
+    pub fn members(&self) -> Vec<&PeerInfo> {
+        let mut members: Vec<&PeerInfo> = Vec::new();
+        for node in self.configs().flatten() {
+            if !members.iter().any(|m| m.node_id == node.node_id) {
+                members.push(node);
+            }
+        }
+        members
+    }
+
+    /// Get the address of a member of either configuration
+    pub fn address_of(&self, node_id: &NodeId) -> Option<&str> {
+        self.configs()
+            .flatten()
+            .find(|n| &n.node_id == node_id)
+            .map(|n| n.address.as_str())
+    }
+
+    /// Check if the nodes for which `granted` holds form a quorum
+    pub fn is_quorum(&self, granted: impl Fn(&NodeId) -> bool) -> bool {
+        self.configs().all(|nodes| {
+            let voters: Vec<&PeerInfo> = nodes.iter().filter(|n| n.voting).collect();
+            let votes = voters.iter().filter(|n| granted(&n.node_id)).count();
+            votes > voters.len() / 2
+        })
+    }
+
+    /// Get the highest index that a quorum has replicated, given each node's match index
+    pub fn quorum_index(&self, match_index: impl Fn(&NodeId) -> LogIndex) -> LogIndex {
+        self.configs()
+            .map(|nodes| {
+                let mut indices: Vec<LogIndex> = nodes
+                    .iter()
+                    .filter(|n| n.voting)
+                    .map(|n| match_index(&n.node_id))
+                    .collect();
+                if indices.is_empty() {
+                    return 0;
+                }
+                indices.sort_unstable_by(|a, b| b.cmp(a));
+                indices[indices.len() / 2]
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Iterate over the member lists that each need a majority
+    fn configs(&self) -> impl Iterator<Item = &Vec<PeerInfo>> {
+        std::iter::once(&self.nodes).chain(self.old_nodes.iter())
+    }
+}
+
 /// Vote request parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoteRequest {
@@ -79,6 +272,10 @@ pub struct VoteRequest {
     pub candidate_id: NodeId,
     pub last_log_index: LogIndex,
     pub last_log_term: Term,
+    /// Set when the leader asked the candidate to run (TimeoutNow), so voters
+    /// do not ignore it for having heard from that leader recently
+    #[serde(default)]
+    pub leadership_transfer: bool,
 }
 
 /// Vote response
@@ -88,6 +285,38 @@ pub struct VoteResponse {
     pub vote_granted: bool,
 }
 
+/// Pre-vote request parameters
+///
+/// Sent before a real election with the term the candidate would campaign in;
+/// receivers answer without changing their own term or vote.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreVoteRequest {
+    pub term: Term,
+    pub candidate_id: NodeId,
+    pub last_log_index: LogIndex,
+    pub last_log_term: Term,
+}
+
+/// Pre-vote response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreVoteResponse {
+    pub term: Term,
+    pub vote_granted: bool,
+}
+
+/// TimeoutNow request: the leader asks a caught-up voter to start an election now
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeoutNowRequest {
+    pub term: Term,
+    pub leader_id: NodeId,
+}
+
+/// TimeoutNow response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeoutNowResponse {
+    pub term: Term,
+}
+
 /// Append entries request parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppendRequest {
@@ -107,3 +336,34 @@ pub struct AppendResponse {
     pub conflict_index: Option<LogIndex>,
     pub conflict_term: Option<Term>,
 }
+
+/// A snapshot of the state machine covering the log up to `last_included_index`
+///
+/// `config` is the cluster configuration in effect at that index, if it came
+/// from the log rather than from the node's static configuration.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub last_included_index: LogIndex,
+    pub last_included_term: Term,
+    pub config: Option<ClusterConfig>,
+    pub data: Vec<u8>,
+}
+
+/// Install snapshot request parameters (one chunk of a snapshot)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallSnapshotRequest {
+    pub term: Term,
+    pub leader_id: NodeId,
+    pub last_included_index: LogIndex,
+    pub last_included_term: Term,
+    pub config: Option<ClusterConfig>,
+    pub offset: u64,
+    pub data: Bytes,
+    pub done: bool,
+}
+
+/// Install snapshot response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallSnapshotResponse {
+    pub term: Term,
+}
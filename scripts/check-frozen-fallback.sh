@@ -0,0 +1,21 @@
+#!/usr/bin/env bash
+# Fail if any commit in the given range edits both proto/raft.proto and the
+# frozen wire format in proto/src/fallback.rs.
+#
+# Usage: scripts/check-frozen-fallback.sh <base>..<head>
+set -euo pipefail
+
+range="${1:?usage: $0 <base>..<head>}"
+status=0
+
+for commit in $(git rev-list "$range"); do
+    files=$(git diff-tree --no-commit-id --name-only -r --root "$commit")
+    if grep -qx 'proto/raft.proto' <<<"$files" && grep -qx 'proto/src/fallback.rs' <<<"$files"; then
+        echo "error: $(git log -1 --format='%h %s' "$commit")"
+        echo "       edits proto/raft.proto and proto/src/fallback.rs together;"
+        echo "       fallback.rs is the deployed wire format and must not change with the schema"
+        status=1
+    fi
+done
+
+exit $status
@@ -0,0 +1,692 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::{FutureExt, StreamExt};
+use futures::stream::FuturesUnordered;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{RwLock, mpsc, oneshot, watch};
+use tracing::{debug, info, warn};
+
+use raft_core::{RaftHandle, RaftResult, NodeStatus, NodeState, EntryType, LogEntry, LogIndex, ReadMode, Term};
+use state::{SessionCheck, SessionTable, StateMachine};
+use state::state_machine::{Command, CommandResult};
+use crate::error::ServerError;
+use crate::metrics::RaftMetrics;
+
+/// The state machine's answer to a command and the log index it was applied at
+type CommandReply = Result<(CommandResult, LogIndex), ServerError>;
+
+type ReadReply = Result<ReadOutcome, ServerError>;
+
+/// A served read
+#[derive(Debug)]
+pub struct ReadOutcome {
+    /// The state machine's answer
+    pub result: CommandResult,
+    /// How the read was confirmed to be up to date
+    pub mode: ReadMode,
+    /// The `last_applied` index the read was served at
+    pub applied_index: LogIndex,
+}
+
+/// Consistency a client asks for when reading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadConsistency {
+    /// Linearizable, through the log or by read index
+    Linearizable,
+    /// Linearizable while the leader holds its lease, by read index otherwise
+    Lease,
+    /// Whatever this node has applied, leader or not
+    Stale,
+    /// This node's state, as long as it is within the given lag
+    Bounded(MaxLag),
+}
+
+/// How far behind a bounded-staleness read may be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxLag {
+    /// Committed entries not yet applied on this node
+    Entries(u64),
+    /// Milliseconds since this node last heard from a leader
+    Millis(u64),
+}
+
+/// The event loop's answer to a submission, paired with the client waiting on it
+type Submitted = (RaftResult<(LogIndex, Term)>, oneshot::Sender<CommandReply>);
+
+/// A client command waiting to be submitted to Raft
+pub(crate) struct Submission {
+    pub command: Command,
+    pub session: Option<(String, u64)>,
+    pub response_tx: oneshot::Sender<CommandReply>,
+}
+
+/// The event loop's answer to a read index request, paired with the read waiting on it
+type ReadIndexed = (RaftResult<(LogIndex, ReadMode)>, Read);
+
+/// A read-only client command waiting to be served
+pub(crate) struct Read {
+    pub command: Command,
+    pub consistency: ReadConsistency,
+    pub response_tx: oneshot::Sender<ReadReply>,
+}
+
+/// A client command parked on the log index it was appended at
+struct Waiter {
+    term: Term,
+    response_tx: oneshot::Sender<CommandReply>,
+}
+
+/// Handle used by request handlers to submit commands and wait for their result
+#[derive(Clone)]
+pub struct ApplierHandle {
+    submit_tx: mpsc::Sender<Submission>,
+    read_tx: mpsc::Sender<Read>,
+    read_mode: ReadMode,
+}
+
+impl ApplierHandle {
+    /// Submit a command and wait until it has been committed and applied
+    ///
+    /// Resolves with the `CommandResult` produced by the state machine and the
+    /// index of the entry, or with `ServerError::TermChanged` if a new term
+    /// began before the entry committed, or `ServerError::SteppedDown` if this
+    /// node stepped down within the entry's term. `ServerError::OutcomeUnknown` means
+    /// the result could not be collected; only a retry in a session can tell
+    /// whether the command was applied. With a client session (client ID
+    /// and sequence number) a retried command is answered from the session
+    /// table instead of being applied twice.
+    pub async fn submit(&self, command: Command, session: Option<(String, u64)>) -> CommandReply {
+        let (response_tx, response_rx) = oneshot::channel();
+        self.submit_tx
+            .try_send(Submission { command, session, response_tx })
+            .map_err(queue_error)?;
+
+        response_rx.await.map_err(|_| ServerError::Unavailable)?
+    }
+
+    /// Serve a read-only command at the given consistency, reporting how it was served
+    ///
+    /// Without a consistency level the server's `ReadMode` decides. Linearizable
+    /// reads go through the log only in `ReadMode::Log`; otherwise nothing is
+    /// appended, and a new leader holds them until its election NoOp commits.
+    /// Stale and bounded reads are answered by this node, leader or not.
+    pub async fn read(&self, command: Command, consistency: Option<ReadConsistency>) -> ReadReply {
+        let consistency = consistency.unwrap_or(match self.read_mode {
+            ReadMode::Lease => ReadConsistency::Lease,
+            _ => ReadConsistency::Linearizable,
+        });
+        if consistency == ReadConsistency::Linearizable && self.read_mode == ReadMode::Log {
+            return self.read_through_log(command).await;
+        }
+
+        let (response_tx, response_rx) = oneshot::channel();
+        let read = Read {
+            command: command.clone(),
+            consistency,
+            response_tx,
+        };
+        self.read_tx.try_send(read).map_err(queue_error)?;
+
+        response_rx.await.map_err(|_| ServerError::Unavailable)?
+    }
+
+    /// Serve a read by appending it to the log like a write
+    async fn read_through_log(&self, command: Command) -> ReadReply {
+        let (result, applied_index) = self.submit(command, None).await?;
+        Ok(ReadOutcome { result, mode: ReadMode::Log, applied_index })
+    }
+}
+
+/// Turn a failed hand-off to the apply loop into the error the client sees
+fn queue_error<T>(error: TrySendError<T>) -> ServerError {
+    match error {
+        TrySendError::Full(_) => ServerError::Overloaded("apply queue is full".to_string()),
+        TrySendError::Closed(_) => ServerError::Unavailable,
+    }
+}
+
+/// Apply loop that feeds committed log entries into the state machine
+///
+/// One runs on every node, leader or follower, so each replica's state machine
+/// follows the committed log. On the leader it also owns the commit
+/// notifications: every submitted command is parked on its log index and
+/// answered only once that entry has been applied. Reads wait on their read
+/// index the same way and are then answered from the state machine; stale
+/// and bounded reads are answered right away. It also restores the state
+/// machine from installed snapshots and compacts the log once
+/// `snapshot_threshold` entries have been applied since the last snapshot.
+///
+/// Client sessions are kept next to the state machine and saved with it in
+/// snapshots. The leader expires sessions idle for `session_timeout` through
+/// the log.
+///
+/// The node is reached only through its `RaftHandle`. Since nothing else
+/// applies entries, the loop keeps its own copy of `last_applied` and of the
+/// index of the latest snapshot.
+pub struct Applier {
+    raft: RaftHandle,
+    state_machine: Arc<RwLock<dyn StateMachine>>,
+    status_rx: watch::Receiver<NodeStatus>,
+    metrics: Arc<RaftMetrics>,
+    submit_rx: mpsc::Receiver<Submission>,
+    read_rx: mpsc::Receiver<Read>,
+    waiters: HashMap<LogIndex, Waiter>,
+    reads: Vec<(LogIndex, ReadMode, Read)>,
+    last_applied: LogIndex,
+    snapshot_index: LogIndex,
+    snapshot_threshold: u64,
+    sessions: SessionTable,
+    /// When each session was last used, as seen by this node
+    session_activity: HashMap<String, Instant>,
+    session_timeout: Duration,
+    /// Submissions handed to the event loop that have not been parked yet
+    submitting: usize,
+    /// Results of entries applied while a submission was still on its way
+    /// back from the event loop, kept for a submitter that parks too late
+    unclaimed: HashMap<LogIndex, (Term, Option<Result<CommandResult, ServerError>>)>,
+}
+
+impl Applier {
+    /// Create a new apply loop and the handle used to submit commands to it
+    ///
+    /// The handle queues at most `queue_capacity` writes and as many reads;
+    /// beyond that clients are turned away with `ServerError::Overloaded`.
+    pub fn new(
+        raft: RaftHandle,
+        state_machine: Arc<RwLock<dyn StateMachine>>,
+        snapshot_threshold: u64,
+        read_mode: ReadMode,
+        session_timeout: Duration,
+        queue_capacity: usize,
+        metrics: Arc<RaftMetrics>,
+    ) -> (Self, ApplierHandle) {
+        let (submit_tx, submit_rx) = mpsc::channel(queue_capacity.max(1));
+        let (read_tx, read_rx) = mpsc::channel(queue_capacity.max(1));
+
+        let applier = Self {
+            status_rx: raft.subscribe(),
+            metrics,
+            raft,
+            state_machine,
+            submit_rx,
+            read_rx,
+            waiters: HashMap::new(),
+            reads: Vec::new(),
+            last_applied: 0,
+            snapshot_index: 0,
+            snapshot_threshold,
+            sessions: SessionTable::new(),
+            session_activity: HashMap::new(),
+            session_timeout,
+            submitting: 0,
+            unclaimed: HashMap::new(),
+        };
+
+        (applier, ApplierHandle { submit_tx, read_tx, read_mode })
+    }
+
+    /// Run the apply loop until the event loop or all handles go away
+    pub async fn run(mut self) {
+        let mut pending = FuturesUnordered::new();
+        let mut pending_reads = FuturesUnordered::new();
+        let mut expiry_timer = tokio::time::interval((self.session_timeout / 4).max(Duration::from_millis(1)));
+
+        loop {
+            tokio::select! {
+                submission = self.submit_rx.recv() => {
+                    match submission {
+                        Some(submission) => {
+                            if let Some(future) = self.submit(submission) {
+                                pending.push(future);
+                            }
+                        }
+                        None => break,
+                    }
+                }
+
+                Some(submitted) = pending.next(), if !pending.is_empty() => {
+                    self.park(submitted);
+                }
+
+                read = self.read_rx.recv() => {
+                    match read {
+                        Some(read) => match read.consistency {
+                            ReadConsistency::Stale | ReadConsistency::Bounded(_) => {
+                                self.serve_local_read(read).await;
+                            }
+                            ReadConsistency::Linearizable | ReadConsistency::Lease => {
+                                pending_reads.push(self.read_index(read));
+                            }
+                        },
+                        None => break,
+                    }
+                }
+
+                Some(read_indexed) = pending_reads.next(), if !pending_reads.is_empty() => {
+                    self.park_read(read_indexed).await;
+                }
+
+                _ = expiry_timer.tick() => {
+                    self.expire_idle_sessions();
+                }
+
+                changed = self.status_rx.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+
+                    // Park every command the event loop has already answered before
+                    // applying, so no committed entry can overtake its own waiter
+                    while let Some(Some(submitted)) = pending.next().now_or_never() {
+                        self.park(submitted);
+                    }
+
+                    self.apply_committed().await;
+                    self.serve_reads().await;
+                    self.fail_stale_waiters();
+                }
+            }
+
+            self.metrics.event_queue_depth.set(self.raft.queue_depth() as f64);
+        }
+
+        debug!("Apply loop stopped");
+    }
+
+    /// Hand a command to the event loop, returning a future for its log position
+    pub(crate) fn submit(
+        &mut self,
+        submission: Submission,
+    ) -> Option<impl Future<Output = Submitted>> {
+        let Submission { command, session, response_tx } = submission;
+        let (client_id, sequence_number) = session.unzip();
+
+        let command = match serde_json::to_vec(&command) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                let _ = response_tx.send(Err(e.into()));
+                return None;
+            }
+        };
+
+        let raft = self.raft.clone();
+        self.submitting += 1;
+        Some(async move { (raft.submit_command(command, client_id, sequence_number).await, response_tx) })
+    }
+
+    /// Ask the event loop for a read index, returning a future for the answer
+    fn read_index(&self, read: Read) -> impl Future<Output = ReadIndexed> {
+        let raft = self.raft.clone();
+        let allow_lease = read.consistency == ReadConsistency::Lease;
+        async move { (raft.read_index(allow_lease).await, read) }
+    }
+
+    /// Park a read on its read index, or serve it at once if that is already applied
+    async fn park_read(&mut self, (result, read): ReadIndexed) {
+        match result {
+            Ok((index, mode)) => {
+                if index <= self.last_applied {
+                    self.serve_read(mode, read).await;
+                } else {
+                    self.reads.push((index, mode, read));
+                }
+            }
+            Err(e) => {
+                let _ = read.response_tx.send(Err(e.into()));
+            }
+        }
+    }
+
+    /// Serve every parked read whose read index has been applied
+    async fn serve_reads(&mut self) {
+        let last_applied = self.last_applied;
+        let (ready, waiting) = std::mem::take(&mut self.reads)
+            .into_iter()
+            .partition(|(index, _, _)| *index <= last_applied);
+        self.reads = waiting;
+
+        for (_, mode, read) in ready {
+            self.serve_read(mode, read).await;
+        }
+    }
+
+    /// Serve a stale or bounded read from this node, unless it lags too far behind
+    pub(crate) async fn serve_local_read(&self, read: Read) {
+        let refusal = match read.consistency {
+            ReadConsistency::Bounded(MaxLag::Entries(max_lag)) => {
+                self.raft.status().await.map(|status| {
+                    let lag = status.commit_index.saturating_sub(self.last_applied);
+                    (lag > max_lag).then(|| format!(
+                        "{} committed entries not applied yet, more than {}", lag, max_lag
+                    ))
+                })
+            }
+            ReadConsistency::Bounded(MaxLag::Millis(max_lag)) => {
+                self.raft.leader_contact().await.map(|contact| match contact {
+                    Some(contact) if contact.as_millis() <= max_lag as u128 => None,
+                    Some(contact) => Some(format!(
+                        "last heard from the leader {} ms ago, more than {} ms", contact.as_millis(), max_lag
+                    )),
+                    None => Some("no leader known".to_string()),
+                })
+            }
+            _ => Ok(None),
+        };
+
+        match refusal {
+            Ok(Some(reason)) => {
+                let _ = read.response_tx.send(Err(ServerError::StaleRead(reason)));
+            }
+            Ok(None) => self.serve_read(ReadMode::Local, read).await,
+            Err(e) => {
+                let _ = read.response_tx.send(Err(e.into()));
+            }
+        }
+    }
+
+    /// Answer a read from the state machine without going through the log
+    async fn serve_read(&self, mode: ReadMode, read: Read) {
+        // Only this loop applies entries, so nothing moves between these two steps
+        let applied_index = self.last_applied;
+        let result = self.state_machine.read().await.query(read.command).await;
+        let reply = result
+            .map(|result| ReadOutcome { result, mode, applied_index })
+            .map_err(ServerError::from);
+        let _ = read.response_tx.send(reply);
+    }
+
+    /// Park a submitted command on its log index until it is applied
+    ///
+    /// The entry may already have been applied by a pass that fetched it
+    /// before this answer came back; the submitter then gets the result kept
+    /// for it, since the entry will not be applied again.
+    pub(crate) fn park(&mut self, (result, response_tx): Submitted) {
+        self.submitting = self.submitting.saturating_sub(1);
+        match result {
+            Ok((index, term)) if index <= self.last_applied => {
+                let reply = match self.unclaimed.remove(&index) {
+                    Some((entry_term, Some(result))) if entry_term == term => result.map(|result| (result, index)),
+                    _ => Err(ServerError::OutcomeUnknown { index }),
+                };
+                let _ = response_tx.send(reply);
+            }
+            Ok((index, term)) => {
+                let current_term = self.status_rx.borrow().current_term;
+                if term < current_term {
+                    let _ = response_tx.send(Err(ServerError::TermChanged { index }));
+                } else {
+                    self.waiters.insert(index, Waiter { term, response_tx });
+                }
+            }
+            Err(e) => {
+                let _ = response_tx.send(Err(e.into()));
+            }
+        }
+        if self.submitting == 0 {
+            self.unclaimed.clear();
+        }
+    }
+
+    /// Apply every committed entry that has not been applied yet, in index order
+    pub(crate) async fn apply_committed(&mut self) {
+        self.restore_snapshot().await;
+
+        let entries = match self.raft.entries_to_apply().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Failed to get committed entries: {}", e);
+                return;
+            }
+        };
+        let Some(last_index) = entries.last().map(|entry| entry.index) else {
+            return;
+        };
+
+        for entry in entries {
+            let result = self.apply_entry(&entry).await;
+            self.last_applied = entry.index;
+
+            if let Some(waiter) = self.waiters.remove(&entry.index) {
+                let reply = match result {
+                    Some(result) if waiter.term == entry.term => result.map(|result| (result, entry.index)),
+                    _ => Err(ServerError::TermChanged { index: entry.index }),
+                };
+                let _ = waiter.response_tx.send(reply);
+            } else if self.submitting > 0 {
+                self.unclaimed.insert(entry.index, (entry.term, result));
+            }
+        }
+
+        if let Err(e) = self.raft.set_last_applied(last_index).await {
+            warn!("Failed to record applied index {}: {}", last_index, e);
+        }
+        self.compact_log().await;
+    }
+
+    /// Restore the state machine from the node's snapshot if it is behind it
+    async fn restore_snapshot(&mut self) {
+        let snapshot = match self.raft.snapshot_to_restore().await {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!("Failed to get snapshot to restore: {}", e);
+                return;
+            }
+        };
+        let Some(snapshot) = snapshot else {
+            return;
+        };
+
+        let index = snapshot.last_included_index;
+        let (sessions, data) = match decode_snapshot(&snapshot.data) {
+            Ok(decoded) => decoded,
+            Err(e) => {
+                warn!("Failed to decode snapshot at index {}: {}", index, e);
+                return;
+            }
+        };
+
+        match self.state_machine.write().await.restore(data).await {
+            Ok(()) => {
+                self.session_activity.clear();
+                self.sessions = sessions;
+                self.last_applied = index;
+                self.snapshot_index = index;
+                if let Err(e) = self.raft.set_last_applied(index).await {
+                    warn!("Failed to record applied index {}: {}", index, e);
+                }
+                info!("Restored state machine from snapshot at index {}", index);
+            }
+            Err(e) => warn!("Failed to restore snapshot at index {}: {}", index, e),
+        }
+    }
+
+    /// Snapshot the state machine and compact the log once enough entries are applied
+    async fn compact_log(&mut self) {
+        let last_applied = self.last_applied;
+        if last_applied.saturating_sub(self.snapshot_index) < self.snapshot_threshold {
+            return;
+        }
+
+        // Nothing else applies entries, so the snapshot reflects exactly `last_applied`
+        let data = match self.state_machine.read().await.snapshot().await {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Failed to snapshot state machine at index {}: {}", last_applied, e);
+                return;
+            }
+        };
+
+        let data = match encode_snapshot(&self.sessions, data) {
+            Ok(data) => data,
+            Err(e) => {
+                warn!("Failed to encode snapshot at index {}: {}", last_applied, e);
+                return;
+            }
+        };
+
+        match self.raft.compact(last_applied, data).await {
+            Ok(()) => self.snapshot_index = last_applied,
+            Err(e) => warn!("Failed to compact log at index {}: {}", last_applied, e),
+        }
+    }
+
+    /// Apply a committed entry to the state machine
+    ///
+    /// Returns `None` for entries that carry no state machine command. An entry
+    /// that fails to decode is reported and skipped rather than stalling the loop,
+    /// since every replica would fail on it the same way. Session commands only
+    /// touch the session table, and commands from a session are checked against
+    /// it so a retry gets the cached result.
+    async fn apply_entry(&mut self, entry: &LogEntry) -> Option<Result<CommandResult, ServerError>> {
+        match entry.entry_type {
+            EntryType::Command => {}
+            EntryType::NoOp | EntryType::Configuration => {
+                debug!("Skipping {:?} entry at index {}", entry.entry_type, entry.index);
+                return None;
+            }
+        }
+
+        let command: Command = match serde_json::from_slice(&entry.data) {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("Failed to decode command at index {}: {}", entry.index, e);
+                return Some(Err(e.into()));
+            }
+        };
+
+        match command {
+            Command::RegisterSession => {
+                let client_id = entry.index.to_string();
+                self.sessions.register(client_id.clone());
+                self.session_activity.insert(client_id.clone(), Instant::now());
+                return Some(Ok(CommandResult::Success { value: Some(client_id) }));
+            }
+            Command::ExpireSessions { client_ids } => {
+                debug!("Expiring {} idle sessions", client_ids.len());
+                self.sessions.expire(&client_ids);
+                for client_id in &client_ids {
+                    self.session_activity.remove(client_id);
+                }
+                return Some(Ok(CommandResult::Success { value: None }));
+            }
+            _ => {}
+        }
+
+        let session = entry.client_id.clone().zip(entry.sequence_number);
+        if let Some((client_id, sequence_number)) = &session {
+            match self.sessions.check(client_id, *sequence_number) {
+                SessionCheck::Apply => {}
+                SessionCheck::Duplicate(result) => {
+                    debug!("Command {} of session {} already applied", sequence_number, client_id);
+                    return Some(Ok(result));
+                }
+                SessionCheck::Rejected(message) => return Some(Ok(CommandResult::Error { message })),
+            }
+            self.session_activity.insert(client_id.clone(), Instant::now());
+        }
+
+        let result = self.state_machine.write().await.apply(command).await;
+        if let (Some((client_id, sequence_number)), Ok(result)) = (&session, &result) {
+            self.sessions.record(client_id, *sequence_number, result.clone());
+        }
+        Some(result.map_err(ServerError::from))
+    }
+
+    /// Propose expiring the sessions that have been idle for `session_timeout` (leaders only)
+    ///
+    /// Idleness is tracked locally; sessions a new leader has not seen used yet
+    /// start their clock when it first looks.
+    fn expire_idle_sessions(&mut self) {
+        if self.status_rx.borrow().state != NodeState::Leader {
+            return;
+        }
+
+        let now = Instant::now();
+        for client_id in self.sessions.client_ids() {
+            self.session_activity.entry(client_id.clone()).or_insert(now);
+        }
+
+        let idle: Vec<String> = self.session_activity
+            .iter()
+            .filter(|(_, used)| now.duration_since(**used) >= self.session_timeout)
+            .map(|(client_id, _)| client_id.clone())
+            .collect();
+        if idle.is_empty() {
+            return;
+        }
+
+        // Forget them here so the next tick does not propose them again
+        for client_id in &idle {
+            self.session_activity.remove(client_id);
+        }
+
+        let command = match serde_json::to_vec(&Command::ExpireSessions { client_ids: idle }) {
+            Ok(command) => command,
+            Err(e) => {
+                warn!("Failed to encode session expiry: {}", e);
+                return;
+            }
+        };
+        let raft = self.raft.clone();
+        tokio::spawn(async move {
+            if let Err(e) = raft.submit_command(command, None, None).await {
+                debug!("Session expiry not proposed: {}", e);
+            }
+        });
+    }
+
+    /// Fail waiters whose entries may never commit: those of an earlier term,
+    /// and all of them once this node no longer leads
+    pub(crate) fn fail_stale_waiters(&mut self) {
+        let (state, current_term) = {
+            let status = self.status_rx.borrow();
+            (status.state, status.current_term)
+        };
+
+        let stale: Vec<LogIndex> = self.waiters
+            .iter()
+            .filter(|(_, waiter)| state != NodeState::Leader || waiter.term < current_term)
+            .map(|(index, _)| *index)
+            .collect();
+
+        for index in stale {
+            if let Some(waiter) = self.waiters.remove(&index) {
+                let error = if waiter.term < current_term {
+                    warn!("Term changed before log index {} committed", index);
+                    ServerError::TermChanged { index }
+                } else {
+                    warn!("Stepped down before log index {} committed", index);
+                    ServerError::SteppedDown { index }
+                };
+                let _ = waiter.response_tx.send(Err(error));
+            }
+        }
+    }
+}
+
+/// Frame the session table and the state machine snapshot into one snapshot
+///
+/// Layout: `[sessions_len u64 LE][sessions json][state machine snapshot]`.
+pub(crate) fn encode_snapshot(sessions: &SessionTable, state: Vec<u8>) -> Result<Vec<u8>, ServerError> {
+    let sessions = serde_json::to_vec(sessions)?;
+    let mut data = Vec::with_capacity(8 + sessions.len() + state.len());
+    data.extend_from_slice(&(sessions.len() as u64).to_le_bytes());
+    data.extend_from_slice(&sessions);
+    data.extend_from_slice(&state);
+    Ok(data)
+}
+
+/// Split a snapshot written by `encode_snapshot`
+pub(crate) fn decode_snapshot(data: &[u8]) -> Result<(SessionTable, Vec<u8>), ServerError> {
+    let corrupt = || ServerError::Configuration("snapshot is truncated".to_string());
+    let (len, rest) = data.split_first_chunk::<8>().ok_or_else(corrupt)?;
+    let len = usize::try_from(u64::from_le_bytes(*len)).map_err(|_| corrupt())?;
+    if rest.len() < len {
+        return Err(corrupt());
+    }
+
+    let (sessions, state) = rest.split_at(len);
+    Ok((serde_json::from_slice(sessions)?, state.to_vec()))
+}
@@ -1,4 +1,51 @@
 use serde::{Deserialize, Serialize};
+use raft_core::{PeerInfo, ReadMode};
+use std::path::PathBuf;
+
+/// Overwrite `value` with the environment variable `name`, if it is set
+fn env_parse<T: std::str::FromStr>(name: &str, value: &mut T) -> Result<(), String> {
+    if let Ok(raw) = std::env::var(name) {
+        *value = raw.parse().map_err(|_| format!("Invalid {}: {}", name, raw))?;
+    }
+    Ok(())
+}
+
+/// Overwrite an enum `value` with the environment variable `name`, given by
+/// its serde name such as `read_index`, if it is set
+fn env_enum<T: serde::de::DeserializeOwned>(name: &str, value: &mut T) -> Result<(), String> {
+    if let Ok(raw) = std::env::var(name) {
+        *value = serde_json::from_value(serde_json::Value::String(raw.clone()))
+            .map_err(|_| format!("Invalid {}: {}", name, raw))?;
+    }
+    Ok(())
+}
+
+/// Check whether a host is a wildcard that listens on every interface
+fn is_wildcard(host: &str) -> bool {
+    matches!(host.trim_matches(|c| c == '[' || c == ']'), "" | "0.0.0.0" | "::")
+}
+
+/// How a follower answers client requests that only the leader can serve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FollowerMode {
+    /// Forward the request to the leader and relay its response
+    Proxy,
+    /// Answer 307 Temporary Redirect to the same path on the leader
+    Redirect,
+    /// Answer 421 Misdirected Request naming the leader
+    Reject,
+}
+
+/// How this node sends Raft RPCs to its peers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerTransport {
+    /// JSON or binary bodies posted to the peer's HTTP server
+    Http,
+    /// The peer's gRPC `RaftService`, dialled at its `grpc_address`
+    Grpc,
+}
 
 /// Configuration for the Raft server
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -6,14 +53,24 @@ pub struct ServerConfig {
     /// Unique node identifier
     pub node_id: String,
     
-    /// Address to bind the gRPC server to
+    /// Address to bind the HTTP and gRPC servers to
     pub bind_address: String,
     
-    /// Port for the gRPC server
+    /// Address other nodes and clients reach this node's HTTP server at, as
+    /// recorded in the cluster configuration; defaults to the bind address
+    pub advertise_address: Option<String>,
+    
+    /// Port for the HTTP server
     pub port: u16,
     
-    /// List of peer addresses
-    pub peers: Vec<String>,
+    /// Port for the gRPC `RaftService`
+    pub grpc_port: u16,
+    
+    /// The other voters the cluster starts with, by node ID and address
+    pub peers: Vec<PeerInfo>,
+    
+    /// How Raft RPCs reach the peers
+    pub peer_transport: PeerTransport,
     
     /// Minimum election timeout in milliseconds
     pub election_timeout_min: u64,
@@ -27,11 +84,51 @@ pub struct ServerConfig {
     /// Maximum number of log entries per append request
     pub max_append_entries: usize,
     
+    /// Append requests in flight to one follower before waiting for its answers
+    pub max_inflight_appends: usize,
+    
+    /// Entries the leader may hold past its commit index before rejecting writes
+    pub max_uncommitted_entries: usize,
+    
+    /// Bytes of command data the leader may hold past its commit index
+    pub max_uncommitted_bytes: usize,
+    
+    /// Events the Raft event queue holds before client requests are rejected
+    pub event_queue_capacity: usize,
+    
+    /// Number of applied entries after which the log is compacted into a snapshot
+    pub snapshot_threshold: u64,
+    
+    /// Maximum size in bytes of one InstallSnapshot chunk
+    pub snapshot_chunk_size: usize,
+    
+    /// How many entries a learner may trail the leader by and still be promoted
+    pub max_learner_lag: u64,
+    
+    /// Run a pre-vote round before each election
+    pub pre_vote: bool,
+    
+    /// How the leader confirms reads that do not ask for a consistency level:
+    /// through the log, by read index or by lease
+    pub read_mode: ReadMode,
+    
+    /// Clock drift allowance in milliseconds subtracted from the leader lease
+    pub clock_drift: u64,
+    
+    /// How a follower answers client requests that only the leader can serve
+    pub follower_mode: FollowerMode,
+    
+    /// Milliseconds a client session may sit idle before the leader expires it
+    pub session_timeout: u64,
+    
     /// Enable metrics endpoint
     pub enable_metrics: bool,
     
     /// Metrics server port
     pub metrics_port: u16,
+    
+    /// Directory under which each node keeps its Raft log and hard state
+    pub data_dir: String,
 }
 
 impl Default for ServerConfig {
@@ -39,29 +136,123 @@ impl Default for ServerConfig {
         Self {
             node_id: "node-1".to_string(),
             bind_address: "0.0.0.0".to_string(),
+            advertise_address: None,
             port: 50051,
+            grpc_port: 50052,
             peers: Vec::new(),
+            peer_transport: PeerTransport::Http,
             election_timeout_min: 150,
             election_timeout_max: 300,
             heartbeat_interval: 50,
             max_append_entries: 100,
+            max_inflight_appends: 4,
+            max_uncommitted_entries: 10_000,
+            max_uncommitted_bytes: 64 * 1024 * 1024,
+            event_queue_capacity: 1024,
+            snapshot_threshold: 10_000,
+            snapshot_chunk_size: 64 * 1024,
+            max_learner_lag: 100,
+            pre_vote: false,
+            read_mode: ReadMode::ReadIndex,
+            clock_drift: 10,
+            follower_mode: FollowerMode::Proxy,
+            session_timeout: 60 * 60 * 1000,
             enable_metrics: true,
             metrics_port: 8080,
+            data_dir: "data".to_string(),
         }
     }
 }
 
 impl ServerConfig {
+    /// Build a configuration from the defaults overridden by `RAFT_*` environment variables
+    ///
+    /// `RAFT_PEERS` lists voters as `node-2=host:8081,node-3=host:8082`. An entry
+    /// for this node itself is dropped, so every node can share one peer list.
+    pub fn from_env() -> Result<Self, String> {
+        let mut config = Self::default();
+
+        if let Ok(node_id) = std::env::var("RAFT_NODE_ID") {
+            config.node_id = node_id;
+        }
+        if let Ok(bind_address) = std::env::var("RAFT_BIND_ADDRESS") {
+            config.bind_address = bind_address;
+        }
+        if let Ok(advertise_address) = std::env::var("RAFT_ADVERTISE_ADDRESS") {
+            config.advertise_address = Some(advertise_address);
+        }
+        if let Ok(data_dir) = std::env::var("RAFT_DATA_DIR") {
+            config.data_dir = data_dir;
+        }
+        if let Ok(peers) = std::env::var("RAFT_PEERS") {
+            config.peers = PeerInfo::parse_list(&peers).map_err(|e| e.to_string())?;
+            config.peers.retain(|peer| peer.node_id != config.node_id);
+        }
+        env_enum("RAFT_PEER_TRANSPORT", &mut config.peer_transport)?;
+        env_parse("RAFT_PORT", &mut config.port)?;
+        env_parse("RAFT_GRPC_PORT", &mut config.grpc_port)?;
+        env_parse("RAFT_METRICS_PORT", &mut config.metrics_port)?;
+        env_parse("RAFT_ELECTION_TIMEOUT_MIN", &mut config.election_timeout_min)?;
+        env_parse("RAFT_ELECTION_TIMEOUT_MAX", &mut config.election_timeout_max)?;
+        env_parse("RAFT_HEARTBEAT_INTERVAL", &mut config.heartbeat_interval)?;
+        env_parse("RAFT_MAX_APPEND_ENTRIES", &mut config.max_append_entries)?;
+        env_parse("RAFT_MAX_INFLIGHT_APPENDS", &mut config.max_inflight_appends)?;
+        env_parse("RAFT_MAX_UNCOMMITTED_ENTRIES", &mut config.max_uncommitted_entries)?;
+        env_parse("RAFT_MAX_UNCOMMITTED_BYTES", &mut config.max_uncommitted_bytes)?;
+        env_parse("RAFT_EVENT_QUEUE_CAPACITY", &mut config.event_queue_capacity)?;
+        env_parse("RAFT_SNAPSHOT_THRESHOLD", &mut config.snapshot_threshold)?;
+        env_parse("RAFT_SNAPSHOT_CHUNK_SIZE", &mut config.snapshot_chunk_size)?;
+        env_parse("RAFT_MAX_LEARNER_LAG", &mut config.max_learner_lag)?;
+        env_parse("RAFT_PRE_VOTE", &mut config.pre_vote)?;
+        env_enum("RAFT_READ_MODE", &mut config.read_mode)?;
+        env_parse("RAFT_CLOCK_DRIFT", &mut config.clock_drift)?;
+        env_enum("RAFT_FOLLOWER_MODE", &mut config.follower_mode)?;
+        env_parse("RAFT_SESSION_TIMEOUT", &mut config.session_timeout)?;
+
+        Ok(config)
+    }
+
     /// Get the full server address
     pub fn server_address(&self) -> String {
         format!("{}:{}", self.bind_address, self.port)
     }
     
+    /// Get the address this node is known by in the cluster configuration
+    ///
+    /// A wildcard bind address such as `0.0.0.0` cannot be dialled from other
+    /// hosts, so a lone node bound to one advertises loopback instead; a node
+    /// with peers must set `advertise_address`, which `validate` checks.
+    pub fn advertise_address(&self) -> String {
+        match &self.advertise_address {
+            Some(address) => address.clone(),
+            None if is_wildcard(&self.bind_address) => format!("127.0.0.1:{}", self.port),
+            None => self.server_address(),
+        }
+    }
+    
+    /// Get the address this node's gRPC service is known by in the cluster
+    /// configuration: the advertised host with the gRPC port
+    pub fn advertise_grpc_address(&self) -> String {
+        let address = self.advertise_address();
+        let host = address.rsplit_once(':').map_or(address.as_str(), |(host, _)| host);
+        format!("{}:{}", host, self.grpc_port)
+    }
+    
+    /// Get the gRPC server address
+    pub fn grpc_address(&self) -> String {
+        format!("{}:{}", self.bind_address, self.grpc_port)
+    }
+    
     /// Get the metrics address
     pub fn metrics_address(&self) -> String {
         format!("{}:{}", self.bind_address, self.metrics_port)
     }
     
+    /// Get the directory holding this node's Raft storage
+    pub fn node_data_dir(&self) -> PathBuf {
+        PathBuf::from(&self.data_dir).join(&self.node_id)
+    }
+    
     /// Validate the configuration
     pub fn validate(&self) -> Result<(), String> {
         if self.node_id.is_empty() {
@@ -72,6 +263,36 @@ impl ServerConfig {
             return Err("Port must be greater than 0".to_string());
         }
         
+        if self.grpc_port == 0 || self.grpc_port == self.port {
+            return Err("gRPC port must be greater than 0 and differ from the HTTP port".to_string());
+        }
+        
+        match &self.advertise_address {
+            Some(address) if address.rsplit_once(':').is_none_or(|(host, _)| is_wildcard(host)) => {
+                return Err(format!("Advertise address {} must be a reachable host:port", address));
+            }
+            None if is_wildcard(&self.bind_address) && !self.peers.is_empty() => {
+                return Err(format!(
+                    "An advertise address is needed when binding to {} with peers", self.bind_address
+                ));
+            }
+            _ => {}
+        }
+        
+        for (i, peer) in self.peers.iter().enumerate() {
+            if peer.node_id == self.node_id {
+                return Err(format!("Peer list contains this node ({})", peer.node_id));
+            }
+            if self.peers[..i].iter().any(|other| other.node_id == peer.node_id) {
+                return Err(format!("Peer {} is listed more than once", peer.node_id));
+            }
+            if self.peer_transport == PeerTransport::Grpc && peer.grpc_address.is_none() {
+                return Err(format!(
+                    "Peer {} needs a gRPC address (node_id=address|grpc_address) for the gRPC transport", peer.node_id
+                ));
+            }
+        }
+        
         if self.election_timeout_min >= self.election_timeout_max {
             return Err("Election timeout min must be less than max".to_string());
         }
@@ -80,6 +301,42 @@ impl ServerConfig {
             return Err("Heartbeat interval must be greater than 0".to_string());
         }
         
+        if self.max_append_entries == 0 || self.max_inflight_appends == 0 {
+            return Err("Max append entries and max in-flight appends must be greater than 0".to_string());
+        }
+        
+        if self.max_uncommitted_entries == 0 || self.max_uncommitted_bytes == 0 {
+            return Err("Max uncommitted entries and bytes must be greater than 0".to_string());
+        }
+        
+        if self.event_queue_capacity == 0 {
+            return Err("Event queue capacity must be greater than 0".to_string());
+        }
+        
+        if self.snapshot_threshold == 0 {
+            return Err("Snapshot threshold must be greater than 0".to_string());
+        }
+        
+        if self.snapshot_chunk_size == 0 {
+            return Err("Snapshot chunk size must be greater than 0".to_string());
+        }
+        
+        if self.read_mode == ReadMode::Local {
+            return Err("Read mode must be log, read_index or lease".to_string());
+        }
+        
+        if self.read_mode == ReadMode::Lease && self.clock_drift >= self.election_timeout_min {
+            return Err("Clock drift must be less than the minimum election timeout for lease reads".to_string());
+        }
+        
+        if self.session_timeout == 0 {
+            return Err("Session timeout must be greater than 0".to_string());
+        }
+        
+        if self.data_dir.is_empty() {
+            return Err("Data directory cannot be empty".to_string());
+        }
+        
         Ok(())
     }
 }
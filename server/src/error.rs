@@ -23,4 +23,30 @@ pub enum ServerError {
     
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
+    
+    #[error("Term changed before log index {index} committed, outcome unknown")]
+    TermChanged { index: u64 },
+    
+    #[error("Stepped down before log index {index} committed, outcome unknown")]
+    SteppedDown { index: u64 },
+    
+    #[error("Log index {index} was applied before its result was collected, outcome unknown")]
+    OutcomeUnknown { index: u64 },
+    
+    #[error("Stale read refused: {0}")]
+    StaleRead(String),
+    
+    #[error("Raft event loop unavailable")]
+    Unavailable,
+    
+    #[error("Overloaded: {0}")]
+    Overloaded(String),
+}
+
+impl ServerError {
+    /// Check whether the request was shed because the node is overloaded;
+    /// such requests are safe to retry after a backoff
+    pub fn is_overloaded(&self) -> bool {
+        matches!(self, ServerError::Overloaded(_) | ServerError::Raft(raft_core::RaftError::Overloaded(_)))
+    }
 }
@@ -0,0 +1,142 @@
+//! Answering leader-only requests that reach a follower
+//!
+//! Depending on the configured `FollowerMode`, a follower either proxies the
+//! request to the leader, redirects the client there, or refuses it with the
+//! leader's ID and address so the client can retry on its own.
+
+use std::time::Duration;
+use axum::{
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use tokio::sync::watch;
+use tracing::{debug, warn};
+
+use raft_core::{address_url, NodeId, NodeStatus};
+use crate::config::FollowerMode;
+
+/// Header set on proxied requests so they are never forwarded a second time
+pub const FORWARDED_HEADER: &str = "x-raft-forwarded";
+
+/// How long to wait for the leader to answer a proxied request
+const PROXY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Error body for requests this node cannot serve because it is not the leader
+#[derive(Debug, Serialize)]
+pub struct NotLeaderResponse {
+    pub success: bool,
+    pub error: String,
+    pub leader_id: Option<NodeId>,
+    pub leader_address: Option<String>,
+}
+
+/// Sends requests that need the leader on to it, as configured by `FollowerMode`
+#[derive(Clone)]
+pub struct LeaderForwarder {
+    mode: FollowerMode,
+    status_rx: watch::Receiver<NodeStatus>,
+    client: reqwest::Client,
+}
+
+impl LeaderForwarder {
+    /// Create a forwarder that learns the leader from the event loop's status updates
+    pub fn new(mode: FollowerMode, status_rx: watch::Receiver<NodeStatus>) -> Self {
+        Self {
+            mode,
+            status_rx,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Answer a request that failed because this node is not the leader
+    ///
+    /// `path` and `body` describe the original POST so it can be replayed on the
+    /// leader. A request that was already proxied once is refused with 421
+    /// instead, so nodes that disagree about the leader cannot bounce it around.
+    pub async fn not_leader(&self, path: &str, body: Option<serde_json::Value>, headers: &HeaderMap) -> Response {
+        let (leader_id, leader_address) = {
+            let status = self.status_rx.borrow();
+            (status.leader_id.clone(), status.leader_address.clone())
+        };
+
+        let Some(address) = leader_address else {
+            let error = match &leader_id {
+                Some(leader) => format!("Not the leader; no address known for leader {}", leader),
+                None => "Not the leader and no leader is known".to_string(),
+            };
+            return refuse(StatusCode::SERVICE_UNAVAILABLE, error, leader_id, None);
+        };
+
+        let url = format!("{}{}", address_url(&address), path);
+        let forwarded = headers.contains_key(FORWARDED_HEADER);
+        match self.mode {
+            FollowerMode::Proxy if !forwarded => self.proxy(&url, body, leader_id, address).await,
+            FollowerMode::Redirect if !forwarded => {
+                let mut response = refuse(StatusCode::TEMPORARY_REDIRECT, "Not the leader".to_string(), leader_id, Some(address));
+                if let Ok(location) = HeaderValue::from_str(&url) {
+                    response.headers_mut().insert(header::LOCATION, location);
+                }
+                response
+            }
+            _ => refuse(StatusCode::MISDIRECTED_REQUEST, "Not the leader".to_string(), leader_id, Some(address)),
+        }
+    }
+
+    /// Replay the request on the leader and relay its status and body
+    async fn proxy(
+        &self,
+        url: &str,
+        body: Option<serde_json::Value>,
+        leader_id: Option<NodeId>,
+        address: String,
+    ) -> Response {
+        debug!("Proxying request to leader at {}", url);
+        let mut request = self.client
+            .post(url)
+            .header(FORWARDED_HEADER, "1")
+            .timeout(PROXY_TIMEOUT);
+        if let Some(body) = &body {
+            request = request.json(body);
+        }
+
+        let response = match request.send().await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to proxy request to leader at {}: {}", url, e);
+                let error = format!("Failed to reach the leader: {}", e);
+                return refuse(StatusCode::BAD_GATEWAY, error, leader_id, Some(address));
+            }
+        };
+
+        let status = StatusCode::from_u16(response.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| HeaderValue::from_bytes(value.as_bytes()).ok());
+        match response.bytes().await {
+            Ok(bytes) => {
+                let mut response = (status, bytes).into_response();
+                if let Some(content_type) = content_type {
+                    response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+                }
+                response
+            }
+            Err(e) => {
+                let error = format!("Failed to read the leader's response: {}", e);
+                refuse(StatusCode::BAD_GATEWAY, error, leader_id, Some(address))
+            }
+        }
+    }
+}
+
+/// Build a `NotLeaderResponse` with the given status
+fn refuse(status: StatusCode, error: String, leader_id: Option<NodeId>, leader_address: Option<String>) -> Response {
+    let body = NotLeaderResponse {
+        success: false,
+        error,
+        leader_id,
+        leader_address,
+    };
+    (status, Json(body)).into_response()
+}
@@ -5,9 +5,19 @@
 //! This module provides the HTTP server that handles client requests
 //! and provides metrics and status endpoints.
 
+pub mod applier;
 pub mod metrics;
 pub mod config;
 pub mod error;
+pub mod forward;
+pub mod grpc_server;
+pub mod raft_api;
 
-pub use config::ServerConfig;
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests;
+
+pub use applier::{Applier, ApplierHandle, MaxLag, ReadConsistency, ReadOutcome};
+pub use config::{FollowerMode, PeerTransport, ServerConfig};
 pub use error::ServerError;
+pub use forward::LeaderForwarder;
@@ -1,36 +1,83 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use tracing::{info, error};
-use tracing_subscriber;
 use axum::{
     routing::{get, post},
     Router,
-    extract::{State, Json},
-    response::Json as ResponseJson,
+    extract::{Path, State, Json},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Json as ResponseJson, Response},
 };
 use serde::{Deserialize, Serialize};
 
-use server::{ServerConfig, metrics::RaftMetrics};
-use raft_core::{RaftNode, RaftEventLoop, RaftEvent, NodeConfig, NodeStatus};
-use state::{StateMachine, InMemoryKvStore, state_machine::Command};
+use server::grpc_server::RaftGrpcServer;
+use server::{Applier, ApplierHandle, LeaderForwarder, MaxLag, PeerTransport, ReadConsistency, ServerConfig, ServerError, metrics::RaftMetrics};
+use raft_core::{RaftNode, RaftEventLoop, RaftHandle, RaftError, NodeConfig, NodeStatus, FileStorage, GrpcTransport, HttpTransport, LogIndex, PeerInfo, ReadMode, Transport};
+use state::{StateMachine, InMemoryKvStore, state_machine::{Command, CommandResult}};
 
 /// Application state shared across handlers
 #[derive(Clone)]
 struct AppState {
-    event_tx: mpsc::UnboundedSender<RaftEvent>,
+    raft: RaftHandle,
     metrics: Arc<RaftMetrics>,
-    state_machine: Arc<RwLock<dyn StateMachine>>,
+    applier: ApplierHandle,
+    forwarder: LeaderForwarder,
 }
 
 /// Command request from clients
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct CommandRequest {
     #[serde(rename = "type")]
     command_type: String,
     key: String,
     value: Option<String>,
+    /// Read consistency for GET: `linearizable`, `lease`, `stale` or `bounded`
+    consistency: Option<String>,
+    /// Bounded reads: maximum number of committed entries not yet applied
+    max_lag_entries: Option<u64>,
+    /// Bounded reads: maximum milliseconds since the node heard from a leader
+    max_lag_ms: Option<u64>,
+    /// Session registered through `/session`; writes carrying it are applied once
+    client_id: Option<String>,
+    /// Per-session sequence number, starting at 1
+    sequence_number: Option<u64>,
+}
+
+impl CommandRequest {
+    /// Parse the requested read consistency, if any
+    fn read_consistency(&self) -> Result<Option<ReadConsistency>, String> {
+        let Some(consistency) = self.consistency.as_deref() else {
+            return Ok(None);
+        };
+
+        let consistency = match consistency {
+            "linearizable" => ReadConsistency::Linearizable,
+            "lease" => ReadConsistency::Lease,
+            "stale" => ReadConsistency::Stale,
+            "bounded" => match (self.max_lag_entries, self.max_lag_ms) {
+                (Some(entries), None) => ReadConsistency::Bounded(MaxLag::Entries(entries)),
+                (None, Some(ms)) => ReadConsistency::Bounded(MaxLag::Millis(ms)),
+                _ => return Err("bounded reads need exactly one of max_lag_entries or max_lag_ms".to_string()),
+            },
+            other => return Err(format!("Unknown consistency level: {}", other)),
+        };
+        Ok(Some(consistency))
+    }
+
+    /// The client session the command belongs to, if any
+    fn session(&self) -> Result<Option<(String, u64)>, String> {
+        match (&self.client_id, self.sequence_number) {
+            (Some(client_id), _) if client_id.is_empty() => Err("client_id cannot be empty".to_string()),
+            (_, Some(0)) => Err("sequence_number starts at 1".to_string()),
+            (Some(client_id), Some(sequence_number)) => Ok(Some((client_id.clone(), sequence_number))),
+            (None, None) => Ok(None),
+            _ => Err("client_id and sequence_number must be given together".to_string()),
+        }
+    }
 }
 
 /// Command response to clients
@@ -39,6 +86,59 @@ struct CommandResponse {
     success: bool,
     result: Option<String>,
     error: Option<String>,
+    /// How a read was served: `log`, `read_index`, `lease` or `local`
+    read_mode: Option<ReadMode>,
+    /// The applied index the command was answered at
+    last_applied: Option<LogIndex>,
+}
+
+impl CommandResponse {
+    fn error(error: String) -> Self {
+        Self {
+            success: false,
+            result: None,
+            error: Some(error),
+            read_mode: None,
+            last_applied: None,
+        }
+    }
+}
+
+/// Membership change request: the full set of nodes the cluster should have
+#[derive(Debug, Serialize, Deserialize)]
+struct MembershipRequest {
+    nodes: Vec<PeerInfo>,
+}
+
+/// Request to add a learner
+#[derive(Debug, Serialize, Deserialize)]
+struct LearnerRequest {
+    node_id: String,
+    address: String,
+    /// Address of the learner's gRPC `RaftService`, needed with the gRPC peer transport
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    grpc_address: Option<String>,
+}
+
+/// Request to hand leadership over to another voter
+#[derive(Debug, Serialize, Deserialize)]
+struct TransferLeaderRequest {
+    node_id: String,
+}
+
+/// Leadership transfer response
+#[derive(Debug, Serialize)]
+struct TransferLeaderResponse {
+    success: bool,
+    error: Option<String>,
+}
+
+/// Membership change response
+#[derive(Debug, Serialize)]
+struct MembershipResponse {
+    success: bool,
+    config_index: Option<LogIndex>,
+    error: Option<String>,
 }
 
 #[tokio::main]
@@ -46,52 +146,83 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize tracing
     tracing_subscriber::fmt::init();
 
-    // Load configuration (for now, use default)
-    let config = ServerConfig::default();
+    // Load configuration from the environment
+    let config = ServerConfig::from_env()?;
     config.validate().map_err(|e| format!("Invalid configuration: {}", e))?;
 
     info!("Starting Raft node: {}", config.node_id);
     info!("HTTP server address: {}", config.server_address());
+    info!("gRPC server address: {}", config.grpc_address());
 
     // Create Raft node
     let node_config = NodeConfig {
         node_id: config.node_id.clone(),
-        address: config.server_address(),
+        address: config.advertise_address(),
+        grpc_address: Some(config.advertise_grpc_address()),
         peers: config.peers.clone(),
         election_timeout_min: config.election_timeout_min,
         election_timeout_max: config.election_timeout_max,
         heartbeat_interval: config.heartbeat_interval,
+        max_append_entries: config.max_append_entries,
+        max_inflight_appends: config.max_inflight_appends,
+        snapshot_chunk_size: config.snapshot_chunk_size,
+        max_learner_lag: config.max_learner_lag,
+        pre_vote: config.pre_vote,
+        clock_drift: config.clock_drift,
+        max_uncommitted_entries: config.max_uncommitted_entries,
+        max_uncommitted_bytes: config.max_uncommitted_bytes,
+        event_queue_capacity: config.event_queue_capacity,
     };
 
-    let raft_node = Arc::new(RwLock::new(RaftNode::new(node_config)));
+    // Recover term, vote and log from disk before serving anything
+    let storage = FileStorage::open(config.node_data_dir())?;
+    let raft_node = RaftNode::with_storage(node_config, Box::new(storage))?;
     let state_machine: Arc<RwLock<dyn StateMachine>> = Arc::new(RwLock::new(InMemoryKvStore::new()));
     let metrics = Arc::new(RaftMetrics::new().map_err(|e| format!("Failed to create metrics: {}", e))?);
 
-    // Create event channel
-    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    // Start the Raft event loop, which owns the node, and create the apply loop that follows its commits
+    let (event_loop_handle, raft) = match config.peer_transport {
+        PeerTransport::Http => spawn_event_loop(raft_node, HttpTransport::new()),
+        PeerTransport::Grpc => spawn_event_loop(raft_node, GrpcTransport::new()),
+    };
+    let (applier, applier_handle) = Applier::new(
+        raft.clone(),
+        Arc::clone(&state_machine),
+        config.snapshot_threshold,
+        config.read_mode,
+        Duration::from_millis(config.session_timeout),
+        config.event_queue_capacity,
+        Arc::clone(&metrics),
+    );
+
+    // The gRPC service answers from the same event loop and apply loop
+    let grpc_server = RaftGrpcServer::new(raft.clone(), applier_handle.clone(), Arc::clone(&metrics));
 
     // Create application state
     let app_state = AppState {
-        event_tx: event_tx.clone(),
+        raft: raft.clone(),
         metrics: Arc::clone(&metrics),
-        state_machine: Arc::clone(&state_machine),
+        applier: applier_handle,
+        forwarder: LeaderForwarder::new(config.follower_mode, raft.subscribe()),
     };
-    
-    // Start Raft event loop
-    let event_loop = RaftEventLoop::new(Arc::clone(&raft_node), event_rx);
-    let event_loop_handle = tokio::spawn(async move {
-        if let Err(e) = event_loop.run().await {
-            error!("Raft event loop error: {}", e);
-        }
-    });
+
+
+    // Start apply loop
+    tokio::spawn(applier.run());
 
     // Create HTTP server
     let app = Router::new()
         .route("/command", post(handle_command))
+        .route("/session", post(handle_register_session))
         .route("/status", get(handle_status))
         .route("/metrics", get(handle_metrics))
         .route("/health", get(handle_health))
-        .with_state(app_state);
+        .route("/admin/membership", post(handle_membership))
+        .route("/admin/learners", post(handle_add_learner))
+        .route("/admin/learners/:node_id/promote", post(handle_promote_learner))
+        .route("/admin/transfer-leader", post(handle_transfer_leader))
+        .with_state(app_state)
+        .merge(server::raft_api::router(raft.clone()));
 
     // Start HTTP server
     let addr: SocketAddr = config.server_address().parse()?;
@@ -103,6 +234,19 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     });
     
+    // Start gRPC server
+    let grpc_addr: SocketAddr = config.grpc_address().parse()?;
+    let grpc_handle = tokio::spawn(async move {
+        info!("Starting gRPC server on {}", grpc_addr);
+        if let Err(e) = tonic::transport::Server::builder()
+            .add_service(grpc_server.service())
+            .serve(grpc_addr)
+            .await
+        {
+            error!("gRPC server error: {}", e);
+        }
+    });
+    
     // Wait for shutdown signal
     tokio::select! {
         _ = signal::ctrl_c() => {
@@ -111,151 +255,249 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         _ = http_handle => {
             error!("HTTP server terminated unexpectedly");
         }
+        _ = grpc_handle => {
+            error!("gRPC server terminated unexpectedly");
+        }
         _ = event_loop_handle => {
             error!("Event loop terminated unexpectedly");
         }
     }
 
     // Send shutdown event
-    let _ = event_tx.send(RaftEvent::Shutdown);
+    let _ = raft.shutdown().await;
 
     info!("Raft node shutting down");
     Ok(())
 }
 
+/// Start an event loop owning `node` that reaches its peers over `transport`
+fn spawn_event_loop<T: Transport>(node: RaftNode, transport: T) -> (JoinHandle<()>, RaftHandle) {
+    let (event_loop, raft) = RaftEventLoop::new(node, transport);
+    let handle = tokio::spawn(async move {
+        if let Err(e) = event_loop.run().await {
+            error!("Raft event loop error: {}", e);
+        }
+    });
+    (handle, raft)
+}
+
 /// Handle command submission
+///
+/// Commands that need the leader are answered according to the configured
+/// follower mode when they reach a follower.
 async fn handle_command(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(request): Json<CommandRequest>,
-) -> ResponseJson<CommandResponse> {
+) -> Response {
+    let consistency = match request.read_consistency() {
+        Ok(consistency) => consistency,
+        Err(error) => return ResponseJson(CommandResponse::error(error)).into_response(),
+    };
+    let session = match request.session() {
+        Ok(session) => session,
+        Err(error) => return ResponseJson(CommandResponse::error(error)).into_response(),
+    };
+
     // Convert HTTP request to state machine command
     let command = match request.command_type.as_str() {
         "SET" => {
-            if let Some(value) = request.value {
+            if let Some(value) = request.value.clone() {
                 Command::Set {
-                    key: request.key,
+                    key: request.key.clone(),
                     value,
                 }
             } else {
-                return ResponseJson(CommandResponse {
-                    success: false,
-                    result: None,
-                    error: Some("SET command requires a value".to_string()),
-                });
+                return ResponseJson(CommandResponse::error("SET command requires a value".to_string())).into_response();
             }
         }
-        "GET" => Command::Get { key: request.key },
-        "DELETE" => Command::Delete { key: request.key },
+        "GET" => Command::Get { key: request.key.clone() },
+        "DELETE" => Command::Delete { key: request.key.clone() },
         _ => {
-            return ResponseJson(CommandResponse {
-                success: false,
-                result: None,
-                error: Some(format!("Unknown command type: {}", request.command_type)),
-            });
+            return ResponseJson(CommandResponse::error(format!(
+                "Unknown command type: {}", request.command_type
+            ))).into_response();
         }
     };
 
-    // Serialize command
-    let command_bytes = match serde_json::to_vec(&command) {
-        Ok(bytes) => bytes,
-        Err(e) => {
-            return ResponseJson(CommandResponse {
-                success: false,
-                result: None,
-                error: Some(format!("Failed to serialize command: {}", e)),
-            });
-        }
-    };
+    if consistency.is_some() && !matches!(command, Command::Get { .. }) {
+        return ResponseJson(CommandResponse::error("Only GET takes a consistency level".to_string())).into_response();
+    }
 
-    // Submit command to Raft
-    let (response_tx, response_rx) = tokio::sync::oneshot::channel();
-    let event = RaftEvent::SubmitCommand {
-        command: command_bytes,
-        response_tx,
+    // Reads are served at the requested consistency; writes wait until committed and applied
+    let (result, read_mode) = match command {
+        Command::Get { .. } => match state.applier.read(command, consistency).await {
+            Ok(outcome) => (Ok((outcome.result, outcome.applied_index)), Some(outcome.mode)),
+            Err(e) => (Err(e), None),
+        },
+        _ => (state.applier.submit(command, session).await, None),
     };
 
-    if let Err(_) = state.event_tx.send(event) {
-        return ResponseJson(CommandResponse {
-            success: false,
-            result: None,
-            error: Some("Failed to submit command to Raft".to_string()),
-        });
+    if is_not_leader(&result) {
+        let body = serde_json::to_value(&request).ok();
+        return state.forwarder.not_leader("/command", body, &headers).await;
     }
+    command_response(result, read_mode)
+}
 
-    // Wait for Raft response
-    match response_rx.await {
-        Ok(Ok(_log_index)) => {
-            // Command was accepted by Raft, now apply it to state machine
-            let mut sm = state.state_machine.write().await;
-            match sm.apply(command).await {
-                Ok(result) => {
-                    let result_value = match result {
-                        state::state_machine::CommandResult::Success { value } => value,
-                        state::state_machine::CommandResult::Error { message } => {
-                            return ResponseJson(CommandResponse {
-                                success: false,
-                                result: None,
-                                error: Some(message),
-                            });
-                        }
-                    };
-
-                    ResponseJson(CommandResponse {
-                        success: true,
-                        result: result_value,
-                        error: None,
-                    })
-                }
-                Err(e) => ResponseJson(CommandResponse {
-                    success: false,
-                    result: None,
-                    error: Some(e.to_string()),
-                }),
-            }
-        }
-        Ok(Err(e)) => ResponseJson(CommandResponse {
-            success: false,
-            result: None,
-            error: Some(e.to_string()),
-        }),
-        Err(_) => ResponseJson(CommandResponse {
+/// Handle client session registration
+///
+/// The session's client ID is returned in `result`; pass it with a sequence
+/// number on each write so retries are applied at most once.
+async fn handle_register_session(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    let result = state.applier.submit(Command::RegisterSession, None).await;
+    if is_not_leader(&result) {
+        return state.forwarder.not_leader("/session", None, &headers).await;
+    }
+    command_response(result, None)
+}
+
+/// Turn the outcome of a command into a response
+fn command_response(
+    result: Result<(CommandResult, LogIndex), ServerError>,
+    read_mode: Option<ReadMode>,
+) -> Response {
+    match result {
+        Ok((CommandResult::Success { value }, applied_index)) => ResponseJson(CommandResponse {
+            success: true,
+            result: value,
+            error: None,
+            read_mode,
+            last_applied: Some(applied_index),
+        }).into_response(),
+        Ok((CommandResult::Error { message }, applied_index)) => ResponseJson(CommandResponse {
             success: false,
             result: None,
-            error: Some("Failed to receive response from Raft".to_string()),
-        }),
+            error: Some(message),
+            read_mode,
+            last_applied: Some(applied_index),
+        }).into_response(),
+        Err(e) => (error_status(&e), ResponseJson(CommandResponse::error(e.to_string()))).into_response(),
     }
 }
 
-/// Handle status requests
-async fn handle_status(State(state): State<AppState>) -> ResponseJson<NodeStatus> {
-    let (response_tx, response_rx) = tokio::sync::oneshot::channel();
-    let event = RaftEvent::GetStatus { response_tx };
+/// Handle membership change requests
+///
+/// Replies once the joint configuration has been appended on the leader; the
+/// change is complete when `/status` lists the new peers on every node.
+async fn handle_membership(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<MembershipRequest>,
+) -> Response {
+    let body = serde_json::to_value(&request).ok();
+    let result = state.raft.change_membership(request.nodes).await.map_err(ServerError::from);
+    if is_not_leader(&result) {
+        return state.forwarder.not_leader("/admin/membership", body, &headers).await;
+    }
+    membership_response(result)
+}
 
-    if let Err(_) = state.event_tx.send(event) {
-        // Return a default status if we can't get the real one
-        return ResponseJson(NodeStatus {
-            node_id: "unknown".to_string(),
-            state: raft_core::NodeState::Follower,
-            current_term: 0,
-            leader_id: None,
-            commit_index: 0,
-            last_applied: 0,
-            log_length: 0,
-            peers: vec![],
-        });
+/// Handle requests to add a non-voting learner
+async fn handle_add_learner(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<LearnerRequest>,
+) -> Response {
+    let body = serde_json::to_value(&request).ok();
+    let learner = PeerInfo {
+        node_id: request.node_id,
+        address: request.address,
+        voting: false,
+        grpc_address: request.grpc_address,
+    };
+    let result = state.raft.add_learner(learner).await.map_err(ServerError::from);
+    if is_not_leader(&result) {
+        return state.forwarder.not_leader("/admin/learners", body, &headers).await;
+    }
+    membership_response(result)
+}
+
+/// Handle requests to promote a caught-up learner to a voter
+async fn handle_promote_learner(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(node_id): Path<String>,
+) -> Response {
+    let path = format!("/admin/learners/{}/promote", node_id);
+    let result = state.raft.promote_learner(node_id).await.map_err(ServerError::from);
+    if is_not_leader(&result) {
+        return state.forwarder.not_leader(&path, None, &headers).await;
+    }
+    membership_response(result)
+}
+
+/// Handle leadership transfer requests
+///
+/// Replies once the target has started its election, or with an error if the
+/// transfer did not finish within an election timeout.
+async fn handle_transfer_leader(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<TransferLeaderRequest>,
+) -> Response {
+    let body = serde_json::to_value(&request).ok();
+    let result = state.raft.transfer_leadership(request.node_id).await.map_err(ServerError::from);
+    if is_not_leader(&result) {
+        return state.forwarder.not_leader("/admin/transfer-leader", body, &headers).await;
+    }
+
+    let status = result.as_ref().err().map_or(StatusCode::OK, error_status);
+    (status, ResponseJson(TransferLeaderResponse {
+        success: result.is_ok(),
+        error: result.err().map(|e| e.to_string()),
+    })).into_response()
+}
+
+/// Check whether a request failed only because this node is not the leader
+fn is_not_leader<T>(result: &Result<T, ServerError>) -> bool {
+    matches!(result, Err(ServerError::Raft(RaftError::NotLeader)))
+}
+
+/// Get the HTTP status for a failed request
+///
+/// Errors are reported in the body with 200 OK, except that an overloaded
+/// node answers 429 Too Many Requests so clients back off and retry.
+fn error_status(error: &ServerError) -> StatusCode {
+    if error.is_overloaded() {
+        StatusCode::TOO_MANY_REQUESTS
+    } else {
+        StatusCode::OK
     }
+}
 
-    match response_rx.await {
+/// Turn the outcome of a membership change into a response
+fn membership_response(result: Result<LogIndex, ServerError>) -> Response {
+    match result {
+        Ok(index) => ResponseJson(MembershipResponse {
+            success: true,
+            config_index: Some(index),
+            error: None,
+        }).into_response(),
+        Err(error) => (error_status(&error), ResponseJson(MembershipResponse {
+            success: false,
+            config_index: None,
+            error: Some(error.to_string()),
+        })).into_response(),
+    }
+}
+
+/// Handle status requests
+async fn handle_status(State(state): State<AppState>) -> ResponseJson<NodeStatus> {
+    match state.raft.status().await {
         Ok(status) => ResponseJson(status),
+        // Return a default status if we can't get the real one
         Err(_) => ResponseJson(NodeStatus {
             node_id: "unknown".to_string(),
             state: raft_core::NodeState::Follower,
             current_term: 0,
             leader_id: None,
+            leader_address: None,
             commit_index: 0,
             last_applied: 0,
             log_length: 0,
             peers: vec![],
+            learners: vec![],
         }),
     }
 }
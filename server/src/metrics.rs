@@ -11,6 +11,7 @@ pub struct RaftMetrics {
     pub commit_index: Gauge,
     pub last_applied: Gauge,
     pub log_size: Gauge,
+    pub event_queue_depth: Gauge,
     
     // Operation counters
     pub vote_requests_total: Counter,
@@ -32,6 +33,7 @@ impl RaftMetrics {
         let commit_index = Gauge::new("raft_commit_index", "Current commit index")?;
         let last_applied = Gauge::new("raft_last_applied", "Last applied log index")?;
         let log_size = Gauge::new("raft_log_size", "Total number of log entries")?;
+        let event_queue_depth = Gauge::new("raft_event_queue_depth", "Events waiting in the Raft event queue")?;
         
         let vote_requests_total = Counter::new("raft_vote_requests_total", "Total vote requests")?;
         let append_requests_total = Counter::new("raft_append_requests_total", "Total append requests")?;
@@ -50,6 +52,7 @@ impl RaftMetrics {
         registry.register(Box::new(commit_index.clone()))?;
         registry.register(Box::new(last_applied.clone()))?;
         registry.register(Box::new(log_size.clone()))?;
+        registry.register(Box::new(event_queue_depth.clone()))?;
         registry.register(Box::new(vote_requests_total.clone()))?;
         registry.register(Box::new(append_requests_total.clone()))?;
         registry.register(Box::new(commands_total.clone()))?;
@@ -63,6 +66,7 @@ impl RaftMetrics {
             commit_index,
             last_applied,
             log_size,
+            event_queue_depth,
             vote_requests_total,
             append_requests_total,
             commands_total,
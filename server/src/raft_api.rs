@@ -0,0 +1,113 @@
+//! HTTP endpoints for peer RPCs
+//!
+//! These are the routes `raft_core::HttpTransport` posts to. Each one hands the
+//! request to the local event loop through its `RaftHandle` and answers in the encoding the request
+//! came in: the versioned binary encoding or JSON. A binary request in a
+//! version this release cannot read is refused with 415 Unsupported Media
+//! Type, which makes the sender fall back to JSON.
+
+use axum::{
+    body::Bytes,
+    extract::{Json, State},
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Router,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::future::Future;
+use tracing::debug;
+
+use raft_core::codec::{self, WireMessage};
+use raft_core::transport::http::{
+    APPEND_PATH, BINARY_CONTENT_TYPE, PRE_VOTE_PATH, SNAPSHOT_PATH, TIMEOUT_NOW_PATH, VOTE_PATH,
+};
+use raft_core::{RaftError, RaftHandle, RaftResult};
+
+/// Routes serving peer RPCs through the event loop behind `raft`
+pub fn router(raft: RaftHandle) -> Router {
+    Router::new()
+        .route(VOTE_PATH, post(handle_vote))
+        .route(PRE_VOTE_PATH, post(handle_pre_vote))
+        .route(APPEND_PATH, post(handle_append))
+        .route(SNAPSHOT_PATH, post(handle_snapshot))
+        .route(TIMEOUT_NOW_PATH, post(handle_timeout_now))
+        .with_state(raft)
+}
+
+/// Encoding of a peer RPC body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Binary,
+    Json,
+}
+
+/// Decode a request body according to its content type
+fn decode<T: WireMessage + DeserializeOwned>(headers: &HeaderMap, body: &[u8]) -> Result<(T, Encoding), StatusCode> {
+    let content_type = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    if content_type == BINARY_CONTENT_TYPE {
+        match codec::decode(body) {
+            Ok(request) => Ok((request, Encoding::Binary)),
+            Err(RaftError::UnsupportedWireVersion { version }) => {
+                debug!("Refusing wire version {}", version);
+                Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+            }
+            Err(_) => Err(StatusCode::BAD_REQUEST),
+        }
+    } else if content_type.starts_with("application/json") {
+        serde_json::from_slice(body)
+            .map(|request| (request, Encoding::Json))
+            .map_err(|_| StatusCode::BAD_REQUEST)
+    } else {
+        Err(StatusCode::UNSUPPORTED_MEDIA_TYPE)
+    }
+}
+
+/// Hand a decoded request to the event loop and answer in the request's encoding
+///
+/// Answers 503 if the event loop is gone or failed to handle the request, so
+/// the sender treats it like any other unreachable peer.
+async fn dispatch<Req, Resp, F>(headers: &HeaderMap, body: &[u8], handle: impl FnOnce(Req) -> F) -> Response
+where
+    Req: WireMessage + DeserializeOwned,
+    Resp: WireMessage + Serialize,
+    F: Future<Output = RaftResult<Resp>>,
+{
+    let (request, encoding) = match decode(headers, body) {
+        Ok(decoded) => decoded,
+        Err(status) => return status.into_response(),
+    };
+
+    let Ok(response) = handle(request).await else {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    };
+
+    match encoding {
+        Encoding::Binary => ([(CONTENT_TYPE, BINARY_CONTENT_TYPE)], codec::encode(&response)).into_response(),
+        Encoding::Json => Json(response).into_response(),
+    }
+}
+
+async fn handle_vote(State(raft): State<RaftHandle>, headers: HeaderMap, body: Bytes) -> Response {
+    dispatch(&headers, &body, |request| raft.request_vote(request)).await
+}
+
+async fn handle_pre_vote(State(raft): State<RaftHandle>, headers: HeaderMap, body: Bytes) -> Response {
+    dispatch(&headers, &body, |request| raft.pre_vote(request)).await
+}
+
+async fn handle_append(State(raft): State<RaftHandle>, headers: HeaderMap, body: Bytes) -> Response {
+    dispatch(&headers, &body, |request| raft.append_entries(request)).await
+}
+
+async fn handle_snapshot(State(raft): State<RaftHandle>, headers: HeaderMap, body: Bytes) -> Response {
+    dispatch(&headers, &body, |request| raft.install_snapshot(request)).await
+}
+
+async fn handle_timeout_now(State(raft): State<RaftHandle>, headers: HeaderMap, body: Bytes) -> Response {
+    dispatch(&headers, &body, |request| raft.timeout_now(request)).await
+}
@@ -0,0 +1,661 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::{IntoResponse, Json, Response};
+    use axum::routing::post;
+    use axum::Router;
+    use serde_json::{json, Value};
+    use tokio::sync::{oneshot, watch, RwLock};
+    use tokio::task::JoinHandle;
+
+    use proto::raft::raft_service_client::RaftServiceClient;
+    use raft_core::{
+        AppendRequest, EntryType, LogEntry, GrpcTransport, HttpTransport, InMemoryNetwork, NodeConfig, NodeState, NodeStatus, PeerInfo,
+        RaftEventLoop, RaftHandle, RaftNode, ReadMode, Transport, VoteRequest,
+    };
+    use state::state_machine::{Command, CommandResult};
+    use state::{InMemoryKvStore, SessionCheck, SessionTable, StateMachine};
+
+    use crate::applier::{
+        decode_snapshot, encode_snapshot, Applier, ApplierHandle, MaxLag, Read, ReadConsistency, ReadOutcome, Submission,
+    };
+    use crate::config::FollowerMode;
+    use crate::error::ServerError;
+    use crate::forward::{LeaderForwarder, FORWARDED_HEADER};
+    use crate::grpc_server::RaftGrpcServer;
+    use crate::metrics::RaftMetrics;
+    use crate::raft_api;
+
+    fn create_test_config(node_id: &str) -> NodeConfig {
+        NodeConfig {
+            node_id: node_id.to_string(),
+            address: format!("127.0.0.1:500{}", node_id.chars().last().unwrap()),
+            grpc_address: None,
+            peers: vec![],
+            election_timeout_min: 150,
+            election_timeout_max: 300,
+            heartbeat_interval: 50,
+            max_append_entries: 100,
+            max_inflight_appends: 4,
+            snapshot_chunk_size: 64 * 1024,
+            max_learner_lag: 10,
+            pre_vote: false,
+            clock_drift: 10,
+            max_uncommitted_entries: 10_000,
+            max_uncommitted_bytes: 64 * 1024 * 1024,
+            event_queue_capacity: 1024,
+        }
+    }
+
+    /// Start a single-node cluster and wait until it leads
+    async fn start_leader() -> (RaftHandle, JoinHandle<()>) {
+        let network = InMemoryNetwork::new();
+        let (event_loop, raft) = RaftEventLoop::new(RaftNode::new(create_test_config("1")), network.transport("1".to_string()));
+        network.register("1".to_string(), raft.clone());
+        let handle = tokio::spawn(async move {
+            let _ = event_loop.run().await;
+        });
+
+        let mut status_rx = raft.subscribe();
+        tokio::time::timeout(Duration::from_secs(5), status_rx.wait_for(|status| status.state == NodeState::Leader))
+            .await
+            .expect("no leader was elected")
+            .unwrap();
+        (raft, handle)
+    }
+
+    fn create_applier(raft: &RaftHandle, read_mode: ReadMode) -> (Applier, ApplierHandle) {
+        let state_machine: Arc<RwLock<dyn StateMachine>> = Arc::new(RwLock::new(InMemoryKvStore::new()));
+        let metrics = Arc::new(RaftMetrics::new().unwrap());
+        Applier::new(raft.clone(), state_machine, 10_000, read_mode, Duration::from_secs(3600), 16, metrics)
+    }
+
+    fn set(key: &str, value: &str) -> Command {
+        Command::Set { key: key.to_string(), value: value.to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_submission_answered_after_its_entry_was_applied() {
+        let (raft, handle) = start_leader().await;
+        let (mut applier, _applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+
+        let (response_tx, response_rx) = oneshot::channel();
+        let submission = Submission { command: set("a", "1"), session: None, response_tx };
+        let submitted = applier.submit(submission).unwrap().await;
+        let (index, _) = *submitted.0.as_ref().unwrap();
+
+        // The entry is applied before the submission's answer is parked
+        let mut status_rx = raft.subscribe();
+        status_rx.wait_for(|status| status.commit_index >= index).await.unwrap();
+        applier.apply_committed().await;
+        applier.park(submitted);
+
+        let reply = tokio::time::timeout(Duration::from_secs(1), response_rx).await.expect("submitter hangs");
+        let (result, applied_index) = reply.unwrap().unwrap();
+        assert!(matches!(result, CommandResult::Success { .. }));
+        assert_eq!(applied_index, index);
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_waiter_of_leader_that_stepped_down_within_its_term() {
+        let network = InMemoryNetwork::new();
+        let ids = ["1", "2", "3"];
+        let mut nodes = Vec::new();
+        for id in ids {
+            let mut config = create_test_config(id);
+            config.peers = ids
+                .iter()
+                .filter(|peer| **peer != id)
+                .map(|peer| PeerInfo {
+                    node_id: peer.to_string(),
+                    address: create_test_config(peer).address,
+                    voting: true,
+                    grpc_address: None,
+                })
+                .collect();
+            let (event_loop, raft) = RaftEventLoop::new(RaftNode::new(config), network.transport(id.to_string()));
+            network.register(id.to_string(), raft.clone());
+            let handle = tokio::spawn(async move {
+                let _ = event_loop.run().await;
+            });
+            nodes.push((id.to_string(), raft, handle));
+        }
+
+        let mut leader = None;
+        for _ in 0..200 {
+            leader = nodes.iter().find(|(_, raft, _)| raft.subscribe().borrow().state == NodeState::Leader);
+            if leader.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let (leader_id, raft, _) = leader.expect("no leader was elected");
+
+        // Cut off from its peers, the leader appends an entry that cannot commit
+        network.disconnect(leader_id);
+        let (mut applier, _applier_handle) = create_applier(raft, ReadMode::ReadIndex);
+        let (response_tx, response_rx) = oneshot::channel();
+        let submission = Submission { command: set("a", "1"), session: None, response_tx };
+        let submitted = applier.submit(submission).unwrap().await;
+        let (index, term) = *submitted.0.as_ref().unwrap();
+        applier.park(submitted);
+
+        // CheckQuorum makes it step down without learning of a newer term
+        let mut status_rx = raft.subscribe();
+        tokio::time::timeout(Duration::from_secs(5), status_rx.wait_for(|status| status.state != NodeState::Leader))
+            .await
+            .expect("leader did not step down")
+            .unwrap();
+        assert_eq!(status_rx.borrow().current_term, term);
+        applier.fail_stale_waiters();
+
+        let reply = tokio::time::timeout(Duration::from_secs(1), response_rx).await.expect("submitter hangs");
+        assert!(matches!(reply.unwrap(), Err(ServerError::SteppedDown { index: failed }) if failed == index));
+        for (_, _, handle) in &nodes {
+            handle.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_full_apply_queue_rejects_client_requests() {
+        let (raft, handle) = start_leader().await;
+        // The apply loop is not running, so nothing drains its queues
+        let (_applier, applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+
+        for i in 0..16 {
+            let applier_handle = applier_handle.clone();
+            tokio::spawn(async move { applier_handle.submit(set("key", &i.to_string()), None).await });
+        }
+        tokio::task::yield_now().await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let result = applier_handle.submit(set("key", "overflow"), None).await;
+        assert!(matches!(result, Err(ServerError::Overloaded(_))));
+        assert!(result.unwrap_err().is_overloaded());
+        handle.abort();
+    }
+
+    /// Serve the peer RPCs of `raft` over HTTP and gRPC on ephemeral ports,
+    /// returning the node as a peer reachable through either transport
+    async fn serve_peer_rpcs(raft: &RaftHandle) -> PeerInfo {
+        let http_listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let http_address = http_listener.local_addr().unwrap().to_string();
+        let app = raft_api::router(raft.clone());
+        tokio::spawn(async move {
+            let _ = axum::serve(http_listener, app).await;
+        });
+
+        let (_applier, applier_handle) = create_applier(raft, ReadMode::ReadIndex);
+        let grpc_address = serve_grpc(raft, applier_handle).await;
+
+        PeerInfo::new("1", http_address).with_grpc_address(grpc_address)
+    }
+
+    /// Serve `RaftService` for `raft` on an ephemeral port, returning its address
+    async fn serve_grpc(raft: &RaftHandle, applier_handle: ApplierHandle) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let incoming = tonic::transport::server::TcpIncoming::from_listener(listener, true, None).unwrap();
+        let service = RaftGrpcServer::new(raft.clone(), applier_handle, Arc::new(RaftMetrics::new().unwrap())).service();
+        tokio::spawn(async move {
+            let _ = tonic::transport::Server::builder().add_service(service).serve_with_incoming(incoming).await;
+        });
+        address
+    }
+
+    /// Win the node's vote for a candidate in a later term, then replicate to
+    /// it as that term's leader
+    async fn vote_and_append<T: Transport>(raft: &RaftHandle, transport: T) {
+        let peer = serve_peer_rpcs(raft).await;
+        let term = raft.status().await.unwrap().current_term + 1;
+
+        let vote = VoteRequest {
+            term,
+            candidate_id: "2".to_string(),
+            last_log_index: 100,
+            last_log_term: term,
+            leadership_transfer: true,
+        };
+        let response = transport.request_vote(&peer, &vote).await.unwrap();
+        assert!(response.vote_granted);
+        assert_eq!(response.term, term);
+
+        let append = AppendRequest {
+            term,
+            leader_id: "2".to_string(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![],
+            leader_commit: 0,
+        };
+        let response = transport.append_entries(&peer, &append).await.unwrap();
+        assert!(response.success);
+        assert_eq!(response.term, term);
+
+        let status = raft.status().await.unwrap();
+        assert_eq!(status.state, NodeState::Follower);
+        assert_eq!(status.leader_id.as_deref(), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn test_vote_and_append_over_http() {
+        let (raft, handle) = start_leader().await;
+        vote_and_append(&raft, HttpTransport::new()).await;
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_vote_and_append_over_grpc() {
+        let (raft, handle) = start_leader().await;
+        vote_and_append(&raft, GrpcTransport::new()).await;
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_grpc_transport_needs_a_grpc_address() {
+        let (raft, handle) = start_leader().await;
+        let mut peer = serve_peer_rpcs(&raft).await;
+        peer.grpc_address = None;
+
+        let vote = VoteRequest {
+            term: 1,
+            candidate_id: "2".to_string(),
+            last_log_index: 0,
+            last_log_term: 0,
+            leadership_transfer: false,
+        };
+        assert!(GrpcTransport::new().request_vote(&peer, &vote).await.is_err());
+        handle.abort();
+    }
+
+    #[test]
+    fn test_snapshot_keeps_sessions() {
+        let mut sessions = SessionTable::new();
+        sessions.register("7".to_string());
+        sessions.record("7", 2, CommandResult::Success { value: Some("cached".to_string()) });
+        sessions.register("9".to_string());
+
+        let data = encode_snapshot(&sessions, b"state machine".to_vec()).unwrap();
+        let (restored, state) = decode_snapshot(&data).unwrap();
+
+        assert_eq!(state, b"state machine");
+        assert_eq!(restored.len(), 2);
+        assert!(matches!(restored.check("7", 2), SessionCheck::Duplicate(_)));
+        assert!(matches!(restored.check("7", 1), SessionCheck::Rejected(_)));
+        assert!(matches!(restored.check("9", 1), SessionCheck::Apply));
+    }
+
+    #[test]
+    fn test_truncated_snapshot_is_rejected() {
+        let data = encode_snapshot(&SessionTable::new(), b"state machine".to_vec()).unwrap();
+
+        assert!(decode_snapshot(&data[..4]).is_err());
+        let mut oversized = data.clone();
+        oversized[..8].copy_from_slice(&(data.len() as u64).to_le_bytes());
+        assert!(decode_snapshot(&oversized).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_applier_deduplicates_session_commands() {
+        let (raft, handle) = start_leader().await;
+        let (applier, applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+        let applier_task = tokio::spawn(applier.run());
+
+        let (registered, index) = applier_handle.submit(Command::RegisterSession, None).await.unwrap();
+        let CommandResult::Success { value: Some(client_id) } = registered else {
+            panic!("session was not registered: {:?}", registered);
+        };
+        assert_eq!(client_id, index.to_string());
+
+        applier_handle.submit(set("key", "value"), None).await.unwrap();
+        let delete = Command::Delete { key: "key".to_string() };
+        let session = Some((client_id.clone(), 1));
+        let (first, _) = applier_handle.submit(delete.clone(), session.clone()).await.unwrap();
+        assert!(matches!(first, CommandResult::Success { .. }));
+
+        // Applied again, the delete would fail: the key is gone
+        let (retried, _) = applier_handle.submit(delete.clone(), session).await.unwrap();
+        assert!(matches!(retried, CommandResult::Success { .. }));
+
+        let (stale, _) = applier_handle.submit(set("key", "stale"), Some((client_id.clone(), 0))).await.unwrap();
+        assert!(matches!(stale, CommandResult::Error { .. }));
+
+        let (unknown, _) = applier_handle.submit(set("key", "unknown"), Some(("missing".to_string(), 1))).await.unwrap();
+        assert!(matches!(unknown, CommandResult::Error { .. }));
+
+        applier_task.abort();
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_applier_rejects_commands_of_expired_sessions() {
+        let (raft, handle) = start_leader().await;
+        let (applier, applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+        let applier_task = tokio::spawn(applier.run());
+
+        let (registered, _) = applier_handle.submit(Command::RegisterSession, None).await.unwrap();
+        let CommandResult::Success { value: Some(client_id) } = registered else {
+            panic!("session was not registered: {:?}", registered);
+        };
+        let (first, _) = applier_handle.submit(set("key", "1"), Some((client_id.clone(), 1))).await.unwrap();
+        assert!(matches!(first, CommandResult::Success { .. }));
+
+        let expire = Command::ExpireSessions { client_ids: vec![client_id.clone()] };
+        applier_handle.submit(expire, None).await.unwrap();
+
+        // Neither a retry nor a new command is accepted once the session is gone
+        let (retried, _) = applier_handle.submit(set("key", "1"), Some((client_id.clone(), 1))).await.unwrap();
+        assert!(matches!(retried, CommandResult::Error { .. }));
+        let (next, _) = applier_handle.submit(set("key", "2"), Some((client_id, 2))).await.unwrap();
+        assert!(matches!(next, CommandResult::Error { .. }));
+
+        let read = applier_handle.read(Command::Get { key: "key".to_string() }, None).await.unwrap();
+        assert!(matches!(read.result, CommandResult::Success { value: Some(ref value) } if value == "1"));
+
+        applier_task.abort();
+        handle.abort();
+    }
+
+    /// Serve a bounded-staleness read of `key` straight from the applier
+    async fn bounded_read(applier: &Applier, key: &str, max_lag: MaxLag) -> Result<ReadOutcome, ServerError> {
+        let (response_tx, response_rx) = oneshot::channel();
+        let read = Read {
+            command: Command::Get { key: key.to_string() },
+            consistency: ReadConsistency::Bounded(max_lag),
+            response_tx,
+        };
+        applier.serve_local_read(read).await;
+        response_rx.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bounded_read_refused_beyond_its_lag() {
+        let (raft, handle) = start_leader().await;
+        let mut last_index = 0;
+        for i in 0..3 {
+            let command = serde_json::to_vec(&set("key", &i.to_string())).unwrap();
+            last_index = raft.submit_command(command, None, None).await.unwrap().0;
+        }
+        let mut status_rx = raft.subscribe();
+        status_rx.wait_for(|status| status.commit_index >= last_index).await.unwrap();
+
+        // Nothing is applied yet: the applier trails the commit index by every entry
+        let (mut applier, _applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+        let refused = bounded_read(&applier, "key", MaxLag::Entries(1)).await;
+        assert!(matches!(refused, Err(ServerError::StaleRead(_))));
+
+        let served = bounded_read(&applier, "key", MaxLag::Entries(last_index)).await.unwrap();
+        assert_eq!(served.mode, ReadMode::Local);
+        assert_eq!(served.applied_index, 0);
+        assert!(matches!(served.result, CommandResult::Error { .. }));
+
+        applier.apply_committed().await;
+        let served = bounded_read(&applier, "key", MaxLag::Entries(0)).await.unwrap();
+        assert_eq!(served.applied_index, last_index);
+        assert!(matches!(served.result, CommandResult::Success { value: Some(ref value) } if value == "2"));
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_linearizable_read_goes_through_read_index() {
+        let (raft, handle) = start_leader().await;
+        let (applier, applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+        let applier_task = tokio::spawn(applier.run());
+
+        let (_, index) = applier_handle.submit(set("key", "value"), None).await.unwrap();
+        let get = Command::Get { key: "key".to_string() };
+
+        for consistency in [Some(ReadConsistency::Linearizable), None] {
+            let read = applier_handle.read(get.clone(), consistency).await.unwrap();
+            assert_eq!(read.mode, ReadMode::ReadIndex);
+            assert!(read.applied_index >= index);
+            assert!(matches!(read.result, CommandResult::Success { value: Some(ref value) } if value == "value"));
+        }
+
+        applier_task.abort();
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_linearizable_read_goes_through_log_in_log_mode() {
+        let (raft, handle) = start_leader().await;
+        let (applier, applier_handle) = create_applier(&raft, ReadMode::Log);
+        let applier_task = tokio::spawn(applier.run());
+
+        let (_, index) = applier_handle.submit(set("key", "value"), None).await.unwrap();
+        let read = applier_handle.read(Command::Get { key: "key".to_string() }, None).await.unwrap();
+        assert_eq!(read.mode, ReadMode::Log);
+        assert!(read.applied_index > index);
+
+        applier_task.abort();
+        handle.abort();
+    }
+
+    /// Serve a stand-in leader that echoes `/command` bodies with 201 Created
+    async fn start_echo_leader() -> String {
+        async fn echo(headers: HeaderMap, Json(body): Json<Value>) -> Response {
+            let forwarded = headers.contains_key(FORWARDED_HEADER);
+            (StatusCode::CREATED, Json(json!({ "echo": body, "forwarded": forwarded }))).into_response()
+        }
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let app = Router::new().route("/command", post(echo));
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        address
+    }
+
+    /// A forwarder on a follower that knows `leader_address` as node 1's address
+    fn follower_forwarder(mode: FollowerMode, leader_address: Option<String>) -> LeaderForwarder {
+        let status = NodeStatus {
+            node_id: "2".to_string(),
+            state: NodeState::Follower,
+            current_term: 1,
+            leader_id: Some("1".to_string()),
+            leader_address,
+            commit_index: 0,
+            last_applied: 0,
+            log_length: 0,
+            peers: vec!["1".to_string()],
+            learners: vec![],
+        };
+        let (_status_tx, status_rx) = watch::channel(status);
+        LeaderForwarder::new(mode, status_rx)
+    }
+
+    async fn body_json(response: Response) -> Value {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    fn forwarded_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(FORWARDED_HEADER, "1".parse().unwrap());
+        headers
+    }
+
+    #[tokio::test]
+    async fn test_forwarder_proxies_to_leader() {
+        let leader = start_echo_leader().await;
+        let forwarder = follower_forwarder(FollowerMode::Proxy, Some(leader));
+
+        let body = json!({ "command": "set" });
+        let response = forwarder.not_leader("/command", Some(body.clone()), &HeaderMap::new()).await;
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+        let relayed = body_json(response).await;
+        assert_eq!(relayed["echo"], body);
+        assert_eq!(relayed["forwarded"], true);
+    }
+
+    #[tokio::test]
+    async fn test_forwarder_redirects_to_leader() {
+        let forwarder = follower_forwarder(FollowerMode::Redirect, Some("10.0.0.1:8080".to_string()));
+
+        let response = forwarder.not_leader("/command", None, &HeaderMap::new()).await;
+
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(response.headers()[header::LOCATION], "http://10.0.0.1:8080/command");
+        let refusal = body_json(response).await;
+        assert_eq!(refusal["leader_id"], "1");
+        assert_eq!(refusal["leader_address"], "10.0.0.1:8080");
+    }
+
+    #[tokio::test]
+    async fn test_forwarder_rejects_with_leader() {
+        let forwarder = follower_forwarder(FollowerMode::Reject, Some("10.0.0.1:8080".to_string()));
+
+        let response = forwarder.not_leader("/command", None, &HeaderMap::new()).await;
+
+        assert_eq!(response.status(), StatusCode::MISDIRECTED_REQUEST);
+        assert!(!response.headers().contains_key(header::LOCATION));
+        let refusal = body_json(response).await;
+        assert_eq!(refusal["success"], false);
+        assert_eq!(refusal["leader_id"], "1");
+        assert_eq!(refusal["leader_address"], "10.0.0.1:8080");
+    }
+
+    #[tokio::test]
+    async fn test_forwarder_never_forwards_twice() {
+        // The stand-in leader would answer 201 if the request reached it again
+        let leader = start_echo_leader().await;
+        for mode in [FollowerMode::Proxy, FollowerMode::Redirect] {
+            let forwarder = follower_forwarder(mode, Some(leader.clone()));
+            let response = forwarder.not_leader("/command", Some(json!({})), &forwarded_headers()).await;
+
+            assert_eq!(response.status(), StatusCode::MISDIRECTED_REQUEST);
+            assert!(!response.headers().contains_key(header::LOCATION));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_forwarder_without_leader_address_is_unavailable() {
+        let forwarder = follower_forwarder(FollowerMode::Proxy, None);
+
+        let response = forwarder.not_leader("/command", None, &HeaderMap::new()).await;
+
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body_json(response).await["leader_id"], "1");
+    }
+
+    async fn grpc_client(address: &str) -> RaftServiceClient<tonic::transport::Channel> {
+        RaftServiceClient::connect(format!("http://{}", address)).await.unwrap()
+    }
+
+    fn submit_request(command: &Command) -> proto::SubmitCommandRequest {
+        proto::SubmitCommandRequest {
+            command: serde_json::to_vec(command).unwrap(),
+            client_id: String::new(),
+            sequence_number: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_grpc_service_submits_commands_and_reports_status() {
+        let (raft, handle) = start_leader().await;
+        let (applier, applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+        let applier_task = tokio::spawn(applier.run());
+        let mut client = grpc_client(&serve_grpc(&raft, applier_handle).await).await;
+
+        let set = client.submit_command(submit_request(&set("key", "value"))).await.unwrap().into_inner();
+        assert!(set.success, "{}", set.error);
+
+        let get = Command::Get { key: "key".to_string() };
+        let got = client.submit_command(submit_request(&get)).await.unwrap().into_inner();
+        assert!(got.success, "{}", got.error);
+        assert_eq!(got.result, b"value");
+
+        let missing = Command::Get { key: "missing".to_string() };
+        let missing = client.submit_command(submit_request(&missing)).await.unwrap().into_inner();
+        assert!(!missing.success);
+        assert!(!missing.error.is_empty());
+
+        let status = client.get_status(proto::GetStatusRequest {}).await.unwrap().into_inner();
+        let local = raft.status().await.unwrap();
+        assert_eq!(status.state, proto::NodeState::Leader as i32);
+        assert_eq!(status.node_id, "1");
+        assert_eq!(status.leader_id, "1");
+        assert_eq!(status.current_term, local.current_term);
+        assert_eq!(status.commit_index, local.commit_index);
+        assert!(status.last_applied >= 2);
+        assert!(status.peers.is_empty());
+
+        applier_task.abort();
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_grpc_service_appends_entries_unchanged() {
+        let (raft, handle) = start_leader().await;
+        // The apply loop is not running, so appended entries stay to be applied
+        let (_applier, applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+        let mut client = grpc_client(&serve_grpc(&raft, applier_handle).await).await;
+
+        let status = raft.status().await.unwrap();
+        let (term, last_index) = (status.current_term, status.commit_index);
+        let entries = vec![
+            LogEntry {
+                index: last_index + 1,
+                term: term + 1,
+                entry_type: EntryType::Command,
+                data: serde_json::to_vec(&set("key", "value")).unwrap().into(),
+                client_id: Some("7".to_string()),
+                sequence_number: Some(3),
+            },
+            LogEntry {
+                index: last_index + 2,
+                term: term + 1,
+                entry_type: EntryType::NoOp,
+                data: Default::default(),
+                client_id: None,
+                sequence_number: None,
+            },
+        ];
+        let request = AppendRequest {
+            term: term + 1,
+            leader_id: "2".to_string(),
+            prev_log_index: last_index,
+            prev_log_term: term,
+            entries: entries.clone(),
+            leader_commit: last_index + 2,
+        };
+
+        let response = client.append_entries(proto::AppendEntriesRequest::from(&request)).await.unwrap().into_inner();
+        assert!(response.success);
+        assert_eq!(response.term, term + 1);
+
+        let applied = raft.entries_to_apply().await.unwrap();
+        assert_eq!(&applied[applied.len() - 2..], &entries[..]);
+        let status = raft.status().await.unwrap();
+        assert_eq!(status.state, NodeState::Follower);
+        assert_eq!(status.leader_id.as_deref(), Some("2"));
+        handle.abort();
+    }
+
+    #[tokio::test]
+    async fn test_grpc_service_answers_stale_vote() {
+        let (raft, handle) = start_leader().await;
+        let (_applier, applier_handle) = create_applier(&raft, ReadMode::ReadIndex);
+        let mut client = grpc_client(&serve_grpc(&raft, applier_handle).await).await;
+
+        let term = raft.status().await.unwrap().current_term;
+        let request = proto::RequestVoteRequest {
+            term: 0,
+            candidate_id: "2".to_string(),
+            last_log_index: 0,
+            last_log_term: 0,
+            leadership_transfer: true,
+        };
+        let response = client.request_vote(request).await.unwrap().into_inner();
+        assert!(!response.vote_granted);
+        assert_eq!(response.term, term);
+        assert_eq!(raft.status().await.unwrap().state, NodeState::Leader);
+        handle.abort();
+    }
+}
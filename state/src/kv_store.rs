@@ -66,6 +66,29 @@ impl StateMachine for InMemoryKvStore {
                     message: "Custom commands not supported by KV store".to_string() 
                 })
             }
+            Command::RegisterSession | Command::ExpireSessions { .. } => {
+                Err(StateError::InvalidCommand(
+                    "session commands are handled by the session table".to_string()
+                ))
+            }
+        }
+    }
+    
+    async fn query(&self, command: Command) -> StateResult<CommandResult> {
+        match command {
+            Command::Get { key } => {
+                match self.data.get(&key) {
+                    Some(value) => Ok(CommandResult::Success { 
+                        value: Some(value.clone()) 
+                    }),
+                    None => Ok(CommandResult::Error { 
+                        message: format!("Key '{}' not found", key) 
+                    }),
+                }
+            }
+            _ => Ok(CommandResult::Error { 
+                message: "Only GET can be served as a read-only query".to_string() 
+            }),
         }
     }
     
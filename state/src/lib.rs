@@ -7,13 +7,19 @@
 
 pub mod state_machine;
 pub mod kv_store;
+pub mod session;
 pub mod error;
 
 #[cfg(feature = "rocksdb-backend")]
 pub mod rocksdb_store;
 
+#[cfg(test)]
+#[allow(clippy::module_inception)]
+mod tests;
+
 pub use state_machine::StateMachine;
 pub use kv_store::InMemoryKvStore;
+pub use session::{SessionTable, SessionCheck};
 pub use error::StateError;
 
 #[cfg(feature = "rocksdb-backend")]
@@ -79,6 +79,32 @@ impl StateMachine for RocksDbStore {
                     message: "Custom commands not supported by RocksDB store".to_string() 
                 })
             }
+            Command::RegisterSession | Command::ExpireSessions { .. } => {
+                Err(StateError::InvalidCommand(
+                    "session commands are handled by the session table".to_string()
+                ))
+            }
+        }
+    }
+    
+    async fn query(&self, command: Command) -> StateResult<CommandResult> {
+        match command {
+            Command::Get { key } => {
+                match self.db.get(key.as_bytes())? {
+                    Some(value) => {
+                        let value_str = String::from_utf8_lossy(&value).to_string();
+                        Ok(CommandResult::Success { 
+                            value: Some(value_str) 
+                        })
+                    }
+                    None => Ok(CommandResult::Error { 
+                        message: format!("Key '{}' not found", key) 
+                    }),
+                }
+            }
+            _ => Ok(CommandResult::Error { 
+                message: "Only GET can be served as a read-only query".to_string() 
+            }),
         }
     }
     
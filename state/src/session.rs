@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::state_machine::CommandResult;
+
+/// What the session table says about a client command before it is applied
+#[derive(Debug, Clone)]
+pub enum SessionCheck {
+    /// A new sequence number: apply the command
+    Apply,
+    /// Already applied: answer with the cached result instead of applying it again
+    Duplicate(CommandResult),
+    /// The session is unknown or the sequence number is older than the last one
+    Rejected(String),
+}
+
+/// The last command applied for one client
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Session {
+    last_sequence: u64,
+    last_result: Option<CommandResult>,
+}
+
+/// Client sessions, replicated through the log so every node deduplicates alike
+///
+/// Each session remembers the last sequence number applied for its client and
+/// the result it produced, which makes client retries exactly-once. Sequence
+/// numbers start at 1. Sessions are opened and expired by log entries and
+/// travel with snapshots.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionTable {
+    sessions: HashMap<String, Session>,
+}
+
+impl SessionTable {
+    /// Create an empty session table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Open a session for a client
+    pub fn register(&mut self, client_id: String) {
+        self.sessions.entry(client_id).or_default();
+    }
+
+    /// Close the given sessions; unknown IDs are ignored
+    pub fn expire(&mut self, client_ids: &[String]) {
+        for client_id in client_ids {
+            self.sessions.remove(client_id);
+        }
+    }
+
+    /// Decide what to do with a client command
+    pub fn check(&self, client_id: &str, sequence_number: u64) -> SessionCheck {
+        let Some(session) = self.sessions.get(client_id) else {
+            return SessionCheck::Rejected(format!("Session '{}' is unknown or expired", client_id));
+        };
+
+        match (&session.last_result, sequence_number.cmp(&session.last_sequence)) {
+            (_, std::cmp::Ordering::Greater) => SessionCheck::Apply,
+            (Some(result), std::cmp::Ordering::Equal) => SessionCheck::Duplicate(result.clone()),
+            _ => SessionCheck::Rejected(format!(
+                "Sequence number {} of session '{}' is not after {}",
+                sequence_number, client_id, session.last_sequence
+            )),
+        }
+    }
+
+    /// Remember the result of a command applied for a client
+    pub fn record(&mut self, client_id: &str, sequence_number: u64, result: CommandResult) {
+        if let Some(session) = self.sessions.get_mut(client_id) {
+            session.last_sequence = sequence_number;
+            session.last_result = Some(result);
+        }
+    }
+
+    /// Check if a session is open
+    pub fn contains(&self, client_id: &str) -> bool {
+        self.sessions.contains_key(client_id)
+    }
+
+    /// Get the IDs of all open sessions
+    pub fn client_ids(&self) -> impl Iterator<Item = &String> {
+        self.sessions.keys()
+    }
+
+    /// Get the number of open sessions
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    /// Check if there are no open sessions
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+}
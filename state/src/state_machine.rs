@@ -16,6 +16,10 @@ pub enum Command {
     Delete { key: String },
     /// Custom command with arbitrary data
     Custom { data: Vec<u8> },
+    /// Open a client session; its ID is the index of this command's log entry
+    RegisterSession,
+    /// Close client sessions that have been idle too long
+    ExpireSessions { client_ids: Vec<String> },
 }
 
 /// Result of applying a command to the state machine
@@ -33,6 +37,9 @@ pub trait StateMachine: Send + Sync {
     /// Apply a command to the state machine
     async fn apply(&mut self, command: Command) -> StateResult<CommandResult>;
     
+    /// Answer a read-only command from the current state without changing it
+    async fn query(&self, command: Command) -> StateResult<CommandResult>;
+    
     /// Create a snapshot of the current state
     async fn snapshot(&self) -> StateResult<Vec<u8>>;
     
@@ -0,0 +1,92 @@
+#[cfg(test)]
+mod tests {
+    use crate::session::{SessionCheck, SessionTable};
+    use crate::state_machine::CommandResult;
+
+    fn success(value: &str) -> CommandResult {
+        CommandResult::Success { value: Some(value.to_string()) }
+    }
+
+    fn session_with(client_id: &str, sequence_number: u64, result: CommandResult) -> SessionTable {
+        let mut sessions = SessionTable::new();
+        sessions.register(client_id.to_string());
+        sessions.record(client_id, sequence_number, result);
+        sessions
+    }
+
+    #[test]
+    fn test_new_session_applies_first_sequence_number() {
+        let mut sessions = SessionTable::new();
+        sessions.register("c1".to_string());
+
+        assert!(matches!(sessions.check("c1", 1), SessionCheck::Apply));
+        // Sequence numbers start at 1
+        assert!(matches!(sessions.check("c1", 0), SessionCheck::Rejected(_)));
+    }
+
+    #[test]
+    fn test_duplicate_sequence_number_returns_cached_result() {
+        let sessions = session_with("c1", 3, success("first"));
+
+        match sessions.check("c1", 3) {
+            SessionCheck::Duplicate(CommandResult::Success { value }) => assert_eq!(value.as_deref(), Some("first")),
+            other => panic!("expected the cached result, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stale_sequence_number_is_rejected() {
+        let sessions = session_with("c1", 3, success("third"));
+
+        assert!(matches!(sessions.check("c1", 2), SessionCheck::Rejected(_)));
+        assert!(matches!(sessions.check("c1", 1), SessionCheck::Rejected(_)));
+    }
+
+    #[test]
+    fn test_out_of_order_sequence_numbers() {
+        let mut sessions = SessionTable::new();
+        sessions.register("c1".to_string());
+
+        // A later command overtakes an earlier one: gaps are allowed...
+        assert!(matches!(sessions.check("c1", 5), SessionCheck::Apply));
+        sessions.record("c1", 5, success("fifth"));
+
+        // ...but the overtaken command can no longer be applied
+        assert!(matches!(sessions.check("c1", 4), SessionCheck::Rejected(_)));
+        assert!(matches!(sessions.check("c1", 6), SessionCheck::Apply));
+    }
+
+    #[test]
+    fn test_expired_session_is_rejected() {
+        let mut sessions = session_with("c1", 1, success("first"));
+        sessions.register("c2".to_string());
+
+        sessions.expire(&["c1".to_string(), "unknown".to_string()]);
+
+        assert!(!sessions.contains("c1"));
+        assert!(sessions.contains("c2"));
+        assert!(matches!(sessions.check("c1", 1), SessionCheck::Rejected(_)));
+        assert!(matches!(sessions.check("c1", 2), SessionCheck::Rejected(_)));
+
+        // Recording against an expired session does not bring it back
+        sessions.record("c1", 2, success("second"));
+        assert!(!sessions.contains("c1"));
+    }
+
+    #[test]
+    fn test_unknown_session_is_rejected() {
+        let sessions = SessionTable::new();
+
+        assert!(matches!(sessions.check("c1", 1), SessionCheck::Rejected(_)));
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn test_register_keeps_existing_session() {
+        let mut sessions = session_with("c1", 2, success("second"));
+        sessions.register("c1".to_string());
+
+        assert_eq!(sessions.len(), 1);
+        assert!(matches!(sessions.check("c1", 2), SessionCheck::Duplicate(_)));
+    }
+}